@@ -0,0 +1,104 @@
+//! which of the game's keys ggez says are down
+
+use ggez::Context;
+use ggez::input::keyboard::KeyCode;
+use ggez::input::mouse::MouseButton;
+use shared::{Key, pad_button};
+
+/// Every key that's down, as the game names them. A gamepad button counts if it's down on any
+/// of the gamepads plugged in.
+pub fn held_keys(ctx: &Context) -> Vec<Key> {
+    Key::all()
+        .filter(|&key| match key {
+            Key::MouseLeft => ctx.mouse.button_pressed(MouseButton::Left),
+            Key::MouseRight => ctx.mouse.button_pressed(MouseButton::Right),
+            Key::MouseMiddle => ctx.mouse.button_pressed(MouseButton::Middle),
+            _ => match pad_button(key) {
+                Some(button) => ctx
+                    .gamepad
+                    .gamepads()
+                    .any(|(_, pad)| pad.is_pressed(button)),
+                None => key_code(key).is_some_and(|code| ctx.keyboard.is_key_pressed(code)),
+            },
+        })
+        .collect()
+}
+
+fn key_code(key: Key) -> Option<KeyCode> {
+    Some(match key {
+        Key::A => KeyCode::A,
+        Key::B => KeyCode::B,
+        Key::C => KeyCode::C,
+        Key::D => KeyCode::D,
+        Key::E => KeyCode::E,
+        Key::F => KeyCode::F,
+        Key::G => KeyCode::G,
+        Key::H => KeyCode::H,
+        Key::I => KeyCode::I,
+        Key::J => KeyCode::J,
+        Key::K => KeyCode::K,
+        Key::L => KeyCode::L,
+        Key::M => KeyCode::M,
+        Key::N => KeyCode::N,
+        Key::O => KeyCode::O,
+        Key::P => KeyCode::P,
+        Key::Q => KeyCode::Q,
+        Key::R => KeyCode::R,
+        Key::S => KeyCode::S,
+        Key::T => KeyCode::T,
+        Key::U => KeyCode::U,
+        Key::V => KeyCode::V,
+        Key::W => KeyCode::W,
+        Key::X => KeyCode::X,
+        Key::Y => KeyCode::Y,
+        Key::Z => KeyCode::Z,
+        Key::Num0 => KeyCode::Key0,
+        Key::Num1 => KeyCode::Key1,
+        Key::Num2 => KeyCode::Key2,
+        Key::Num3 => KeyCode::Key3,
+        Key::Num4 => KeyCode::Key4,
+        Key::Num5 => KeyCode::Key5,
+        Key::Num6 => KeyCode::Key6,
+        Key::Num7 => KeyCode::Key7,
+        Key::Num8 => KeyCode::Key8,
+        Key::Num9 => KeyCode::Key9,
+        Key::Up => KeyCode::Up,
+        Key::Down => KeyCode::Down,
+        Key::Left => KeyCode::Left,
+        Key::Right => KeyCode::Right,
+        Key::Space => KeyCode::Space,
+        Key::Tab => KeyCode::Tab,
+        Key::Enter => KeyCode::Return,
+        Key::Escape => KeyCode::Escape,
+        Key::Backspace => KeyCode::Back,
+        Key::LeftShift => KeyCode::LShift,
+        Key::RightShift => KeyCode::RShift,
+        Key::LeftCtrl => KeyCode::LControl,
+        Key::RightCtrl => KeyCode::RControl,
+        Key::LeftAlt => KeyCode::LAlt,
+        Key::RightAlt => KeyCode::RAlt,
+        Key::Minus => KeyCode::Minus,
+        Key::Equals => KeyCode::Equals,
+        Key::Comma => KeyCode::Comma,
+        Key::Period => KeyCode::Period,
+        Key::Slash => KeyCode::Slash,
+        Key::Semicolon => KeyCode::Semicolon,
+        Key::MouseLeft | Key::MouseRight | Key::MouseMiddle => return None,
+        Key::PadSouth
+        | Key::PadEast
+        | Key::PadNorth
+        | Key::PadWest
+        | Key::PadLeftBumper
+        | Key::PadRightBumper
+        | Key::PadLeftTrigger
+        | Key::PadRightTrigger
+        | Key::PadSelect
+        | Key::PadStart
+        | Key::PadLeftStick
+        | Key::PadRightStick
+        | Key::PadUp
+        | Key::PadDown
+        | Key::PadLeft
+        | Key::PadRight => return None,
+    })
+}
@@ -1,49 +1,123 @@
-use shared::{GameState, GraphicsContext, KeysDown, Drawer};
+mod keys;
+
+use shared::{
+    Bindings, CompiledMap, GameState, GraphicsContext, Drawer, PartitionChooser, Vec2, pad_sticks,
+};
 
 struct GGEZGame {
     state: GameState,
+    bindings: Bindings,
 }
 
 impl GGEZGame {
-    pub fn new() -> Self {
+    pub fn new(level: CompiledMap) -> Self {
+        let mut state = GameState::from_compiled(level);
+        if let Err(e) = state.load_textures() {
+            eprintln!("{e}, walls will be drawn without textures");
+        }
+        let bindings = shared::load_bindings().unwrap_or_else(|e| {
+            eprintln!("{}: {e}, using the default controls", shared::BINDINGS_FILE);
+            Bindings::default()
+        });
         Self {
-            state: GameState::new(),
+            state,
+            bindings,
         }
     }
 }
 
 fn main() {
-    let cb = ggez::ContextBuilder::new("micro_doom", ":P");
-    let (ctx, event_loop) = cb.build().unwrap();
-    let game = GGEZGame::new();
+    let loaded = shared::level_from_args().unwrap_or_else(|e| {
+        eprintln!("{e}");
+        std::process::exit(1);
+    });
+    for warning in &loaded.warnings {
+        eprintln!("{}: {warning}", loaded.map.display_name());
+    }
+    let level = loaded.into_level(&PartitionChooser::default());
+    let cb = ggez::ContextBuilder::new("micro_doom", ":P")
+        .window_setup(ggez::conf::WindowSetup::default().title(&level.map.display_name()));
+    let (mut ctx, event_loop) = cb.build().unwrap();
+    grab_pointer(&mut ctx, true);
+    let game = GGEZGame::new(level);
     ggez::event::run(ctx, event_loop, game);
 }
 
 use ggez::event;
 use ggez::Context;
 use ggez::GameResult;
-use ggez::input::keyboard::KeyCode;
+use ggez::input::keyboard::{KeyCode, KeyInput};
+use ggez::input::mouse::{self, MouseButton};
+use ggez::winit::dpi::PhysicalPosition;
 use ggez::graphics::{Canvas, Color};
 use mint::Point2;
 
+/// Hides the pointer and keeps it in the window, so the mouse can look around without running
+/// off the edge, or puts it back.
+fn grab_pointer(ctx: &mut Context, grab: bool) {
+    let grabbed = grab && mouse::set_cursor_grabbed(ctx, true).is_ok();
+    if !grabbed {
+        let _ = mouse::set_cursor_grabbed(ctx, false);
+    }
+    mouse::set_cursor_hidden(ctx, grabbed);
+}
+
+/// Puts the pointer back in the middle of the window, since it stops moving once it's up against
+/// the edge.
+fn recentre_pointer(ctx: &mut Context) {
+    let window = ctx.gfx.window();
+    let size = window.inner_size();
+    let (x, y) = (size.width as f32 / 2.0, size.height as f32 / 2.0);
+    if window.set_cursor_position(PhysicalPosition::new(x, y)).is_ok() {
+        // so the jump back doesn't count as the mouse moving
+        ctx.mouse.handle_move(x, y);
+    }
+}
 
 impl event::EventHandler for GGEZGame {
     fn update(&mut self, ctx: &mut Context) -> GameResult<()> {
-        let keys_down = KeysDown {
-            up: ctx.keyboard.is_key_pressed(KeyCode::Up),
-            down: ctx.keyboard.is_key_pressed(KeyCode::Down),
-            left: ctx.keyboard.is_key_pressed(KeyCode::Left),
-            right: ctx.keyboard.is_key_pressed(KeyCode::Right),
-            w: ctx.keyboard.is_key_pressed(KeyCode::W),
-            s: ctx.keyboard.is_key_pressed(KeyCode::S),
+        let motion = if mouse::cursor_grabbed(ctx) {
+            let delta = ctx.mouse.delta();
+            recentre_pointer(ctx);
+            Vec2::new(delta.x, delta.y)
+        } else {
+            Vec2::default()
         };
+        let mut input = self.bindings.input(&keys::held_keys(ctx), motion);
+        let pads = ctx.gamepad.gamepads().map(|(_, pad)| pad_sticks(&pad));
+        self.bindings.add_sticks(&mut input, pads);
 
         let delta = ctx.time.delta().as_secs_f32();
-        self.state.keys(delta, keys_down);
+        self.state.update(delta, &input);
 
         Ok(())
     }
 
+    fn key_down_event(&mut self, ctx: &mut Context, input: KeyInput, _repeated: bool) -> GameResult {
+        // escape lets go of the mouse, and quits if it's already been let go
+        if input.keycode == Some(KeyCode::Escape) {
+            if mouse::cursor_grabbed(ctx) {
+                grab_pointer(ctx, false);
+            } else {
+                ctx.request_quit();
+            }
+        }
+        Ok(())
+    }
+
+    fn mouse_button_down_event(
+        &mut self,
+        ctx: &mut Context,
+        button: MouseButton,
+        _x: f32,
+        _y: f32,
+    ) -> GameResult {
+        if button == MouseButton::Left && !mouse::cursor_grabbed(ctx) {
+            grab_pointer(ctx, true);
+        }
+        Ok(())
+    }
+
     fn draw(&mut self, ctx: &mut Context) -> GameResult<()> {
         let mut canvas = Canvas::from_frame(ctx, Color::WHITE);
         let (width, height) = ctx.gfx.size();
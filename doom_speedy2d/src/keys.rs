@@ -0,0 +1,97 @@
+//! which of the game's keys speedy2d has said are down
+
+use std::collections::HashSet;
+
+use shared::Key;
+use speedy2d::window::{MouseButton, VirtualKeyCode};
+
+/// Every key that's down, as the game names them, going by the key and button events so far.
+pub fn held_keys(keys: &HashSet<VirtualKeyCode>, buttons: &HashSet<MouseButton>) -> Vec<Key> {
+    Key::all()
+        .filter(|&key| match key {
+            Key::MouseLeft => buttons.contains(&MouseButton::Left),
+            Key::MouseRight => buttons.contains(&MouseButton::Right),
+            Key::MouseMiddle => buttons.contains(&MouseButton::Middle),
+            _ => key_code(key).is_some_and(|code| keys.contains(&code)),
+        })
+        .collect()
+}
+
+fn key_code(key: Key) -> Option<VirtualKeyCode> {
+    Some(match key {
+        Key::A => VirtualKeyCode::A,
+        Key::B => VirtualKeyCode::B,
+        Key::C => VirtualKeyCode::C,
+        Key::D => VirtualKeyCode::D,
+        Key::E => VirtualKeyCode::E,
+        Key::F => VirtualKeyCode::F,
+        Key::G => VirtualKeyCode::G,
+        Key::H => VirtualKeyCode::H,
+        Key::I => VirtualKeyCode::I,
+        Key::J => VirtualKeyCode::J,
+        Key::K => VirtualKeyCode::K,
+        Key::L => VirtualKeyCode::L,
+        Key::M => VirtualKeyCode::M,
+        Key::N => VirtualKeyCode::N,
+        Key::O => VirtualKeyCode::O,
+        Key::P => VirtualKeyCode::P,
+        Key::Q => VirtualKeyCode::Q,
+        Key::R => VirtualKeyCode::R,
+        Key::S => VirtualKeyCode::S,
+        Key::T => VirtualKeyCode::T,
+        Key::U => VirtualKeyCode::U,
+        Key::V => VirtualKeyCode::V,
+        Key::W => VirtualKeyCode::W,
+        Key::X => VirtualKeyCode::X,
+        Key::Y => VirtualKeyCode::Y,
+        Key::Z => VirtualKeyCode::Z,
+        Key::Num0 => VirtualKeyCode::Key0,
+        Key::Num1 => VirtualKeyCode::Key1,
+        Key::Num2 => VirtualKeyCode::Key2,
+        Key::Num3 => VirtualKeyCode::Key3,
+        Key::Num4 => VirtualKeyCode::Key4,
+        Key::Num5 => VirtualKeyCode::Key5,
+        Key::Num6 => VirtualKeyCode::Key6,
+        Key::Num7 => VirtualKeyCode::Key7,
+        Key::Num8 => VirtualKeyCode::Key8,
+        Key::Num9 => VirtualKeyCode::Key9,
+        Key::Up => VirtualKeyCode::Up,
+        Key::Down => VirtualKeyCode::Down,
+        Key::Left => VirtualKeyCode::Left,
+        Key::Right => VirtualKeyCode::Right,
+        Key::Space => VirtualKeyCode::Space,
+        Key::Tab => VirtualKeyCode::Tab,
+        Key::Enter => VirtualKeyCode::Return,
+        Key::Escape => VirtualKeyCode::Escape,
+        Key::Backspace => VirtualKeyCode::Backspace,
+        Key::LeftShift => VirtualKeyCode::LShift,
+        Key::RightShift => VirtualKeyCode::RShift,
+        Key::LeftCtrl => VirtualKeyCode::LControl,
+        Key::RightCtrl => VirtualKeyCode::RControl,
+        Key::LeftAlt => VirtualKeyCode::LAlt,
+        Key::RightAlt => VirtualKeyCode::RAlt,
+        Key::Minus => VirtualKeyCode::Minus,
+        Key::Equals => VirtualKeyCode::Equals,
+        Key::Comma => VirtualKeyCode::Comma,
+        Key::Period => VirtualKeyCode::Period,
+        Key::Slash => VirtualKeyCode::Slash,
+        Key::Semicolon => VirtualKeyCode::Semicolon,
+        Key::MouseLeft | Key::MouseRight | Key::MouseMiddle => return None,
+        Key::PadSouth
+        | Key::PadEast
+        | Key::PadNorth
+        | Key::PadWest
+        | Key::PadLeftBumper
+        | Key::PadRightBumper
+        | Key::PadLeftTrigger
+        | Key::PadRightTrigger
+        | Key::PadSelect
+        | Key::PadStart
+        | Key::PadLeftStick
+        | Key::PadRightStick
+        | Key::PadUp
+        | Key::PadDown
+        | Key::PadLeft
+        | Key::PadRight => return None,
+    })
+}
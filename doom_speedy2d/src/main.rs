@@ -1,43 +1,97 @@
+mod keys;
+
 use mint::Point2;
 use speedy2d::dimen::Vec2;
-use speedy2d::window::{VirtualKeyCode, WindowHelper};
+use speedy2d::window::{MouseButton, VirtualKeyCode, WindowHelper, WindowStartupInfo};
 use speedy2d::{Window, window::WindowHandler};
 use speedy2d::color::Color;
-use shared::{Drawer, GameState, GraphicsContext, KeysDown};
+use shared::{Bindings, CompiledMap, Drawer, GameState, GraphicsContext, PartitionChooser};
 use std::time::Instant;
 
 fn main() {
-    let window = Window::new_centered("My Speedy2D Window", (800, 600)).unwrap();
-    window.run_loop(WindowState::default());
+    let loaded = shared::level_from_args().unwrap_or_else(|e| {
+        eprintln!("{e}");
+        std::process::exit(1);
+    });
+    for warning in &loaded.warnings {
+        eprintln!("{}: {warning}", loaded.map.display_name());
+    }
+    let level = loaded.into_level(&PartitionChooser::default());
+    let window = Window::new_centered(level.map.display_name(), (800, 600)).unwrap();
+    window.run_loop(WindowState::new(level));
 }
 
 struct WindowState {
     game_state: GameState,
     keys_down: std::collections::HashSet<VirtualKeyCode>,
+    buttons_down: std::collections::HashSet<MouseButton>,
+    bindings: Bindings,
+    // whether the mouse is looking around, and how far it's moved since the last frame
+    grabbed: bool,
+    mouse_motion: Vec2,
     last: Instant,
 }
 
-impl Default for WindowState {
-    fn default() -> Self {
+impl WindowState {
+    fn new(level: CompiledMap) -> Self {
+        let mut game_state = GameState::from_compiled(level);
+        if let Err(e) = game_state.load_textures() {
+            eprintln!("{e}, walls will be drawn without textures");
+        }
+        let bindings = shared::load_bindings().unwrap_or_else(|e| {
+            eprintln!("{}: {e}, using the default controls", shared::BINDINGS_FILE);
+            Bindings::default()
+        });
         Self {
-            game_state: GameState::new(),
+            game_state,
             keys_down: std::collections::HashSet::new(),
+            buttons_down: std::collections::HashSet::new(),
+            bindings,
+            grabbed: false,
+            mouse_motion: Vec2::ZERO,
             last: Instant::now(),
         }
     }
 }
 
+/// Keeps the pointer in the window, so the mouse can look around without running off the edge,
+/// or lets it go. The pointer gets hidden once the grab goes through.
+fn grab_pointer(helper: &mut WindowHelper<()>, grab: bool) {
+    if let Err(e) = helper.set_cursor_grab(grab) {
+        eprintln!("couldn't grab the mouse: {e:?}");
+    }
+}
+
 impl WindowHandler for WindowState {
+    fn on_start(&mut self, helper: &mut WindowHelper<()>, _info: WindowStartupInfo) {
+        grab_pointer(helper, true);
+    }
+
+    fn on_mouse_grab_status_changed(&mut self, helper: &mut WindowHelper<()>, mouse_grabbed: bool) {
+        self.grabbed = mouse_grabbed;
+        helper.set_cursor_visible(!mouse_grabbed);
+    }
+
+    fn on_mouse_move(&mut self, _helper: &mut WindowHelper<()>, position: Vec2) {
+        // while the mouse is grabbed this is how far it moved rather than where it is
+        if self.grabbed {
+            self.mouse_motion += position;
+        }
+    }
 
     fn on_key_down(
         &mut self,
-        _helper: &mut WindowHelper<()>,
+        helper: &mut WindowHelper<()>,
         key: Option<VirtualKeyCode>,
         _scancode: speedy2d::window::KeyScancode,
     ) {
         if let Some(k) = key {
             self.keys_down.insert(k);
         }
+        // escape lets go of the mouse, and clicking in the window grabs it again
+        if key == Some(VirtualKeyCode::Escape) {
+            grab_pointer(helper, false);
+        }
     }
 
     fn on_key_up(
@@ -51,6 +105,17 @@ impl WindowHandler for WindowState {
         }
     }
 
+    fn on_mouse_button_down(&mut self, helper: &mut WindowHelper<()>, button: MouseButton) {
+        self.buttons_down.insert(button);
+        if button == MouseButton::Left && !self.grabbed {
+            grab_pointer(helper, true);
+        }
+    }
+
+    fn on_mouse_button_up(&mut self, _helper: &mut WindowHelper<()>, button: MouseButton) {
+        self.buttons_down.remove(&button);
+    }
+
     fn on_draw(
             &mut self,
             helper: &mut speedy2d::window::WindowHelper<()>,
@@ -73,16 +138,10 @@ impl WindowHandler for WindowState {
 
 
 
-        let keys_down = KeysDown {
-            up: self.keys_down.contains(&VirtualKeyCode::Up),
-            down: self.keys_down.contains(&VirtualKeyCode::Down),
-            left: self.keys_down.contains(&VirtualKeyCode::Left),
-            right: self.keys_down.contains(&VirtualKeyCode::Right),
-            w: self.keys_down.contains(&VirtualKeyCode::W),
-            s: self.keys_down.contains(&VirtualKeyCode::S),
-        };
-
-        self.game_state.keys(dt, keys_down);
+        let held = keys::held_keys(&self.keys_down, &self.buttons_down);
+        let motion = std::mem::replace(&mut self.mouse_motion, Vec2::ZERO);
+        let input = self.bindings.input(&held, shared::Vec2::new(motion.x, motion.y));
+        self.game_state.update(dt, &input);
         // Request that we draw another frame once this one has finished
         helper.request_redraw();
     }
@@ -1,4 +1,5 @@
-use shared::{Drawer, KeysDown};
+use shared::{Drawer, Key, Light, LitVertex, Texture, TexturedVertex};
+use winit::event::MouseButton;
 use winit::keyboard::KeyCode;
 use winit_input_helper::WinitInputHelper;
 
@@ -27,16 +28,104 @@ impl<'a> Drawer for PixelDrawer<'a> {
             println!("Unsupported polygon with {} points", points.len());
         }
     }
-}
 
+    fn draw_lit_polygon(&mut self, points: &[LitVertex], color: (u8, u8, u8, u8), light: Light) {
+        self.draw_lit_poly(points, color, light);
+    }
 
-pub fn keys(input: &WinitInputHelper) -> KeysDown {
-    KeysDown {
-        up: input.key_held(KeyCode::ArrowUp),
-        down: input.key_held(KeyCode::ArrowDown),
-        left: input.key_held(KeyCode::ArrowLeft),
-        right: input.key_held(KeyCode::ArrowRight),
-        w: input.key_held(KeyCode::KeyW),
-        s: input.key_held(KeyCode::KeyS),
+    fn draw_textured_polygon(&mut self, points: &[TexturedVertex], texture: &Texture, light: Light) {
+        self.draw_textured_poly(points, texture, light);
     }
 }
+
+
+/// Every key that's down, as the game names them.
+pub fn held_keys(input: &WinitInputHelper) -> Vec<Key> {
+    Key::all()
+        .filter(|&key| match key {
+            Key::MouseLeft => input.mouse_held(MouseButton::Left),
+            Key::MouseRight => input.mouse_held(MouseButton::Right),
+            Key::MouseMiddle => input.mouse_held(MouseButton::Middle),
+            _ => key_code(key).is_some_and(|code| input.key_held(code)),
+        })
+        .collect()
+}
+
+fn key_code(key: Key) -> Option<KeyCode> {
+    Some(match key {
+        Key::A => KeyCode::KeyA,
+        Key::B => KeyCode::KeyB,
+        Key::C => KeyCode::KeyC,
+        Key::D => KeyCode::KeyD,
+        Key::E => KeyCode::KeyE,
+        Key::F => KeyCode::KeyF,
+        Key::G => KeyCode::KeyG,
+        Key::H => KeyCode::KeyH,
+        Key::I => KeyCode::KeyI,
+        Key::J => KeyCode::KeyJ,
+        Key::K => KeyCode::KeyK,
+        Key::L => KeyCode::KeyL,
+        Key::M => KeyCode::KeyM,
+        Key::N => KeyCode::KeyN,
+        Key::O => KeyCode::KeyO,
+        Key::P => KeyCode::KeyP,
+        Key::Q => KeyCode::KeyQ,
+        Key::R => KeyCode::KeyR,
+        Key::S => KeyCode::KeyS,
+        Key::T => KeyCode::KeyT,
+        Key::U => KeyCode::KeyU,
+        Key::V => KeyCode::KeyV,
+        Key::W => KeyCode::KeyW,
+        Key::X => KeyCode::KeyX,
+        Key::Y => KeyCode::KeyY,
+        Key::Z => KeyCode::KeyZ,
+        Key::Num0 => KeyCode::Digit0,
+        Key::Num1 => KeyCode::Digit1,
+        Key::Num2 => KeyCode::Digit2,
+        Key::Num3 => KeyCode::Digit3,
+        Key::Num4 => KeyCode::Digit4,
+        Key::Num5 => KeyCode::Digit5,
+        Key::Num6 => KeyCode::Digit6,
+        Key::Num7 => KeyCode::Digit7,
+        Key::Num8 => KeyCode::Digit8,
+        Key::Num9 => KeyCode::Digit9,
+        Key::Up => KeyCode::ArrowUp,
+        Key::Down => KeyCode::ArrowDown,
+        Key::Left => KeyCode::ArrowLeft,
+        Key::Right => KeyCode::ArrowRight,
+        Key::Space => KeyCode::Space,
+        Key::Tab => KeyCode::Tab,
+        Key::Enter => KeyCode::Enter,
+        Key::Escape => KeyCode::Escape,
+        Key::Backspace => KeyCode::Backspace,
+        Key::LeftShift => KeyCode::ShiftLeft,
+        Key::RightShift => KeyCode::ShiftRight,
+        Key::LeftCtrl => KeyCode::ControlLeft,
+        Key::RightCtrl => KeyCode::ControlRight,
+        Key::LeftAlt => KeyCode::AltLeft,
+        Key::RightAlt => KeyCode::AltRight,
+        Key::Minus => KeyCode::Minus,
+        Key::Equals => KeyCode::Equal,
+        Key::Comma => KeyCode::Comma,
+        Key::Period => KeyCode::Period,
+        Key::Slash => KeyCode::Slash,
+        Key::Semicolon => KeyCode::Semicolon,
+        Key::MouseLeft | Key::MouseRight | Key::MouseMiddle => return None,
+        Key::PadSouth
+        | Key::PadEast
+        | Key::PadNorth
+        | Key::PadWest
+        | Key::PadLeftBumper
+        | Key::PadRightBumper
+        | Key::PadLeftTrigger
+        | Key::PadRightTrigger
+        | Key::PadSelect
+        | Key::PadStart
+        | Key::PadLeftStick
+        | Key::PadRightStick
+        | Key::PadUp
+        | Key::PadDown
+        | Key::PadLeft
+        | Key::PadRight => return None,
+    })
+}
@@ -0,0 +1,46 @@
+//! gamepads, through gilrs, since winit doesn't know about them
+
+use gilrs::Gilrs;
+use shared::{Key, Vec2, pad_button, pad_sticks};
+
+/// Every gamepad plugged in. If gilrs can't start there just aren't any, and the keyboard and
+/// mouse still work.
+pub struct Gamepads {
+    gilrs: Option<Gilrs>,
+}
+
+impl Gamepads {
+    pub fn new() -> Self {
+        let gilrs = Gilrs::new()
+            .inspect_err(|e| eprintln!("{e}, gamepads won't work"))
+            .ok();
+        Gamepads { gilrs }
+    }
+
+    /// Catches up on what the gamepads have been doing since last frame. gilrs only keeps track
+    /// of which buttons are down as its events are read.
+    pub fn update(&mut self) {
+        if let Some(gilrs) = &mut self.gilrs {
+            while gilrs.next_event().is_some() {}
+        }
+    }
+
+    /// Adds the gamepad buttons that are down on any of the gamepads to `held`.
+    pub fn add_held_keys(&self, held: &mut Vec<Key>) {
+        let Some(gilrs) = &self.gilrs else {
+            return;
+        };
+        held.extend(Key::all().filter(|&key| {
+            pad_button(key)
+                .is_some_and(|button| gilrs.gamepads().any(|(_, pad)| pad.is_pressed(button)))
+        }));
+    }
+
+    /// The left and right sticks of every gamepad plugged in, with +y up.
+    pub fn sticks(&self) -> Vec<(Vec2, Vec2)> {
+        match &self.gilrs {
+            Some(gilrs) => gilrs.gamepads().map(|(_, pad)| pad_sticks(&pad)).collect(),
+            None => Vec::new(),
+        }
+    }
+}
@@ -1,4 +1,5 @@
 use pixels::Pixels;
+use shared::{Light, LitVertex, Texture, TexturedVertex};
 
 use super::Color;
 
@@ -79,7 +80,7 @@ impl <'a> PixelDrawer<'a> {
     pub fn triangle(&mut self, p1: (i32, i32), p2: (i32, i32), p3: (i32, i32), color: Color) {
         // sort points by y
         let mut v = [(p1.0, p1.1), (p2.0, p2.1), (p3.0, p3.1)];
-        v.sort_by(|a, b| a.1.cmp(&b.1));
+        v.sort_by_key(|p| p.1);
         let (p1, p2, p3) = (v[0], v[1], v[2]);
 
         if p2.1 == p3.1 {
@@ -197,6 +198,86 @@ impl <'a> PixelDrawer<'a> {
     }
 
 
+    /// Scanline fills a convex polygon with a texture, lit per pixel.
+    pub fn draw_textured_poly(&mut self, points: &[TexturedVertex], texture: &Texture, light: Light) {
+        self.scanline(points, |u, v, inv_w| {
+            let (r, g, b, _) = light.at(inv_w).apply(texture.sample(u, v));
+            Color(r, g, b)
+        });
+    }
+
+    /// Scanline fills a convex polygon with one colour, lit per pixel.
+    pub fn draw_lit_poly(&mut self, points: &[LitVertex], color: (u8, u8, u8, u8), light: Light) {
+        let points: Vec<TexturedVertex> = points
+            .iter()
+            .map(|p| TexturedVertex { pos: p.pos, u: 0.0, v: 0.0, inv_w: p.inv_w })
+            .collect();
+        self.scanline(&points, |_, _, inv_w| {
+            let (r, g, b, _) = light.at(inv_w).apply(color);
+            Color(r, g, b)
+        });
+    }
+
+    /// Scanline fills a convex polygon with whatever `pixel` says for each pixel's u, v and
+    /// 1/w. u/w, v/w and 1/w go linearly across the screen, so those get interpolated and
+    /// divided back out per pixel.
+    fn scanline(&mut self, points: &[TexturedVertex], mut pixel: impl FnMut(f32, f32, f32) -> Color) {
+        let (width, height) = match self.dims() {
+            Some(dims) => dims,
+            None => return,
+        };
+        let buffer = self.pixels.frame_mut();
+
+        let attrs = |p: &TexturedVertex| [p.u * p.inv_w, p.v * p.inv_w, p.inv_w];
+        let lerp = |a: [f32; 3], b: [f32; 3], t: f32| {
+            [a[0] + (b[0] - a[0]) * t, a[1] + (b[1] - a[1]) * t, a[2] + (b[2] - a[2]) * t]
+        };
+
+        let (y_min, y_max) = points.iter().fold((f32::INFINITY, f32::NEG_INFINITY), |(lo, hi), p| {
+            (lo.min(p.pos.y), hi.max(p.pos.y))
+        });
+        let y_start = (y_min - 0.5).ceil().max(0.0) as i32;
+        let y_end = ((y_max - 0.5).floor() as i32).min(height - 1);
+
+        for y in y_start..=y_end {
+            let yc = y as f32 + 0.5;
+
+            // where this row crosses the outline, leftmost and rightmost
+            let mut left: Option<(f32, [f32; 3])> = None;
+            let mut right: Option<(f32, [f32; 3])> = None;
+            for i in 0..points.len() {
+                let a = &points[i];
+                let b = &points[(i + 1) % points.len()];
+                if (a.pos.y <= yc) == (b.pos.y <= yc) {
+                    continue;
+                }
+                let t = (yc - a.pos.y) / (b.pos.y - a.pos.y);
+                let x = a.pos.x + (b.pos.x - a.pos.x) * t;
+                let crossing = (x, lerp(attrs(a), attrs(b), t));
+                if left.is_none_or(|(lx, _)| x < lx) {
+                    left = Some(crossing);
+                }
+                if right.is_none_or(|(rx, _)| x > rx) {
+                    right = Some(crossing);
+                }
+            }
+            let (Some((xl, al)), Some((xr, ar))) = (left, right) else {
+                continue;
+            };
+            if xr <= xl {
+                continue;
+            }
+
+            let x_start = (xl - 0.5).ceil().max(0.0) as i32;
+            let x_end = ((xr - 0.5).floor() as i32).min(width - 1);
+            for x in x_start..=x_end {
+                let t = (x as f32 + 0.5 - xl) / (xr - xl);
+                let [uw, vw, iw] = lerp(al, ar, t);
+                draw_point(buffer, width, height, x, y, pixel(uw / iw, vw / iw, iw));
+            }
+        }
+    }
+
     pub fn draw_point(&mut self, x: i32, y: i32, color: Color) {
         let (width, height) = match self.dims() {
             Some(dims) => dims,
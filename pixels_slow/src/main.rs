@@ -1,14 +1,16 @@
 mod lines;
 mod bindings;
+mod gamepad;
 use pixels::{Error, Pixels, SurfaceTexture};
 use winit::application::ApplicationHandler;
-use winit::event::{DeviceEvent, DeviceId, StartCause, WindowEvent};
+use winit::event::{DeviceEvent, DeviceId, MouseButton, StartCause, WindowEvent};
 use winit::event_loop::{ActiveEventLoop, ControlFlow, EventLoop};
 use winit::keyboard::KeyCode;
-use winit::window::{Window, WindowId};
+use winit::window::{CursorGrabMode, Window, WindowId};
 use winit_input_helper::WinitInputHelper;
 use lines::PixelDrawer;
-use shared::{GameState, GraphicsContext};
+use gamepad::Gamepads;
+use shared::{Bindings, GameState, GraphicsContext, PartitionChooser, Vec2};
 
 #[derive(Copy, Clone)]
 struct Color(u8, u8, u8);
@@ -18,6 +20,10 @@ struct App {
     input: WinitInputHelper,
     pixels: Option<Pixels>,
     game_state: GameState,
+    bindings: Bindings,
+    gamepads: Gamepads,
+    // whether the mouse is looking around
+    grabbed: bool,
     last_frame: std::time::Instant,
 }
 
@@ -29,14 +35,24 @@ impl App {
         let dt = now.duration_since(self.last_frame).as_secs_f32();
         self.last_frame = now;
 
-        let keys = bindings::keys(&self.input);
-        self.game_state.keys(dt, keys);
+        let motion = if self.grabbed {
+            let (dx, dy) = self.input.mouse_diff();
+            Vec2::new(dx, dy)
+        } else {
+            Vec2::default()
+        };
+        self.gamepads.update();
+        let mut held = bindings::held_keys(&self.input);
+        self.gamepads.add_held_keys(&mut held);
+        let mut input = self.bindings.input(&held, motion);
+        self.bindings.add_sticks(&mut input, self.gamepads.sticks());
+        self.game_state.update(dt, &input);
         let pixels = match &mut self.pixels {
             Some(pixels) => pixels,
             None => return,
         };
         let t = pixels.context().texture_extent;
-        let (width, height) = (t.width as u32, t.height as u32);
+        let (width, height) = (t.width, t.height);
         let mut drawer = PixelDrawer::new(pixels);
         drawer.clear(WHITE);
 
@@ -65,6 +81,7 @@ impl ApplicationHandler for App {
 
         let pixels = Pixels::new(320, 240, surface).expect("Pixels init failed");
 
+        self.grabbed = grab_pointer(&window, true);
         self.window = Some(window);
         self.pixels = Some(pixels);
     }
@@ -122,13 +139,50 @@ impl ApplicationHandler for App {
         }
 
         if let Some(window) = &self.window {
+            // escape lets go of the mouse, and clicking back in the window grabs it again
+            if self.input.key_pressed(KeyCode::Escape) {
+                self.grabbed = grab_pointer(window, false);
+            } else if !self.grabbed && self.input.mouse_pressed(MouseButton::Left) {
+                self.grabbed = grab_pointer(window, true);
+            }
             // For continuous rendering:
             window.request_redraw();
         }
     }
 }
 
+/// Hides the pointer and keeps it in the window, so the mouse can look around without running
+/// off the edge, or puts it back. Returns whether it's grabbed now.
+fn grab_pointer(window: &Window, grab: bool) -> bool {
+    let grabbed = grab
+        && window
+            .set_cursor_grab(CursorGrabMode::Locked)
+            .or_else(|_| window.set_cursor_grab(CursorGrabMode::Confined))
+            .is_ok();
+    if !grabbed {
+        let _ = window.set_cursor_grab(CursorGrabMode::None);
+    }
+    window.set_cursor_visible(!grabbed);
+    grabbed
+}
+
 fn main() -> Result<(), Error> {
+    let loaded = shared::level_from_args().unwrap_or_else(|e| {
+        eprintln!("{e}");
+        std::process::exit(1);
+    });
+    for warning in &loaded.warnings {
+        eprintln!("{}: {warning}", loaded.map.display_name());
+    }
+    let level = loaded.into_level(&PartitionChooser::default());
+    let mut game_state = GameState::from_compiled(level);
+    if let Err(e) = game_state.load_textures() {
+        eprintln!("{e}, walls will be drawn without textures");
+    }
+    let bindings = shared::load_bindings().unwrap_or_else(|e| {
+        eprintln!("{}: {e}, using the default controls", shared::BINDINGS_FILE);
+        Bindings::default()
+    });
     let event_loop = EventLoop::new().unwrap();
     event_loop.set_control_flow(ControlFlow::Poll);
     event_loop.run_app(&mut App {
@@ -136,7 +190,10 @@ fn main() -> Result<(), Error> {
         pixels: None,
         input: WinitInputHelper::new(),
         last_frame: std::time::Instant::now(),
-        game_state: GameState::new(),
+        game_state,
+        bindings,
+        gamepads: Gamepads::new(),
+        grabbed: false,
     }).unwrap();
     Ok(())
 }
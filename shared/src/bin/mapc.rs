@@ -0,0 +1,101 @@
+//! Builds a map's BSP tree ahead of time and saves it as a compiled map.
+//!
+//! ```text
+//! mapc <map> [--out map.mapc] [--wad-map name] [--candidates n] [--split-weight w]
+//!      [--balance-weight w]
+//! ```
+//!
+//! The map can also be a Doom WAD, in which case `--wad-map` picks which of its maps (like
+//! `E1M1`) to compile, the first one by default. Anything that couldn't be imported gets listed.
+//!
+//! The output defaults to the map with its extension swapped for `.mapc`, next to it so its
+//! textures can still be found. The options tune [`PartitionChooser`]; the same map with the same
+//! options always compiles to the same file.
+
+use std::path::PathBuf;
+use std::process::exit;
+
+use shared::{CompiledMap, PartitionChooser, load_any_map_from_path};
+
+const USAGE: &str = "usage: mapc <map> [--out map.mapc] [--wad-map name] [--candidates n] \
+     [--split-weight w] [--balance-weight w]";
+
+struct Options {
+    map: PathBuf,
+    out: Option<PathBuf>,
+    wad_map: Option<String>,
+    chooser: PartitionChooser,
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut map = None;
+    let mut options = Options {
+        map: PathBuf::new(),
+        out: None,
+        wad_map: None,
+        chooser: PartitionChooser::default(),
+    };
+
+    fn value<T: std::str::FromStr>(
+        flag: &str,
+        args: &mut impl Iterator<Item = String>,
+    ) -> Result<T, String> {
+        let arg = args
+            .next()
+            .ok_or_else(|| format!("`{flag}` is missing a value"))?;
+        arg.parse()
+            .map_err(|_| format!("`{arg}` isn't a valid value for `{flag}`"))
+    }
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--out" | "-o" => options.out = Some(value(&arg, &mut args)?),
+            "--wad-map" => options.wad_map = Some(value(&arg, &mut args)?),
+            "--candidates" => {
+                options.chooser.max_candidates = value(&arg, &mut args)?;
+                if options.chooser.max_candidates == 0 {
+                    return Err("`--candidates` has to be at least 1".to_string());
+                }
+            }
+            "--split-weight" => options.chooser.split_weight = value(&arg, &mut args)?,
+            "--balance-weight" => options.chooser.balance_weight = value(&arg, &mut args)?,
+            "-h" | "--help" => {
+                println!("{USAGE}");
+                exit(0);
+            }
+            flag if flag.starts_with("--") => return Err(format!("unknown option `{flag}`")),
+            _ if map.is_none() => map = Some(PathBuf::from(arg)),
+            _ => return Err(format!("unexpected argument `{arg}`")),
+        }
+    }
+
+    options.map = map.ok_or("no map given")?;
+    Ok(options)
+}
+
+fn main() {
+    let options = parse_args(std::env::args().skip(1)).unwrap_or_else(|e| {
+        eprintln!("{e}\n{USAGE}");
+        exit(2);
+    });
+
+    let loaded =
+        load_any_map_from_path(&options.map, options.wad_map.as_deref()).unwrap_or_else(|e| {
+            eprintln!("{e}");
+            exit(1);
+        });
+    for warning in &loaded.warnings {
+        eprintln!("{}: {warning}", options.map.display());
+    }
+    // already compiled maps get built again, with these options
+    let level = CompiledMap::compile(loaded.map, &options.chooser);
+
+    let out = options
+        .out
+        .unwrap_or_else(|| options.map.with_extension("mapc"));
+    if let Err(e) = level.save(&out) {
+        eprintln!("couldn't write {}: {e}", out.display());
+        exit(1);
+    }
+    println!("{}: {}", out.display(), level.stats);
+}
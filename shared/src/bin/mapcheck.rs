@@ -0,0 +1,89 @@
+//! Checks maps for mistakes that still load, like zero-length or doubled up walls, rooms that
+//! aren't closed off and spawn points outside the level.
+//!
+//! ```text
+//! mapcheck <map>... [--wad-map name] [--deny-warnings]
+//! ```
+//!
+//! Each problem is printed as `map.txt:12: error: ...`, with the line it's on for text maps and
+//! which wall or sector it is for anything else. It exits with 1 if any map has errors (or
+//! warnings, with `--deny-warnings`), so it can go in a build script.
+
+use std::path::PathBuf;
+use std::process::exit;
+
+use shared::{Severity, check_map, load_any_map_from_path};
+
+const USAGE: &str = "usage: mapcheck <map>... [--wad-map name] [--deny-warnings]";
+
+struct Options {
+    maps: Vec<PathBuf>,
+    wad_map: Option<String>,
+    deny_warnings: bool,
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut options = Options {
+        maps: Vec::new(),
+        wad_map: None,
+        deny_warnings: false,
+    };
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--wad-map" => {
+                let name = args.next().ok_or("`--wad-map` is missing a value")?;
+                options.wad_map = Some(name);
+            }
+            "--deny-warnings" => options.deny_warnings = true,
+            "-h" | "--help" => {
+                println!("{USAGE}");
+                exit(0);
+            }
+            flag if flag.starts_with("--") => return Err(format!("unknown option `{flag}`")),
+            _ => options.maps.push(PathBuf::from(arg)),
+        }
+    }
+    if options.maps.is_empty() {
+        return Err("no map given".to_string());
+    }
+    Ok(options)
+}
+
+fn main() {
+    let options = parse_args(std::env::args().skip(1)).unwrap_or_else(|e| {
+        eprintln!("{e}\n{USAGE}");
+        exit(2);
+    });
+
+    let mut failed = false;
+    for path in &options.maps {
+        let map = match load_any_map_from_path(path, options.wad_map.as_deref()) {
+            Ok(loaded) => {
+                for warning in &loaded.warnings {
+                    println!("{}: warning: {warning}", path.display());
+                }
+                loaded.map
+            }
+            Err(e) => {
+                eprintln!("{e}");
+                failed = true;
+                continue;
+            }
+        };
+        for problem in check_map(&map) {
+            failed |= problem.severity == Severity::Error || options.deny_warnings;
+            match problem.place.line() {
+                Some(line) => println!(
+                    "{}:{line}: {}: {}",
+                    path.display(),
+                    problem.severity,
+                    problem.kind
+                ),
+                None => println!("{}: {problem}", path.display()),
+            }
+        }
+    }
+    if failed {
+        exit(1);
+    }
+}
@@ -0,0 +1,95 @@
+//! Converts a map into the TOML or JSON map format.
+//!
+//! ```text
+//! mapconv <map> [--out map.toml] [--wad-map name]
+//! ```
+//!
+//! The map can be a text, TOML or JSON map, one compiled by `mapc`, or a Doom WAD, in which case
+//! `--wad-map` picks which of its maps to convert (the first one by default) and anything that
+//! couldn't be imported gets listed. Output ending in `.json` is written as JSON,
+//! anything else as TOML, and it defaults to the map with its extension swapped for `.toml`.
+//! Texture paths are copied as they are, so they only still work if the output ends up next to
+//! the map.
+
+use std::path::PathBuf;
+use std::process::exit;
+
+use shared::{load_any_map_from_path, map_to_json, map_to_toml};
+
+const USAGE: &str = "usage: mapconv <map> [--out map.toml] [--wad-map name]";
+
+struct Options {
+    map: PathBuf,
+    out: Option<PathBuf>,
+    wad_map: Option<String>,
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut map = None;
+    let mut out = None;
+    let mut wad_map = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--out" | "-o" => {
+                let path = args.next().ok_or("`--out` is missing a value")?;
+                out = Some(PathBuf::from(path));
+            }
+            "--wad-map" => {
+                let name = args.next().ok_or("`--wad-map` is missing a value")?;
+                wad_map = Some(name);
+            }
+            "-h" | "--help" => {
+                println!("{USAGE}");
+                exit(0);
+            }
+            flag if flag.starts_with("--") => return Err(format!("unknown option `{flag}`")),
+            _ if map.is_none() => map = Some(PathBuf::from(arg)),
+            _ => return Err(format!("unexpected argument `{arg}`")),
+        }
+    }
+    Ok(Options {
+        map: map.ok_or("no map given")?,
+        out,
+        wad_map,
+    })
+}
+
+fn main() {
+    let options = parse_args(std::env::args().skip(1)).unwrap_or_else(|e| {
+        eprintln!("{e}\n{USAGE}");
+        exit(2);
+    });
+
+    let loaded =
+        load_any_map_from_path(&options.map, options.wad_map.as_deref()).unwrap_or_else(|e| {
+            eprintln!("{e}");
+            exit(1);
+        });
+    for warning in &loaded.warnings {
+        eprintln!("{}: {warning}", options.map.display());
+    }
+    let map = loaded.map;
+    let out = options
+        .out
+        .unwrap_or_else(|| options.map.with_extension("toml"));
+    if out == options.map {
+        eprintln!(
+            "{} would be overwritten, pick somewhere else with --out",
+            out.display()
+        );
+        exit(2);
+    }
+
+    let is_json = out
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("json"));
+    let contents = if is_json {
+        map_to_json(&map)
+    } else {
+        map_to_toml(&map)
+    };
+    if let Err(e) = std::fs::write(&out, contents) {
+        eprintln!("couldn't write {}: {e}", out.display());
+        exit(1);
+    }
+}
@@ -0,0 +1,83 @@
+//! Draws a map from above as an SVG, to look over a layout without running the game.
+//!
+//! ```text
+//! mapsvg <map> [--out map.svg] [--wad-map name] [--bsp]
+//! ```
+//!
+//! The map can be a text map, a TOML or JSON one, one compiled by `mapc`, or a Doom WAD (the
+//! first level in it, or the one named by `--wad-map`). `--bsp` adds the BSP tree's partition lines and the points where
+//! it split walls, as built by default or as they were compiled. The output defaults to the map
+//! with its extension swapped for `.svg`.
+
+use std::path::PathBuf;
+use std::process::exit;
+
+use shared::{PartitionChooser, load_any_map_from_path, map_to_svg};
+
+const USAGE: &str = "usage: mapsvg <map> [--out map.svg] [--wad-map name] [--bsp]";
+
+struct Options {
+    map: PathBuf,
+    out: Option<PathBuf>,
+    wad_map: Option<String>,
+    bsp: bool,
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut map = None;
+    let mut out = None;
+    let mut wad_map = None;
+    let mut bsp = false;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--out" | "-o" => {
+                let path = args.next().ok_or("`--out` is missing a value")?;
+                out = Some(PathBuf::from(path));
+            }
+            "--wad-map" => {
+                let name = args.next().ok_or("`--wad-map` is missing a value")?;
+                wad_map = Some(name);
+            }
+            "--bsp" => bsp = true,
+            "-h" | "--help" => {
+                println!("{USAGE}");
+                exit(0);
+            }
+            flag if flag.starts_with("--") => return Err(format!("unknown option `{flag}`")),
+            _ if map.is_none() => map = Some(PathBuf::from(arg)),
+            _ => return Err(format!("unexpected argument `{arg}`")),
+        }
+    }
+    Ok(Options {
+        map: map.ok_or("no map given")?,
+        out,
+        wad_map,
+        bsp,
+    })
+}
+
+fn main() {
+    let options = parse_args(std::env::args().skip(1)).unwrap_or_else(|e| {
+        eprintln!("{e}\n{USAGE}");
+        exit(2);
+    });
+
+    let loaded =
+        load_any_map_from_path(&options.map, options.wad_map.as_deref()).unwrap_or_else(|e| {
+            eprintln!("{e}");
+            exit(1);
+        });
+    for warning in &loaded.warnings {
+        eprintln!("{}: {warning}", options.map.display());
+    }
+    let level = loaded.into_level(&PartitionChooser::default());
+    let svg = map_to_svg(&level.map, options.bsp.then_some(&level.bsp));
+
+    let out = options
+        .out
+        .unwrap_or_else(|| options.map.with_extension("svg"));
+    if let Err(e) = std::fs::write(&out, svg) {
+        eprintln!("couldn't write {}: {e}", out.display());
+        exit(1);
+    }
+}
@@ -0,0 +1,129 @@
+//! Renders a single frame of a map without opening a window.
+//!
+//! ```text
+//! render_frame <map> [--spawn name] [--pos x y] [--yaw radians] [--size width height]
+//!              [--out frame.png] [--automap]
+//! ```
+//!
+//! The map can be a text map or one compiled by `mapc`. The camera starts at the map's spawn,
+//! or the named one given with `--spawn`, and `--pos` and `--yaw` move it from there.
+//! `--automap` draws the top-down map instead, showing the walls the camera can see from there.
+//! Frames ending in `.ppm` are saved as PPM, anything else as PNG.
+
+use std::path::PathBuf;
+use std::process::exit;
+
+use shared::{GameState, PartitionChooser, Vec2, load_any_map_from_path};
+
+const USAGE: &str = "usage: render_frame <map> [--spawn name] [--pos x y] [--yaw radians] \
+     [--size width height] [--out frame.png] [--automap]";
+
+struct Options {
+    map: PathBuf,
+    spawn: Option<String>,
+    pos: Option<Vec2>,
+    yaw: Option<f32>,
+    size: (u32, u32),
+    out: PathBuf,
+    automap: bool,
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut map = None;
+    let mut options = Options {
+        map: PathBuf::new(),
+        spawn: None,
+        pos: None,
+        yaw: None,
+        size: (640, 480),
+        out: PathBuf::from("frame.png"),
+        automap: false,
+    };
+
+    fn value<T: std::str::FromStr>(
+        flag: &str,
+        args: &mut impl Iterator<Item = String>,
+    ) -> Result<T, String> {
+        let arg = args
+            .next()
+            .ok_or_else(|| format!("`{flag}` is missing a value"))?;
+        arg.parse()
+            .map_err(|_| format!("`{arg}` isn't a valid value for `{flag}`"))
+    }
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--pos" => {
+                let x = value(&arg, &mut args)?;
+                let y = value(&arg, &mut args)?;
+                options.pos = Some(Vec2::new(x, y));
+            }
+            "--spawn" => options.spawn = Some(value(&arg, &mut args)?),
+            "--yaw" => options.yaw = Some(value(&arg, &mut args)?),
+            "--size" => {
+                let width: u32 = value(&arg, &mut args)?;
+                let height: u32 = value(&arg, &mut args)?;
+                if width == 0 || height == 0 {
+                    return Err("the frame can't be empty".to_string());
+                }
+                options.size = (width, height);
+            }
+            "--out" => options.out = value(&arg, &mut args)?,
+            "--automap" => options.automap = true,
+            "-h" | "--help" => {
+                println!("{USAGE}");
+                exit(0);
+            }
+            flag if flag.starts_with("--") => return Err(format!("unknown option `{flag}`")),
+            _ if map.is_none() => map = Some(PathBuf::from(arg)),
+            _ => return Err(format!("unexpected argument `{arg}`")),
+        }
+    }
+
+    options.map = map.ok_or("no map given")?;
+    Ok(options)
+}
+
+fn main() {
+    let options = parse_args(std::env::args().skip(1)).unwrap_or_else(|e| {
+        eprintln!("{e}\n{USAGE}");
+        exit(2);
+    });
+
+    let loaded = load_any_map_from_path(&options.map, None).unwrap_or_else(|e| {
+        eprintln!("{e}");
+        exit(1);
+    });
+    for warning in &loaded.warnings {
+        eprintln!("{}: {warning}", options.map.display());
+    }
+    let level = loaded.into_level(&PartitionChooser::default());
+    let mut game_state = GameState::from_compiled(level);
+    if let Err(e) = game_state.load_textures() {
+        eprintln!("{e}");
+    }
+    if let Some(name) = &options.spawn
+        && !game_state.teleport(name)
+    {
+        eprintln!("{} has no spawn called `{name}`", options.map.display());
+        exit(1);
+    }
+    if options.pos.is_some() || options.yaw.is_some() {
+        let camera = game_state.camera();
+        let pos = options.pos.unwrap_or(camera.pos.into());
+        let yaw = options.yaw.unwrap_or(camera.yaw);
+        game_state.place_camera(pos, yaw);
+    }
+
+    let (width, height) = options.size;
+    let mut frame = game_state.render_offscreen(width, height);
+    if options.automap {
+        // the automap only knows about walls that have been drawn, which the frame just did
+        game_state.toggle_automap();
+        frame = game_state.render_offscreen(width, height);
+    }
+    if let Err(e) = frame.save(&options.out) {
+        eprintln!("couldn't write {}: {e}", options.out.display());
+        exit(1);
+    }
+}
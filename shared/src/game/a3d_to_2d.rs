@@ -2,6 +2,7 @@
 //! HOW DOES IT WORK??? FUCK IF I KNOW
 //! CLIP SPACE OR SMTH IG
 
+use super::drawing::{LitVertex, TexturedVertex};
 use super::vecs::Vec2;
 use nalgebra_glm as glm;
 
@@ -9,6 +10,16 @@ use nalgebra_glm as glm;
 #[derive(Clone, Debug)]
 struct Vertex {
     pos: glm::Vec4, // clip-space position
+    uv: glm::Vec2,  // texture coords, just along for the ride
+}
+
+impl Vertex {
+    fn lerp(&self, other: &Vertex, t: f32) -> Vertex {
+        Vertex {
+            pos: self.pos + (other.pos - self.pos) * t,
+            uv: self.uv + (other.uv - self.uv) * t,
+        }
+    }
 }
 
 /// Clip polygon against a single frustum plane
@@ -42,20 +53,14 @@ fn clip_against_plane(
                 let t = (compare * prev.pos.w - prev.pos[component])
                     / ((current.pos[component] - prev.pos[component])
                         - compare * (current.pos.w - prev.pos.w));
-                let intersection = Vertex {
-                    pos: prev.pos + (current.pos - prev.pos) * t,
-                };
-                output.push(intersection);
+                output.push(prev.lerp(current, t));
             }
             output.push(current.clone());
         } else if prev_inside {
             let t = (compare * prev.pos.w - prev.pos[component])
                 / ((current.pos[component] - prev.pos[component])
                     - compare * (current.pos.w - prev.pos.w));
-            let intersection = Vertex {
-                pos: prev.pos + (current.pos - prev.pos) * t,
-            };
-            output.push(intersection);
+            output.push(prev.lerp(current, t));
         }
     }
 
@@ -98,53 +103,92 @@ pub fn project_point(point: glm::Vec3, cam: &super::cam::Camera, proj: glm::Mat4
     Some(ndc_to_screen(ndc_x, ndc_y, screen_width, screen_height))
 }
 
-/// Clip and project polygon to screen space
-///
-/// Essentially takes a 3d polygon n gives u the 2d coords for drawing
-///
-/// the CORE of the core
-pub fn clip_and_project_polygon(
-    world_vertices: &[glm::Vec3],
+/// Transforms to clip space and clips to the view, leaving out the divide
+fn clip_to_view(
+    world_vertices: impl Iterator<Item = (glm::Vec3, glm::Vec2)>,
     cam: &super::cam::Camera,
     proj: glm::Mat4,
-    screen_width: f32,
-    screen_height: f32,
-) -> Vec<Vec2> {
-    
+) -> Vec<Vertex> {
     // Transform to clip space
-    let mut clip_vertices: Vec<Vertex> = world_vertices
-        .iter()
-        .map(|p| {
-            let view = cam.look_matrix();
-            let clip = proj * view * glm::vec4(p.x, p.y, p.z, 1.0);
-            Vertex { pos: clip }
+    let view_proj = proj * cam.look_matrix();
+    let clip_vertices: Vec<Vertex> = world_vertices
+        .map(|(p, uv)| {
+            let clip = view_proj * glm::vec4(p.x, p.y, p.z, 1.0);
+            Vertex { pos: clip, uv }
         })
         .collect();
-    
+
     // if all are behind the camera, return empty
     if clip_vertices.iter().all(|v| v.pos.z < 0.0) {
         return Vec::new();
     }
 
     // Clip polygon
-    clip_vertices = clip_polygon(&clip_vertices);
+    clip_polygon(&clip_vertices)
+}
+
+/// Clip and project polygon to screen space
+///
+/// Essentially takes a 3d polygon n gives u the 2d coords for drawing, along with the 1/w
+/// lighting needs to tell how far away it is
+///
+/// the CORE of the core
+pub fn clip_and_project_polygon(
+    world_vertices: &[glm::Vec3],
+    cam: &super::cam::Camera,
+    proj: glm::Mat4,
+    screen_width: f32,
+    screen_height: f32,
+) -> Vec<LitVertex> {
+    let untextured = world_vertices.iter().map(|p| (*p, glm::Vec2::zeros()));
 
     // Perspective divide and map to screen
-    clip_vertices
+    clip_to_view(untextured, cam, proj)
+        .iter()
+        .map(|v| {
+            let inv_w = 1.0 / v.pos.w;
+            let screen = ndc_to_screen(v.pos.x * inv_w, v.pos.y * inv_w, screen_width, screen_height);
+            LitVertex {
+                pos: screen.into(),
+                inv_w,
+            }
+        })
+        .collect()
+}
+
+/// Same as [`clip_and_project_polygon`], but with a texture coordinate on every point. The uvs
+/// get clipped along with everything else, and come out with the 1/w needed to interpolate them
+/// with perspective.
+pub fn clip_and_project_textured(
+    world_vertices: &[(glm::Vec3, glm::Vec2)],
+    cam: &super::cam::Camera,
+    proj: glm::Mat4,
+    screen_width: f32,
+    screen_height: f32,
+) -> Vec<TexturedVertex> {
+    clip_to_view(world_vertices.iter().copied(), cam, proj)
         .iter()
         .map(|v| {
-            let ndc_x = v.pos.x / v.pos.w;
-            let ndc_y = v.pos.y / v.pos.w;
-            ndc_to_screen(ndc_x, ndc_y, screen_width, screen_height)
+            let inv_w = 1.0 / v.pos.w;
+            let screen = ndc_to_screen(v.pos.x * inv_w, v.pos.y * inv_w, screen_width, screen_height);
+            TexturedVertex {
+                pos: screen.into(),
+                u: v.uv.x,
+                v: v.uv.y,
+                inv_w,
+            }
         })
         .collect()
 }
 
 /// Convert NDC to screen space
 /// i actually understand this one! :D
+///
+/// x gets flipped so maps don't come out mirrored (map y is world z, which makes the world
+/// left handed from up top), and y because NDC goes up and the screen goes down.
 pub fn ndc_to_screen(ndc_x: f32, ndc_y: f32, width: f32, height: f32) -> Vec2 {
     Vec2 {
         x: (-ndc_x + 1.0) * 0.5 * width,
-        y: (ndc_y + 1.0) * 0.5 * height,
+        y: (1.0 - ndc_y) * 0.5 * height,
     }
 }
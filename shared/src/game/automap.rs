@@ -0,0 +1,313 @@
+//! the top-down map, for finding your way around a level
+//!
+//! North (+y) is up and the player's in the middle, drawn as an arrow pointing the way they're
+//! facing. Only walls that have made it onto the screen show up, so the map fills in as the
+//! level gets explored. Walls you can see through (steps and openings between sectors) are drawn
+//! dimmer than solid ones.
+
+use mint::Point2;
+
+use super::GameState;
+use super::colors;
+use super::contexts::GraphicsContext;
+use super::drawing::Drawer;
+use super::input::{Action, Input};
+use super::vecs::Vec2;
+
+/// how many map units fit across the shorter side of the screen before zooming
+const VIEW_SPAN: f32 = 40.0;
+const MIN_ZOOM: f32 = 0.25;
+const MAX_ZOOM: f32 = 8.0;
+/// how many times bigger the map gets each second zooming in's held
+const ZOOM_SPEED: f32 = 2.0;
+/// how far the map moves a second while panning, in map units at the starting zoom
+const PAN_SPEED: f32 = 20.0;
+
+const BACKGROUND: (u8, u8, u8, u8) = (16, 16, 16, 255);
+const PLAYER_COLOR: (u8, u8, u8, u8) = (240, 240, 240, 255);
+/// how thick walls are drawn, in pixels
+const LINE_WIDTH: f32 = 2.0;
+
+/// Whether the automap is up, where it's looking, and which walls it knows about.
+pub(super) struct Automap {
+    open: bool,
+    /// how much bigger than the default everything's drawn
+    zoom: f32,
+    /// how far the middle of the screen is from the player, in map units
+    pan: Vec2,
+    /// one for each of the map's walls, for whether it's been on screen yet
+    seen: Vec<bool>,
+    /// whether toggling was held last frame, so holding it doesn't flip the map every frame
+    toggle_held: bool,
+}
+
+impl Automap {
+    pub(super) fn new(walls: usize) -> Self {
+        Automap {
+            open: false,
+            zoom: 1.0,
+            pan: Vec2::default(),
+            seen: vec![false; walls],
+            toggle_held: false,
+        }
+    }
+
+    pub(super) fn is_open(&self) -> bool {
+        self.open
+    }
+
+    /// Opens or closes the map. It always opens centred on the player.
+    pub(super) fn toggle(&mut self) {
+        self.open = !self.open;
+        self.pan = Vec2::default();
+    }
+
+    pub(super) fn see(&mut self, wall: usize) {
+        self.seen[wall] = true;
+    }
+
+    pub(super) fn has_seen(&self, wall: usize) -> bool {
+        self.seen[wall]
+    }
+
+    /// Flips the map when [`Action::ToggleMap`] starts being held. Returns whether the map's
+    /// open afterwards.
+    pub(super) fn update_toggle(&mut self, input: &Input) -> bool {
+        let toggle = input.is_held(Action::ToggleMap);
+        if toggle && !self.toggle_held {
+            self.toggle();
+        }
+        self.toggle_held = toggle;
+        self.open
+    }
+
+    /// While the map's open walking and turning pan it, north and south for forward and back and
+    /// east and west for right and left.
+    pub(super) fn update(&mut self, delta: f32, input: &Input) {
+        if input.is_held(Action::MapZoomIn) {
+            self.zoom *= ZOOM_SPEED.powf(delta);
+        }
+        if input.is_held(Action::MapZoomOut) {
+            self.zoom /= ZOOM_SPEED.powf(delta);
+        }
+        self.zoom = self.zoom.clamp(MIN_ZOOM, MAX_ZOOM);
+
+        // panning goes at the same speed on screen whatever the zoom
+        let step = PAN_SPEED / self.zoom * delta;
+        let mut direction = input.movement();
+        if input.is_held(Action::TurnLeft) {
+            direction.x -= 1.0;
+        }
+        if input.is_held(Action::TurnRight) {
+            direction.x += 1.0;
+        }
+        self.pan = self.pan + direction.normalize() * direction.length().min(1.0) * step;
+    }
+}
+
+/// Draws the automap over the whole screen.
+pub(super) fn draw_automap<T: Drawer>(
+    game_state: &GameState,
+    graphics_ctx: &mut GraphicsContext<T>,
+) {
+    let automap = &game_state.automap;
+    let (width, height) = (graphics_ctx.width as f32, graphics_ctx.height as f32);
+    let drawer = &mut *graphics_ctx.drawer;
+    let point = |x, y| Point2 { x, y };
+    drawer.draw_polygon(
+        &[
+            point(0.0, 0.0),
+            point(width, 0.0),
+            point(width, height),
+            point(0.0, height),
+        ],
+        BACKGROUND,
+    );
+
+    // pixels per map unit
+    let scale = width.min(height) / VIEW_SPAN * automap.zoom;
+    let player = Vec2::from(game_state.cam.pos);
+    let focus = player + automap.pan;
+    let to_screen = |p: Vec2| {
+        point(
+            width / 2.0 + (p.x - focus.x) * scale,
+            height / 2.0 - (p.y - focus.y) * scale,
+        )
+    };
+
+    for (i, wall) in game_state.map.walls.iter().enumerate() {
+        if !automap.has_seen(i) {
+            continue;
+        }
+        let Some((start, end)) = clip_to_screen(
+            to_screen(wall.line.start),
+            to_screen(wall.line.end),
+            width,
+            height,
+        ) else {
+            continue;
+        };
+        let mut color = colors::wall_color(&game_state.map, i);
+        if game_state.sides[i].is_two_sided() {
+            color = colors::shade(color, 0.5);
+        }
+        drawer.draw_line(start, end, LINE_WIDTH, (color.0, color.1, color.2, 255));
+    }
+
+    // a triangle the same size whatever the zoom, pointing the way the player's facing. It has
+    // to stay convex, which is all backends can be trusted to fill
+    let at = to_screen(player);
+    let forward = (game_state.cam.yaw.sin(), -game_state.cam.yaw.cos());
+    let across = (-forward.1, forward.0);
+    let corner = |ahead: f32, side: f32| {
+        point(
+            at.x + forward.0 * ahead + across.0 * side,
+            at.y + forward.1 * ahead + across.1 * side,
+        )
+    };
+    drawer.draw_polygon(
+        &[corner(10.0, 0.0), corner(-6.0, 6.0), corner(-6.0, -6.0)],
+        PLAYER_COLOR,
+    );
+}
+
+/// Cuts a line down to the part that's on screen (give or take its thickness), so backends
+/// don't get handed walls thousands of pixels long when zoomed in.
+fn clip_to_screen(
+    start: Point2<f32>,
+    end: Point2<f32>,
+    width: f32,
+    height: f32,
+) -> Option<(Point2<f32>, Point2<f32>)> {
+    let margin = LINE_WIDTH;
+    let (dx, dy) = (end.x - start.x, end.y - start.y);
+    let (mut t0, mut t1) = (0.0_f32, 1.0_f32);
+    // each edge as how far along the line moves towards it and how far inside it the start is
+    let edges = [
+        (-dx, start.x + margin),
+        (dx, width + margin - start.x),
+        (-dy, start.y + margin),
+        (dy, height + margin - start.y),
+    ];
+    for (towards, inside) in edges {
+        if towards == 0.0 {
+            if inside < 0.0 {
+                return None;
+            }
+            continue;
+        }
+        let t = inside / towards;
+        if towards < 0.0 {
+            t0 = t0.max(t);
+        } else {
+            t1 = t1.min(t);
+        }
+    }
+    if t0 > t1 {
+        return None;
+    }
+    let along = |t: f32| Point2 {
+        x: start.x + dx * t,
+        y: start.y + dy * t,
+    };
+    Some((along(t0), along(t1)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::compiled::load_any_map_from_path;
+
+    fn maze() -> GameState {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/../maze.txt");
+        GameState::from_map(load_any_map_from_path(path, None).unwrap().map)
+    }
+
+    fn toggling(held: bool) -> Input {
+        let mut input = Input::default();
+        if held {
+            input.press(Action::ToggleMap);
+        }
+        input
+    }
+
+    #[test]
+    fn test_only_walls_on_screen_are_seen() {
+        let mut game_state = maze();
+        assert!(game_state.automap.seen.iter().all(|seen| !seen));
+
+        // from the spawn in the corner, looking north up the west side
+        game_state.place_camera(Vec2::new(2.0, 2.0), 0.0);
+        game_state.render_offscreen(64, 48);
+        let seen = |game_state: &GameState, wall| game_state.automap.has_seen(wall);
+        // the north wall's in front, and the south wall's behind
+        assert!(seen(&game_state, 2));
+        assert!(!seen(&game_state, 0));
+
+        // turning round finds it, and the north wall stays seen
+        game_state.place_camera(Vec2::new(2.0, 2.0), std::f32::consts::PI);
+        game_state.render_offscreen(64, 48);
+        assert!(seen(&game_state, 0));
+        assert!(seen(&game_state, 2));
+    }
+
+    #[test]
+    fn test_toggle_waits_for_the_key_to_come_up() {
+        let mut game_state = maze();
+        let start = game_state.cam.pos;
+        let mut held = toggling(true);
+        held.press(Action::MoveForward);
+        game_state.update(0.5, &held);
+        assert!(game_state.automap.is_open());
+        // moving pans the map rather than walking
+        assert_eq!(game_state.cam.pos, start);
+        assert!(game_state.automap.pan.y > 0.0);
+
+        game_state.update(0.5, &toggling(true));
+        assert!(game_state.automap.is_open());
+        game_state.update(0.5, &toggling(false));
+        game_state.update(0.5, &toggling(true));
+        assert!(!game_state.automap.is_open());
+
+        // and it comes back centred on the player
+        game_state.update(0.5, &toggling(false));
+        game_state.update(0.5, &toggling(true));
+        assert_eq!(game_state.automap.pan, Vec2::default());
+    }
+
+    #[test]
+    fn test_draws_seen_walls_and_the_player() {
+        let mut game_state = maze();
+        game_state.place_camera(Vec2::new(2.0, 2.0), 0.0);
+        game_state.toggle_automap();
+
+        // nothing's been seen, so there's just the player in the middle
+        let frame = game_state.render_offscreen(80, 80);
+        assert_eq!(frame.pixel(40, 38), Some(PLAYER_COLOR));
+        let background = frame
+            .pixels()
+            .chunks_exact(4)
+            .filter(|p| *p == [16, 16, 16, 255]);
+        assert!(background.count() > 80 * 80 - 100);
+
+        game_state.toggle_automap();
+        game_state.render_offscreen(64, 48);
+        game_state.toggle_automap();
+        let frame = game_state.render_offscreen(80, 80);
+        // the north wall is 18 units up, at 2 pixels a unit
+        let (r, g, b) = colors::wall_color(&game_state.map, 2);
+        assert_eq!(frame.pixel(60, 4), Some((r, g, b, 255)));
+        // the south wall still isn't there, 2 units down
+        assert_eq!(frame.pixel(60, 44), Some(BACKGROUND));
+    }
+
+    #[test]
+    fn test_clips_lines_to_the_screen() {
+        let point = |x, y| Point2 { x, y };
+        let (start, end) =
+            clip_to_screen(point(-100.0, 50.0), point(200.0, 50.0), 100.0, 100.0).unwrap();
+        assert!((start.x + LINE_WIDTH).abs() < 1e-4);
+        assert!((end.x - 100.0 - LINE_WIDTH).abs() < 1e-4);
+        assert!(clip_to_screen(point(-100.0, -50.0), point(200.0, -50.0), 100.0, 100.0).is_none());
+    }
+}
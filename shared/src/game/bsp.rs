@@ -1,10 +1,151 @@
+use std::fmt;
+use std::io::{self, Write};
+use std::ops::ControlFlow;
+
+use super::cam::Camera;
+use super::compiled::{CompiledMapError, Decoder, Encoder};
+use super::culling::{BoundingBox, ViewCone};
 use super::lines::{LineSegment, Order, split_line};
+use super::map::Map;
 use super::vecs::Vec2;
 
-pub struct BSPNode {
-    partition: LineSegment,
-    front: Option<Box<BSPNode>>,
-    back: Option<Box<BSPNode>>,
+/// A piece of a wall that ended up in the tree. Walls that cross a partition get split, so a wall
+/// can turn into several of these.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Seg {
+    pub line: LineSegment,
+    /// index of the wall in the map this came from
+    pub wall: usize,
+}
+
+/// How the tree picks which seg to split the rest by at each node.
+///
+/// Each candidate gets a cost of `split_weight` for every seg it would cut in two, plus
+/// `balance_weight` for every seg of difference between the two sides, and the cheapest wins.
+/// Fewer splits means fewer segs to draw, better balance means a shallower tree.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PartitionChooser {
+    pub split_weight: f32,
+    pub balance_weight: f32,
+    /// How many segs to try at each node. Nodes with more segs than this try an evenly spread
+    /// sample of them, so building stays quick on big levels. 1 always takes the first seg.
+    pub max_candidates: usize,
+}
+
+impl PartitionChooser {
+    /// Always splits by the first seg left, which is fast to build but can make a lopsided tree.
+    pub const FIRST: PartitionChooser = PartitionChooser {
+        split_weight: 0.0,
+        balance_weight: 0.0,
+        max_candidates: 1,
+    };
+
+    /// the index of the seg to split by
+    fn choose(&self, partitions: &[Seg]) -> usize {
+        let count = partitions.len();
+        let candidates = self.max_candidates.clamp(1, count);
+        if candidates == 1 {
+            return 0;
+        }
+        (0..candidates)
+            .map(|i| i * count / candidates)
+            .map(|i| (i, self.cost(partitions, i)))
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map_or(0, |(i, _)| i)
+    }
+
+    fn cost(&self, partitions: &[Seg], candidate: usize) -> f32 {
+        let partition = normalize_partition(partitions[candidate]);
+        let (mut front, mut back, mut splits) = (0, 0, 0);
+        for (i, part) in partitions.iter().enumerate() {
+            if i == candidate {
+                continue;
+            }
+            match classify_segment(partition, normalize_partition(*part)) {
+                (Some(_), Some(_)) => splits += 1,
+                (Some(_), None) => front += 1,
+                (None, Some(_)) => back += 1,
+                (None, None) => {}
+            }
+        }
+        self.split_weight * splits as f32 + self.balance_weight * (front as f32 - back as f32).abs()
+    }
+}
+
+impl Default for PartitionChooser {
+    fn default() -> Self {
+        PartitionChooser {
+            split_weight: 4.0,
+            balance_weight: 1.0,
+            max_candidates: 32,
+        }
+    }
+}
+
+/// How a tree came out, for tuning [`PartitionChooser`].
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct BuildStats {
+    /// nodes in the tree, which is also how many segs there are to draw, since every seg
+    /// (or piece of one) ends up as some node's partition
+    pub nodes: usize,
+    /// leaves, including ones squashed down to nothing
+    pub leaves: usize,
+    /// the most nodes on the way down to any leaf
+    pub depth: usize,
+    /// how many times a seg got cut in two by a partition
+    pub splits: usize,
+}
+
+impl fmt::Display for BuildStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} nodes ({} splits), {} leaves, depth {}",
+            self.nodes, self.splits, self.leaves, self.depth
+        )
+    }
+}
+
+// what building the tree needs at every node
+struct Builder<'a> {
+    sector_at: &'a dyn Fn(Vec2) -> Option<usize>,
+    chooser: &'a PartitionChooser,
+    stats: BuildStats,
+}
+
+/// The map's walls cut up into a binary space partition, for going through them in order of
+/// distance from any point.
+///
+/// Nodes and leaves live in two flat lists and point at each other by index, so going through
+/// the tree every frame takes one allocation for its stack rather than chasing a box per node.
+pub struct BSPTree {
+    nodes: Vec<Node>,
+    leaves: Vec<Leaf>,
+    root: Child,
+    /// the most nodes on the way down to any leaf
+    depth: usize,
+}
+
+/// something waiting on the stack while walking the tree
+#[derive(Debug, Clone, Copy)]
+enum Step {
+    Child(Child),
+    /// a node's partition, visited between its two sides
+    Seg(usize),
+}
+
+struct Node {
+    partition: Seg,
+    /// around everything in this node, segs and leaves both
+    bounds: BoundingBox,
+    front: Child,
+    back: Child,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Child {
+    Node(usize),
+    Leaf(usize),
 }
 
 const EPSILON: f32 = 1e-5;
@@ -12,11 +153,10 @@ fn almost_equal(a: f32, b: f32) -> bool {
     (a - b).abs() < EPSILON
 }
 
-fn normalize_partition(mut partition: LineSegment) -> LineSegment {
-    if partition.start.x > partition.end.x
-        || (partition.start.x == partition.end.x && partition.start.y > partition.end.y)
-    {
-        std::mem::swap(&mut partition.start, &mut partition.end);
+fn normalize_partition(mut partition: Seg) -> Seg {
+    let line = &mut partition.line;
+    if line.start.x > line.end.x || (line.start.x == line.end.x && line.start.y > line.end.y) {
+        std::mem::swap(&mut line.start, &mut line.end);
     }
     partition
 }
@@ -37,12 +177,9 @@ fn bsp_point_side_of_line(line: &LineSegment, point: &Vec2) -> Order {
 fn is_degenerate(seg: &LineSegment) -> bool {
     almost_equal(seg.start.x, seg.end.x) && almost_equal(seg.start.y, seg.end.y)
 }
-fn classify_segment(
-    partition: LineSegment,
-    segment: LineSegment,
-) -> (Option<LineSegment>, Option<LineSegment>) {
-    let mut start_side = bsp_point_side_of_line(&partition, &segment.start);
-    let mut end_side = bsp_point_side_of_line(&partition, &segment.end);
+fn classify_segment(partition: Seg, segment: Seg) -> (Option<Seg>, Option<Seg>) {
+    let mut start_side = bsp_point_side_of_line(&partition.line, &segment.line.start);
+    let mut end_side = bsp_point_side_of_line(&partition.line, &segment.line.end);
 
     // If one endpoint is On, treat it as being on the same side as the other endpoint.
     // This avoids unnecessary splits into zero-length pieces.
@@ -61,17 +198,23 @@ fn classify_segment(
         (Order::Right, Order::Right) => (None, Some(segment)),
         // different sides -> actually split
         _ => {
-            if let Some((left, right)) = split_line(&partition, &segment) {
+            if let Some((left, right)) = split_line(&partition.line, &segment.line) {
+                // the first half of the split is whichever side the start was on
+                let (left, right) = if start_side == Order::Left {
+                    (left, right)
+                } else {
+                    (right, left)
+                };
                 // Guard against degenerate zero-length pieces produced by split_line
                 let left_opt = if is_degenerate(&left) {
                     None
                 } else {
-                    Some(left)
+                    Some(Seg { line: left, ..segment })
                 };
                 let right_opt = if is_degenerate(&right) {
                     None
                 } else {
-                    Some(right)
+                    Some(Seg { line: right, ..segment })
                 };
                 (left_opt, right_opt)
             } else {
@@ -82,19 +225,127 @@ fn classify_segment(
     }
 }
 
-impl BSPNode {
-    pub fn new(partitions: Vec<LineSegment>) -> Self {
+// splits a convex region along the infinite line through `line`, giving the (left, right) halves
+fn split_region(region: &[Vec2], line: &LineSegment) -> (Vec<Vec2>, Vec<Vec2>) {
+    let side = |p: &Vec2| {
+        (line.end.x - line.start.x) * (p.y - line.start.y)
+            - (line.end.y - line.start.y) * (p.x - line.start.x)
+    };
+    let mut left = Vec::new();
+    let mut right = Vec::new();
+    for i in 0..region.len() {
+        let current = region[i];
+        let next = region[(i + 1) % region.len()];
+        let (d_current, d_next) = (side(&current), side(&next));
+
+        if d_current >= -EPSILON {
+            left.push(current);
+        }
+        if d_current <= EPSILON {
+            right.push(current);
+        }
+        // crossing from one side to the other, both halves get the crossing point
+        if (d_current > EPSILON && d_next < -EPSILON) || (d_current < -EPSILON && d_next > EPSILON)
+        {
+            let t = d_current / (d_current - d_next);
+            let crossing = current + (next - current) * t;
+            left.push(crossing);
+            right.push(crossing);
+        }
+    }
+    (left, right)
+}
+
+fn region_area(region: &[Vec2]) -> f32 {
+    let mut twice_area = 0.0;
+    for i in 0..region.len() {
+        let (a, b) = (region[i], region[(i + 1) % region.len()]);
+        twice_area += a.x * b.y - b.x * a.y;
+    }
+    (twice_area * 0.5).abs()
+}
+
+/// A convex bit of floor that no wall goes through, so all of it is in the same sector.
+pub struct Leaf {
+    /// the outline, empty if it got squashed down to nothing
+    pub region: Vec<Vec2>,
+    pub sector: Option<usize>,
+    /// around the outline, `None` if there isn't one
+    bounds: Option<BoundingBox>,
+}
+
+/// Something to draw, in the order the BSP wants it drawn.
+pub enum Visit<'a> {
+    Seg(Seg),
+    Leaf(&'a Leaf),
+}
+
+impl BSPTree {
+    /// Builds the tree out of every seg in the map, with `chooser` picking where to split it.
+    /// `sector_at` is used to work out which sector each leaf is in.
+    pub fn new(
+        partitions: Vec<Seg>,
+        sector_at: &dyn Fn(Vec2) -> Option<usize>,
+        chooser: &PartitionChooser,
+    ) -> (Self, BuildStats) {
+        // a box around the whole level (and then some) for the leaves to be cut out of. With no
+        // segs at all it's just the one leaf, around the origin
+        let mut points: Vec<Vec2> = partitions
+            .iter()
+            .flat_map(|seg| [seg.line.start, seg.line.end])
+            .collect();
+        if points.is_empty() {
+            points.push(Vec2::default());
+        }
+        let bounds = BoundingBox::around(points);
+        let margin = (bounds.max.x - bounds.min.x)
+            .max(bounds.max.y - bounds.min.y)
+            .max(1.0);
+        let (min, max) = (bounds.min - margin, bounds.max + margin);
+        let region = vec![min, Vec2::new(max.x, min.y), max, Vec2::new(min.x, max.y)];
+
+        let mut builder = Builder {
+            sector_at,
+            chooser,
+            stats: BuildStats::default(),
+        };
+        let mut tree = BSPTree {
+            nodes: Vec::new(),
+            leaves: Vec::new(),
+            root: Child::Leaf(0),
+            depth: 0,
+        };
+        tree.root = tree.build(partitions, region, &mut builder, 0);
+        tree.depth = builder.stats.depth;
+        (tree, builder.stats)
+    }
+
+    fn build(
+        &mut self,
+        partitions: Vec<Seg>,
+        region: Vec<Vec2>,
+        builder: &mut Builder,
+        depth: usize,
+    ) -> Child {
         if partitions.is_empty() {
-            panic!("Cannot create BSPNode with no partitions");
+            return self.add_leaf(region, builder, depth);
         }
-        let partition = normalize_partition(partitions[0]);
-        let mut front_partitions: Vec<LineSegment> = Vec::new();
-        let mut back_partitions: Vec<LineSegment> = Vec::new();
+        builder.stats.nodes += 1;
+        let chosen = builder.chooser.choose(&partitions);
+        let partition = normalize_partition(partitions[chosen]);
+        let mut front_partitions: Vec<Seg> = Vec::new();
+        let mut back_partitions: Vec<Seg> = Vec::new();
 
-        for part in partitions.iter().skip(1) {
+        for (i, part) in partitions.iter().enumerate() {
+            if i == chosen {
+                continue;
+            }
             let normalized_part = normalize_partition(*part);
 
             let (front, back) = classify_segment(partition, normalized_part);
+            if front.is_some() && back.is_some() {
+                builder.stats.splits += 1;
+            }
             if let Some(f) = front {
                 front_partitions.push(f);
             }
@@ -102,45 +353,505 @@ impl BSPNode {
                 back_partitions.push(b);
             }
         }
-        BSPNode {
+        let (front_region, back_region) = split_region(&region, &partition.line);
+
+        // the node goes in before its children so the root ends up first
+        let index = self.nodes.len();
+        self.nodes.push(Node {
             partition,
-            front: if front_partitions.is_empty() {
-                None
-            } else {
-                Some(Box::new(BSPNode::new(front_partitions)))
-            },
-            back: if back_partitions.is_empty() {
-                None
-            } else {
-                Some(Box::new(BSPNode::new(back_partitions)))
-            },
+            bounds: BoundingBox::around(region.iter().copied()),
+            front: Child::Leaf(0),
+            back: Child::Leaf(0),
+        });
+        let front = self.build(front_partitions, front_region, builder, depth + 1);
+        let back = self.build(back_partitions, back_region, builder, depth + 1);
+        self.nodes[index].front = front;
+        self.nodes[index].back = back;
+        Child::Node(index)
+    }
+
+    fn add_leaf(&mut self, region: Vec<Vec2>, builder: &mut Builder, depth: usize) -> Child {
+        builder.stats.leaves += 1;
+        builder.stats.depth = builder.stats.depth.max(depth);
+        let leaf = if region.len() < 3 || region_area(&region) < EPSILON {
+            Leaf {
+                region: Vec::new(),
+                sector: None,
+                bounds: None,
+            }
+        } else {
+            let centroid = region
+                .iter()
+                .fold(Vec2::default(), |sum, p| sum + *p)
+                / region.len() as f32;
+            Leaf {
+                sector: (builder.sector_at)(centroid),
+                bounds: Some(BoundingBox::around(region.iter().copied())),
+                region,
+            }
+        };
+        self.leaves.push(leaf);
+        Child::Leaf(self.leaves.len() - 1)
+    }
+
+    /// Goes through every seg and leaf that could be on screen, nearest to the camera first,
+    /// until `visit` breaks. Nothing visited can be hidden behind anything visited after it.
+    pub fn for_each_front_to_back<'a>(
+        &'a self,
+        cam: &Camera,
+        mut visit: impl FnMut(Visit<'a>) -> ControlFlow<()>,
+    ) -> ControlFlow<()> {
+        let cone = ViewCone::new(cam);
+        self.walk(cam.pos.into(), cone.as_ref(), true, &mut visit)
+    }
+
+    /// The same segs and leaves as [`for_each_front_to_back`](Self::for_each_front_to_back),
+    /// furthest first, for drawing over whatever's behind.
+    pub fn for_each_back_to_front<'a>(
+        &'a self,
+        cam: &Camera,
+        mut visit: impl FnMut(Visit<'a>) -> ControlFlow<()>,
+    ) -> ControlFlow<()> {
+        let cone = ViewCone::new(cam);
+        self.walk(cam.pos.into(), cone.as_ref(), false, &mut visit)
+    }
+
+    /// The most nodes on the way down to any leaf.
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Every seg that could be on screen, nearest first.
+    pub fn visible_segments(&self, cam: &Camera) -> Vec<Seg> {
+        let mut segs = Vec::new();
+        let _ = self.for_each_front_to_back(cam, |visit| {
+            if let Visit::Seg(seg) = visit {
+                segs.push(seg);
+            }
+            ControlFlow::Continue(())
+        });
+        segs
+    }
+
+    /// Every node's partition line, cut down to the part of the level that node covers, along
+    /// with the seg it came from. Every seg in the tree is the partition of exactly one node, so
+    /// this is every seg too. Parents come before their children.
+    pub fn partition_lines(&self) -> Vec<(Seg, LineSegment)> {
+        let mut lines = Vec::new();
+        let Child::Node(root) = self.root else {
+            return lines;
+        };
+        // each node with the part of the level's bounds it covers, fronts on top so they come first
+        let mut stack = Vec::with_capacity(self.depth + 1);
+        stack.push((root, self.nodes[root].bounds.corners().to_vec()));
+        while let Some((index, region)) = stack.pop() {
+            let node = &self.nodes[index];
+            let line = node.partition.line;
+            let (front, back) = split_region(&region, &line);
+            // where the halves meet is the part of the line that's inside the region
+            let direction = line.end - line.start;
+            let along = |p: &&Vec2| (**p - line.start).dot(&direction);
+            let shared = || front.iter().filter(|p| back.contains(p));
+            if let (Some(start), Some(end)) = (
+                shared().min_by(|a, b| along(a).total_cmp(&along(b))),
+                shared().max_by(|a, b| along(a).total_cmp(&along(b))),
+            ) {
+                lines.push((node.partition, LineSegment::new(*start, *end)));
+            }
+            if let Child::Node(back_index) = node.back {
+                stack.push((back_index, back));
+            }
+            if let Child::Node(front_index) = node.front {
+                stack.push((front_index, front));
+            }
         }
+        lines
     }
-    pub fn order(&self, position: Vec2) -> Vec<LineSegment> {
-        let side = bsp_point_side_of_line(&self.partition, &position);
-        let mut ordered_segments = Vec::new();
-        match side {
-            Order::Left | Order::On => {
-                if let Some(back_node) = &self.back {
-                    ordered_segments.extend(back_node.order(position));
-                }
-                ordered_segments.push(self.partition);
 
-                if let Some(front_node) = &self.front {
-                    ordered_segments.extend(front_node.order(position));
-                }
+    /// Writes the leaves, then the nodes, then which of them is the root.
+    pub(super) fn encode(&self, encoder: &mut Encoder<impl Write>) -> io::Result<()> {
+        encoder.u32(self.leaves.len())?;
+        for leaf in &self.leaves {
+            encoder.option(leaf.sector, Encoder::u32)?;
+            encoder.u32(leaf.region.len())?;
+            for &point in &leaf.region {
+                encoder.point(point)?;
+            }
+        }
+        encoder.u32(self.nodes.len())?;
+        for node in &self.nodes {
+            encoder.line(node.partition.line)?;
+            encoder.u32(node.partition.wall)?;
+            encoder.point(node.bounds.min)?;
+            encoder.point(node.bounds.max)?;
+            encode_child(encoder, node.front)?;
+            encode_child(encoder, node.back)?;
+        }
+        encode_child(encoder, self.root)
+    }
+
+    /// Reads a tree written by [`encode`](Self::encode), checking it's really a tree over
+    /// `map`'s walls and sectors.
+    pub(super) fn decode(
+        decoder: &mut Decoder,
+        map: &Map,
+    ) -> Result<Self, CompiledMapError> {
+        // lists' lengths get checked against the smallest their items can be, like in the map
+        let mut leaves = Vec::new();
+        for _ in 0..decoder.count(1 + 4)? {
+            let sector =
+                decoder.option(|d| d.index(map.sectors.len(), "leaf sector out of range"))?;
+            let mut region = Vec::new();
+            for _ in 0..decoder.count(8)? {
+                region.push(decoder.point()?);
             }
-            Order::Right => {
-                if let Some(front_node) = &self.front {
-                    ordered_segments.extend(front_node.order(position));
+            let bounds = (!region.is_empty()).then(|| BoundingBox::around(region.iter().copied()));
+            leaves.push(Leaf {
+                region,
+                sector,
+                bounds,
+            });
+        }
+
+        let node_count = decoder.count(16 + 4 + 16 + 5 + 5)?;
+        let mut used = Used {
+            nodes: vec![false; node_count],
+            leaves: vec![false; leaves.len()],
+        };
+        let mut nodes = Vec::new();
+        for index in 0..node_count {
+            let line = decoder.line()?;
+            let wall = decoder.index(map.walls.len(), "seg wall out of range")?;
+            let bounds = BoundingBox {
+                min: decoder.point()?,
+                max: decoder.point()?,
+            };
+            // children always come after their parent, so following them can't loop forever
+            let front = decode_child(decoder, &mut used, index + 1)?;
+            let back = decode_child(decoder, &mut used, index + 1)?;
+            nodes.push(Node {
+                partition: Seg { line, wall },
+                bounds,
+                front,
+                back,
+            });
+        }
+        let root = decode_child(decoder, &mut used, 0)?;
+        if used.nodes.contains(&false) || used.leaves.contains(&false) {
+            return Err(CompiledMapError::Invalid("tree has unreachable nodes"));
+        }
+
+        // every node has one parent that comes before it, so a pass front to back reaches each
+        // one after its parent
+        let mut node_depths = vec![0; nodes.len()];
+        let mut depth = 0;
+        for (index, node) in nodes.iter().enumerate() {
+            for child in [node.front, node.back] {
+                match child {
+                    Child::Node(child) => node_depths[child] = node_depths[index] + 1,
+                    Child::Leaf(_) => depth = depth.max(node_depths[index] + 1),
                 }
-                ordered_segments.push(self.partition);
+            }
+        }
+        Ok(BSPTree {
+            nodes,
+            leaves,
+            root,
+            depth,
+        })
+    }
 
-                if let Some(back_node) = &self.back {
-                    ordered_segments.extend(back_node.order(position));
+    /// Goes through the tree with a stack of its own rather than recursing, so however deep the
+    /// tree goes can't run out the thread's stack.
+    fn walk<'a>(
+        &'a self,
+        position: Vec2,
+        cone: Option<&ViewCone>,
+        near_first: bool,
+        visit: &mut impl FnMut(Visit<'a>) -> ControlFlow<()>,
+    ) -> ControlFlow<()> {
+        // each node down the way leaves its partition and far side behind
+        let mut stack = Vec::with_capacity(2 * self.depth + 1);
+        stack.push(Step::Child(self.root));
+        while let Some(step) = stack.pop() {
+            let seen = match step {
+                Step::Seg(index) => {
+                    let partition = self.nodes[index].partition;
+                    let line = partition.line;
+                    let bounds = BoundingBox::around([line.start, line.end]);
+                    cone.is_none_or(|cone| cone.sees(&bounds))
+                        .then_some(Visit::Seg(partition))
                 }
+                Step::Child(Child::Leaf(index)) => {
+                    let leaf = &self.leaves[index];
+                    cone.is_none_or(|cone| leaf.bounds.is_some_and(|b| cone.sees(&b)))
+                        .then_some(Visit::Leaf(leaf))
+                }
+                Step::Child(Child::Node(index)) => {
+                    let node = &self.nodes[index];
+                    if cone.is_none_or(|cone| cone.sees(&node.bounds)) {
+                        let (near, far) =
+                            match bsp_point_side_of_line(&node.partition.line, &position) {
+                                Order::Left | Order::On => (node.front, node.back),
+                                Order::Right => (node.back, node.front),
+                            };
+                        let (first, last) = if near_first { (near, far) } else { (far, near) };
+                        // pushed in reverse, so the first side comes off next
+                        stack.push(Step::Child(last));
+                        stack.push(Step::Seg(index));
+                        stack.push(Step::Child(first));
+                    }
+                    None
+                }
+            };
+            if let Some(seen) = seen {
+                visit(seen)?;
+            }
+        }
+        ControlFlow::Continue(())
+    }
+}
+
+// which nodes and leaves something's pointed at already while decoding
+struct Used {
+    nodes: Vec<bool>,
+    leaves: Vec<bool>,
+}
+
+fn encode_child(encoder: &mut Encoder<impl Write>, child: Child) -> io::Result<()> {
+    match child {
+        Child::Node(index) => {
+            encoder.u8(0)?;
+            encoder.u32(index)
+        }
+        Child::Leaf(index) => {
+            encoder.u8(1)?;
+            encoder.u32(index)
+        }
+    }
+}
+
+/// reads a child, which has to be a node from `first_node` on or a leaf, and nothing else's
+fn decode_child(
+    decoder: &mut Decoder,
+    used: &mut Used,
+    first_node: usize,
+) -> Result<Child, CompiledMapError> {
+    let (child, used) = match decoder.u8()? {
+        0 => {
+            let index = decoder.index(used.nodes.len(), "node out of range")?;
+            if index < first_node {
+                return Err(CompiledMapError::Invalid("node comes before its parent"));
+            }
+            (Child::Node(index), &mut used.nodes[index])
+        }
+        1 => {
+            let index = decoder.index(used.leaves.len(), "leaf out of range")?;
+            (Child::Leaf(index), &mut used.leaves[index])
+        }
+        _ => return Err(CompiledMapError::Invalid("child is neither a node nor a leaf")),
+    };
+    if std::mem::replace(used, true) {
+        return Err(CompiledMapError::Invalid("node or leaf is in the tree twice"));
+    }
+    Ok(child)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::vecs::Vec3;
+
+    fn seg(x1: f32, y1: f32, x2: f32, y2: f32, wall: usize) -> Seg {
+        Seg {
+            line: LineSegment::from((x1, y1, x2, y2)),
+            wall,
+        }
+    }
+
+    fn camera(x: f32, y: f32, yaw: f32, pitch: f32) -> Camera {
+        Camera {
+            pos: Vec3::new(x, 0.0, y),
+            fov: 80.0_f32.to_radians(),
+            yaw,
+            pitch,
+            near: 0.1,
+            aspect: 4.0 / 3.0,
+        }
+    }
+
+    fn square_room() -> Vec<Seg> {
+        vec![
+            seg(0.0, 0.0, 4.0, 0.0, 0),
+            seg(4.0, 0.0, 4.0, 4.0, 1),
+            seg(4.0, 4.0, 0.0, 4.0, 2),
+            seg(0.0, 4.0, 0.0, 0.0, 3),
+        ]
+    }
+
+    #[test]
+    fn test_leaves_cover_the_level() {
+        let segs = square_room();
+        let inside = |p: Vec2| (p.x > 0.0 && p.x < 4.0 && p.y > 0.0 && p.y < 4.0).then_some(0);
+        let bsp = BSPTree::new(segs, &inside, &PartitionChooser::FIRST).0;
+
+        // looking straight down, so nothing gets culled
+        let mut visits = Vec::new();
+        let _ = bsp.for_each_front_to_back(&camera(2.0, 2.0, 0.0, -1.5), |visit| {
+            visits.push(visit);
+            ControlFlow::Continue(())
+        });
+        let leaves: Vec<&Leaf> = visits
+            .iter()
+            .filter_map(|v| match v {
+                Visit::Leaf(leaf) if !leaf.region.is_empty() => Some(*leaf),
+                _ => None,
+            })
+            .collect();
+
+        // exactly one leaf is the room, and it comes first since we're standing in it
+        let rooms: Vec<&&Leaf> = leaves.iter().filter(|l| l.sector == Some(0)).collect();
+        assert_eq!(rooms.len(), 1);
+        assert!((region_area(&rooms[0].region) - 16.0).abs() < 1e-3);
+        assert_eq!(leaves.first().unwrap().sector, Some(0));
+
+        // and the leaves add up to the whole box around the level
+        let total: f32 = leaves.iter().map(|l| region_area(&l.region)).sum();
+        assert!((total - 12.0 * 12.0).abs() < 1e-2);
+        let segs = visits.iter().filter(|v| matches!(v, Visit::Seg(_)));
+        assert_eq!(segs.count(), 4);
+    }
+
+    #[test]
+    fn test_no_segs_is_one_leaf() {
+        let (bsp, stats) = BSPTree::new(Vec::new(), &|_| None, &PartitionChooser::default());
+        assert_eq!((stats.nodes, stats.leaves), (0, 1));
+        let mut leaves = 0;
+        let _ = bsp.for_each_front_to_back(&camera(0.0, 0.0, 0.0, 0.0), |visit| {
+            assert!(matches!(visit, Visit::Leaf(leaf) if !leaf.region.is_empty()));
+            leaves += 1;
+            ControlFlow::Continue(())
+        });
+        assert_eq!(leaves, 1);
+    }
+
+    #[test]
+    fn test_culls_what_is_behind() {
+        let bsp = BSPTree::new(square_room(), &|_| None, &PartitionChooser::FIRST).0;
+
+        // in the middle looking at the +y wall, the -y wall is behind
+        let walls = |cam: &Camera| {
+            let mut walls: Vec<usize> = bsp.visible_segments(cam).iter().map(|s| s.wall).collect();
+            walls.sort();
+            walls
+        };
+        assert_eq!(walls(&camera(2.0, 2.0, 0.0, 0.0)), vec![1, 2, 3]);
+        // right up against the -x wall looking along it, the +x wall is off to the side
+        assert_eq!(walls(&camera(0.1, 2.0, 0.0, 0.0)), vec![2, 3]);
+        assert_eq!(walls(&camera(2.0, 2.0, 0.0, -1.5)), vec![0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn test_back_to_front_is_front_to_back_reversed() {
+        let (bsp, _) = BSPTree::new(square_room(), &|_| None, &PartitionChooser::default());
+        // walls by index, leaves by where they are in memory
+        let key = |visit: Visit| match visit {
+            Visit::Seg(seg) => seg.wall,
+            Visit::Leaf(leaf) => leaf as *const Leaf as usize,
+        };
+        for cam in [camera(2.0, 2.0, 0.0, 0.0), camera(1.0, 3.0, 2.0, -1.5)] {
+            let mut front_to_back = Vec::new();
+            let _ = bsp.for_each_front_to_back(&cam, |visit| {
+                front_to_back.push(key(visit));
+                ControlFlow::Continue(())
+            });
+            let mut back_to_front = Vec::new();
+            let _ = bsp.for_each_back_to_front(&cam, |visit| {
+                back_to_front.push(key(visit));
+                ControlFlow::Continue(())
+            });
+            back_to_front.reverse();
+            assert!(!front_to_back.is_empty());
+            assert_eq!(front_to_back, back_to_front);
+        }
+    }
+
+    #[test]
+    fn test_chooser_avoids_splits_and_lopsided_trees() {
+        // a short wall lined up with the middle of a row of others, so splitting by it first
+        // cuts every other wall in two, while splitting by any of the others cuts nothing
+        let mut crossed = vec![seg(0.0, 0.0, 1.0, 0.0, 0)];
+        crossed.extend((0..9).map(|i| seg(i as f32 + 2.0, -1.0, i as f32 + 2.0, 1.0, i + 1)));
+        let (_, first) = BSPTree::new(crossed.clone(), &|_| None, &PartitionChooser::FIRST);
+        let (_, chosen) = BSPTree::new(crossed, &|_| None, &PartitionChooser::default());
+        assert_eq!(first.splits, 9);
+        assert_eq!(first.nodes, 19);
+        assert_eq!(chosen.splits, 0);
+        assert_eq!(chosen.nodes, 10);
+
+        // a row of parallel walls, in order, makes a chain when split first to last
+        let row: Vec<Seg> = (0..16)
+            .map(|i| seg(i as f32, 0.0, i as f32, 1.0, i))
+            .collect();
+        let (_, first) = BSPTree::new(row.clone(), &|_| None, &PartitionChooser::FIRST);
+        let (_, chosen) = BSPTree::new(row, &|_| None, &PartitionChooser::default());
+        assert_eq!(first.depth, 16);
+        assert!(chosen.depth <= 6, "depth {}", chosen.depth);
+        assert_eq!(chosen.leaves, chosen.nodes + 1);
+    }
+
+    #[test]
+    fn test_deep_trees_walk_without_recursing() {
+        // split first to last, a row of walls is a chain as deep as it's long
+        let row: Vec<Seg> = (0..500)
+            .map(|i| seg(i as f32, 0.0, i as f32, 1.0, i))
+            .collect();
+        let (bsp, stats) = BSPTree::new(row, &|_| None, &PartitionChooser::FIRST);
+        assert_eq!(stats.depth, 500);
+        assert_eq!(bsp.depth, stats.depth);
+
+        // from the far end looking back along the row, every wall is nearer than the one before
+        let cam = camera(500.5, 0.5, -std::f32::consts::FRAC_PI_2, 0.0);
+        let walls: Vec<usize> = bsp.visible_segments(&cam).iter().map(|s| s.wall).collect();
+        assert_eq!(walls.len(), 500);
+        assert!(walls.windows(2).all(|pair| pair[0] > pair[1]));
+
+        // and stopping early stops the walk
+        let mut visits = 0;
+        let flow = bsp.for_each_back_to_front(&cam, |_| {
+            visits += 1;
+            if visits == 10 {
+                ControlFlow::Break(())
+            } else {
+                ControlFlow::Continue(())
+            }
+        });
+        assert_eq!(flow, ControlFlow::Break(()));
+        assert_eq!(visits, 10);
+
+        // and so is listing the partitions, parents first
+        let lines = bsp.partition_lines();
+        assert_eq!(lines.len(), 500);
+        assert!(lines.iter().enumerate().all(|(i, (seg, _))| seg.wall == i));
+    }
+
+    #[test]
+    fn test_partition_lines_stay_in_their_region() {
+        let (bsp, stats) = BSPTree::new(square_room(), &|_| None, &PartitionChooser::FIRST);
+        let lines = bsp.partition_lines();
+        assert_eq!(lines.len(), stats.nodes);
+
+        // the root's goes right across the box around the level, 4 wide plus 4 either side
+        let (seg, root) = lines[0];
+        assert_eq!(seg.wall, 0);
+        assert!((root.length() - 12.0).abs() < 1e-4);
+        for (seg, line) in lines {
+            // on the seg's line, and covering all of it
+            for end in [seg.line.start, seg.line.end] {
+                let closest = line.closest_point(end);
+                assert!((closest - end).length() < 1e-4, "{seg:?} isn't on {line:?}");
             }
+            assert!(line.length() < 12.0 + 1e-4);
         }
-        ordered_segments
     }
 }
@@ -6,12 +6,18 @@ use nalgebra_glm as glm;
 use nalgebra_glm::Mat4 as GMat4;
 use nalgebra_glm::Vec3 as GVec3;
 
+/// How far up or down the camera can look, in radians. Any further and it'd be close enough to
+/// straight up that which way is forward stops making sense.
+pub const MAX_PITCH: f32 = 1.4;
+
 pub struct Camera {
     pub pos: Vec3,
     pub fov: f32,
     pub yaw: f32,
     pub pitch: f32,
     pub near: f32,
+    /// screen width over height, kept up to date by whatever's drawing
+    pub aspect: f32,
 }
 
 impl Camera {
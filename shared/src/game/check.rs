@@ -0,0 +1,460 @@
+//! Looks over a map for mistakes that load fine but come out wrong: walls that go nowhere,
+//! walls listed twice, rooms that aren't closed off, a spawn point out in the void. Nothing here
+//! changes the map, it's for `mapcheck` and anything else that wants to warn about them.
+
+use std::fmt;
+
+use super::lines::LineSegment;
+use super::map::Map;
+use super::vecs::Vec2;
+
+/// how close two points have to be to count as the same one, in map units
+const TOLERANCE: f32 = 1e-3;
+/// how many directions to look from the spawn point for a way out of the level
+const SPAWN_RAYS: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// the map will look or play wrong
+    Error,
+    /// might be on purpose, but probably isn't
+    Warning,
+}
+
+/// Where in the map a problem is. Maps loaded from a text file know which line each part was
+/// on, anything else just gets its index.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Place {
+    Wall {
+        index: usize,
+        line: Option<usize>,
+    },
+    Sector {
+        index: usize,
+        line: Option<usize>,
+    },
+    /// `index` is into [`Map::named_spawns`], `None` for the one the map starts at
+    Spawn {
+        index: Option<usize>,
+        line: Option<usize>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProblemKind {
+    /// both ends of the wall are in the same spot
+    ZeroLength,
+    /// another wall has the same ends, in either direction
+    Duplicate(Place),
+    /// another wall lies along the same line and shares part of it
+    Overlaps(Place),
+    /// the wall ends partway along another one instead of at one of its ends
+    TJunction { at: Vec2, other: Place },
+    /// the sector's walls don't make a closed outline, with a loose end here
+    Unclosed(Vec2),
+    /// the wall ends here without meeting any other wall
+    LooseEnd(Vec2),
+    /// there's a way out of the level from the spawn point
+    SpawnOutside,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Problem {
+    pub severity: Severity,
+    pub place: Place,
+    pub kind: ProblemKind,
+}
+
+impl Place {
+    /// the line of the file it was on, if the map came from a text file
+    pub fn line(&self) -> Option<usize> {
+        match *self {
+            Place::Wall { line, .. } | Place::Sector { line, .. } | Place::Spawn { line, .. } => {
+                line
+            }
+        }
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+impl fmt::Display for Place {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self, self.line()) {
+            (_, Some(line)) => write!(f, "line {line}"),
+            (Place::Wall { index, .. }, None) => write!(f, "wall {index}"),
+            (Place::Sector { index, .. }, None) => write!(f, "sector {index}"),
+            (Place::Spawn { index: None, .. }, None) => write!(f, "spawn"),
+            (
+                Place::Spawn {
+                    index: Some(index), ..
+                },
+                None,
+            ) => write!(f, "named spawn {index}"),
+        }
+    }
+}
+
+impl fmt::Display for ProblemKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProblemKind::ZeroLength => write!(f, "wall has no length"),
+            ProblemKind::Duplicate(other) => write!(f, "wall duplicates {other}"),
+            ProblemKind::Overlaps(other) => write!(f, "wall overlaps {other}"),
+            ProblemKind::TJunction { at, other } => {
+                write!(
+                    f,
+                    "wall ends at ({}, {}), partway along {other}",
+                    at.x, at.y
+                )
+            }
+            ProblemKind::Unclosed(at) => {
+                write!(
+                    f,
+                    "sector isn't closed, its outline has a loose end at ({}, {})",
+                    at.x, at.y
+                )
+            }
+            ProblemKind::LooseEnd(at) => {
+                write!(
+                    f,
+                    "wall end at ({}, {}) doesn't meet any other wall",
+                    at.x, at.y
+                )
+            }
+            ProblemKind::SpawnOutside => write!(f, "spawn point is outside the level"),
+        }
+    }
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}: {}", self.place, self.severity, self.kind)
+    }
+}
+
+fn same_point(a: Vec2, b: Vec2) -> bool {
+    (a - b).length() < TOLERANCE
+}
+
+fn cross(a: Vec2, b: Vec2) -> f32 {
+    a.x * b.y - a.y * b.x
+}
+
+/// how far along `line` a point is, 0 at the start and 1 at the end
+fn along(line: &LineSegment, point: Vec2) -> f32 {
+    let direction = line.end - line.start;
+    (point - line.start).dot(&direction) / direction.dot(&direction)
+}
+
+/// whether a point is on a wall, but not at either of its ends
+fn is_partway_along(line: &LineSegment, point: Vec2) -> bool {
+    same_point(line.closest_point(point), point)
+        && !same_point(line.start, point)
+        && !same_point(line.end, point)
+}
+
+fn is_collinear(a: &LineSegment, b: &LineSegment) -> bool {
+    let direction = (a.end - a.start).normalize();
+    cross(direction, b.start - a.start).abs() < TOLERANCE
+        && cross(direction, b.end - a.start).abs() < TOLERANCE
+}
+
+/// how much of `b` lies along `a`, in map units, assuming they're collinear
+fn shared_length(a: &LineSegment, b: &LineSegment) -> f32 {
+    let (b_start, b_end) = (along(a, b.start), along(a, b.end));
+    let overlap = b_start.max(b_end).min(1.0) - b_start.min(b_end).max(0.0);
+    overlap.max(0.0) * a.length()
+}
+
+/// points that are the end of an odd number of the given walls
+fn odd_ends<'a>(lines: impl Iterator<Item = &'a LineSegment>) -> Vec<Vec2> {
+    let mut ends: Vec<(Vec2, usize)> = Vec::new();
+    for point in lines.flat_map(|line| [line.start, line.end]) {
+        match ends.iter_mut().find(|(end, _)| same_point(*end, point)) {
+            Some((_, count)) => *count += 1,
+            None => ends.push((point, 1)),
+        }
+    }
+    ends.into_iter()
+        .filter(|(_, count)| count % 2 == 1)
+        .map(|(point, _)| point)
+        .collect()
+}
+
+/// Finds everything wrong with a map, in the order it turns up in the file.
+pub fn check_map(map: &Map) -> Vec<Problem> {
+    let lines = map.metadata.lines.as_ref();
+    let wall = |index: usize| Place::Wall {
+        index,
+        line: lines.and_then(|lines| lines.walls.get(index).copied()),
+    };
+    let mut problems = Vec::new();
+    let mut problem = |severity, place, kind| {
+        problems.push(Problem {
+            severity,
+            place,
+            kind,
+        })
+    };
+
+    // the zero-length ones are reported once and then left out of everything else
+    let walls: Vec<(usize, &LineSegment)> = map
+        .walls
+        .iter()
+        .map(|wall| &wall.line)
+        .enumerate()
+        .filter(|&(i, line)| {
+            let degenerate = same_point(line.start, line.end);
+            if degenerate {
+                problem(Severity::Error, wall(i), ProblemKind::ZeroLength);
+            }
+            !degenerate
+        })
+        .collect();
+
+    for (n, &(i, a)) in walls.iter().enumerate() {
+        for &(j, b) in &walls[..n] {
+            let duplicate = (same_point(a.start, b.start) && same_point(a.end, b.end))
+                || (same_point(a.start, b.end) && same_point(a.end, b.start));
+            if duplicate {
+                problem(Severity::Error, wall(i), ProblemKind::Duplicate(wall(j)));
+            } else if is_collinear(a, b) && shared_length(a, b) > TOLERANCE {
+                problem(Severity::Error, wall(i), ProblemKind::Overlaps(wall(j)));
+            }
+        }
+    }
+
+    for &(i, a) in &walls {
+        for at in [a.start, a.end] {
+            let others = walls.iter().filter(|&&(j, _)| j != i);
+            if let Some(&(j, _)) = others
+                .clone()
+                .find(|&&(_, b)| !is_collinear(a, b) && is_partway_along(b, at))
+            {
+                let kind = ProblemKind::TJunction { at, other: wall(j) };
+                problem(Severity::Warning, wall(i), kind);
+            } else if !others.clone().any(|&(_, b)| {
+                same_point(b.start, at) || same_point(b.end, at) || is_partway_along(b, at)
+            }) {
+                problem(Severity::Warning, wall(i), ProblemKind::LooseEnd(at));
+            }
+        }
+    }
+
+    for (index, sector) in map.sectors.iter().enumerate() {
+        let outline = sector.walls.iter().map(|&i| &map.walls[i].line);
+        if let Some(&at) = odd_ends(outline).first() {
+            let line = lines.and_then(|lines| lines.sectors.get(index).copied());
+            problem(
+                Severity::Error,
+                Place::Sector { index, line },
+                ProblemKind::Unclosed(at),
+            );
+        }
+    }
+
+    let named = map.named_spawns.iter().enumerate();
+    let spawns = std::iter::once((None, &map.spawn))
+        .chain(named.map(|(index, (_, spawn))| (Some(index), spawn)));
+    for (index, spawn) in spawns {
+        if can_see_out(map, spawn.position) {
+            let line = lines.and_then(|lines| match index {
+                Some(index) => lines.named_spawns.get(index).copied(),
+                None => Some(lines.spawn),
+            });
+            problem(
+                Severity::Error,
+                Place::Spawn { index, line },
+                ProblemKind::SpawnOutside,
+            );
+        }
+    }
+
+    // stable, so problems on the same line stay in the order they were found
+    problems.sort_by_key(|problem| problem.place.line().unwrap_or(usize::MAX));
+    problems
+}
+
+/// Whether there's any direction to look from a point without a wall in the way. Inside a
+/// closed room every direction hits something.
+fn can_see_out(map: &Map, point: Vec2) -> bool {
+    (0..SPAWN_RAYS).any(|i| {
+        // half a step round, so they don't all go straight through the corners of square rooms
+        let angle = (i as f32 + 0.5) / SPAWN_RAYS as f32 * std::f32::consts::TAU;
+        let direction = Vec2::new(angle.sin(), angle.cos());
+        !map.walls
+            .iter()
+            .any(|wall| ray_hits(point, direction, &wall.line))
+    })
+}
+
+/// Whether a ray from `origin` hits a wall, ends included. `do_lines_intersect` is exact, which
+/// lets rays slip past the ends of walls through rounding.
+fn ray_hits(origin: Vec2, direction: Vec2, line: &LineSegment) -> bool {
+    let along_wall = line.end - line.start;
+    let denom = cross(direction, along_wall);
+    if denom.abs() < f32::EPSILON {
+        return false;
+    }
+    let to_wall = line.start - origin;
+    let distance = cross(to_wall, along_wall) / denom;
+    let t = cross(to_wall, direction) / denom;
+    distance >= 0.0 && (-TOLERANCE..=1.0 + TOLERANCE).contains(&t)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::compiled::load_any_map_from_path;
+    use crate::game::fs::load_map;
+
+    const ROOM: &str = "5 5\n0 0 10 0\n10 0 10 10\n10 10 0 10\n0 10 0 0\n";
+
+    fn kinds(source: &str) -> Vec<(Option<usize>, ProblemKind)> {
+        check_map(&load_map(source).unwrap())
+            .into_iter()
+            .map(|problem| (problem.place.line(), problem.kind))
+            .collect()
+    }
+
+    #[test]
+    fn test_shipped_maps() {
+        let root = concat!(env!("CARGO_MANIFEST_DIR"), "/..");
+        let expected: [(&str, &[&str]); 6] = [
+            ("map01", &[]),
+            ("map02", &[]),
+            // map01 looked at from outside
+            (
+                "map04",
+                &["line 1: error: spawn point is outside the level"],
+            ),
+            // the inner wall stands on its own, touching the outside one
+            (
+                "maze",
+                &[
+                    "line 7: warning: wall end at (15, 10) doesn't meet any other wall",
+                    "line 11: warning: wall ends at (10, 0), partway along line 2",
+                ],
+            ),
+            ("steps", &[]),
+            ("test_map", &[]),
+        ];
+        for (name, expected) in expected {
+            let map = load_any_map_from_path(format!("{root}/{name}.txt"), None)
+                .unwrap()
+                .map;
+            let problems: Vec<String> = check_map(&map).iter().map(|p| p.to_string()).collect();
+            assert_eq!(problems, expected, "{name}");
+        }
+    }
+
+    #[test]
+    fn test_finds_problems_by_line() {
+        assert_eq!(kinds(ROOM), []);
+        assert_eq!(
+            kinds(&format!("{ROOM}3 3 3 3\n10 0 0 0\n")),
+            [
+                (Some(6), ProblemKind::ZeroLength),
+                (
+                    Some(7),
+                    ProblemKind::Duplicate(Place::Wall {
+                        index: 0,
+                        line: Some(2)
+                    })
+                ),
+            ]
+        );
+        assert_eq!(
+            kinds(&format!("{ROOM}5 0 15 0\n15 0 10 0\n")),
+            [
+                (
+                    Some(3),
+                    ProblemKind::TJunction {
+                        at: Vec2::new(10.0, 0.0),
+                        other: Place::Wall {
+                            index: 4,
+                            line: Some(6)
+                        },
+                    },
+                ),
+                (
+                    Some(6),
+                    ProblemKind::Overlaps(Place::Wall {
+                        index: 0,
+                        line: Some(2)
+                    })
+                ),
+                (
+                    Some(7),
+                    ProblemKind::Overlaps(Place::Wall {
+                        index: 4,
+                        line: Some(6)
+                    })
+                ),
+            ]
+        );
+        assert_eq!(
+            kinds(&format!("{ROOM}5 0 5 4\n")),
+            [
+                (
+                    Some(6),
+                    ProblemKind::TJunction {
+                        at: Vec2::new(5.0, 0.0),
+                        other: Place::Wall {
+                            index: 0,
+                            line: Some(2)
+                        },
+                    },
+                ),
+                (Some(6), ProblemKind::LooseEnd(Vec2::new(5.0, 4.0))),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_finds_unclosed_rooms_and_lost_spawns() {
+        assert_eq!(
+            kinds("5 5\n0 0 10 0\n10 0 10 10\n10 10 0 10\n"),
+            [
+                (Some(1), ProblemKind::SpawnOutside),
+                (Some(2), ProblemKind::LooseEnd(Vec2::new(0.0, 0.0))),
+                (Some(4), ProblemKind::LooseEnd(Vec2::new(0.0, 10.0))),
+            ]
+        );
+        assert_eq!(
+            kinds(&format!(
+                "{}spawn inside 1 1\nspawn lost 20 5\n",
+                ROOM.replacen("5 5", "20 5", 1)
+            )),
+            [
+                (Some(1), ProblemKind::SpawnOutside),
+                (Some(7), ProblemKind::SpawnOutside),
+            ]
+        );
+        assert_eq!(
+            kinds(&format!("{ROOM}sector 0 4\n2 2 4 2\n4 2 4 4\nend\n")),
+            [
+                (Some(6), ProblemKind::Unclosed(Vec2::new(2.0, 2.0))),
+                (Some(7), ProblemKind::LooseEnd(Vec2::new(2.0, 2.0))),
+                (Some(8), ProblemKind::LooseEnd(Vec2::new(4.0, 4.0))),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_falls_back_to_indices() {
+        let mut map = load_map(&format!("{ROOM}3 3 3 3\n")).unwrap();
+        map.metadata.lines = None;
+        let problems = check_map(&map);
+        assert_eq!(problems.len(), 1);
+        assert_eq!(problems[0].to_string(), "wall 4: error: wall has no length");
+    }
+}
@@ -1,29 +1,253 @@
-use crate::game::vecs::Vec3;
+//! keeping the player out of walls
+//!
+//! The player is a circle. Moving pushes it back out of any wall it ends up overlapping, which
+//! leaves whatever part of the move ran along the wall, so walking into a wall at an angle
+//! slides along it instead of stopping dead.
 
 use super::GameState;
-use super::lines::{LineSegment, do_lines_intersect};
+use super::lines::{LineSegment, crossing_point, do_lines_intersect};
+use super::vecs::Vec2;
 
-pub fn attempt_move(game_state: &mut GameState, delta: f32, move_vec: &Vec3) {
-    let cam_pos = game_state.cam.pos;
-    let new_pos = game_state.cam.pos + *move_vec * 8.0 * delta;
+/// How far the player keeps from walls, unless [`GameState::set_player_radius`] says otherwise.
+pub const DEFAULT_PLAYER_RADIUS: f32 = 0.25;
+/// the range [`GameState::set_player_radius`] keeps the radius in. A radius of 0 would leave
+/// nothing to push out of walls, and an infinite one would push out to infinity
+pub const PLAYER_RADIUS_RANGE: (f32, f32) = (0.01, 4.0);
 
-    let extended_ray = game_state.cam.pos + (*move_vec * 50.0 * delta);
+/// how fast the player walks, in map units a second
+const FORWARD_SPEED: f32 = 8.0;
+const BACK_SPEED: f32 = 3.0;
+const STRAFE_SPEED: f32 = 6.0;
 
-    let segment = LineSegment {
-        start: cam_pos.into(),
-        end: extended_ray.into(),
+// corners can take a couple of pushes to get clear of both walls
+const RESOLVE_PASSES: usize = 4;
+// so a long frame can't turn into a long loop. Past this the steps just get longer, and a step
+// still can't end up on the far side of a wall it would've walked through
+const MAX_STEPS: usize = 32;
+
+/// Moves the camera by `movement` (in map units), sliding along anything in the way.
+pub fn attempt_move(game_state: &mut GameState, movement: Vec2) {
+    let radius = game_state.player_radius;
+    // short enough steps that nothing thinner than the player can be jumped straight over
+    let max_step = radius * 0.5;
+    let steps = ((movement.length() / max_step).ceil() as usize).clamp(1, MAX_STEPS);
+    let step = movement / steps as f32;
+
+    let mut pos: Vec2 = game_state.cam.pos.into();
+    let mut sector = game_state.map.sector_at(pos);
+    for _ in 0..steps {
+        let (next, changed_sector) = step_towards(game_state, sector, pos, pos + step, radius);
+        if changed_sector {
+            sector = game_state.map.sector_at(next);
+        }
+        pos = next;
+    }
+    game_state.cam.pos.x = pos.x;
+    game_state.cam.pos.z = pos.y;
+}
+
+/// Walks the player for `delta` seconds. `movement` is relative to the way they're facing (+y
+/// forward, +x to their right) and full speed is a length of 1, so it should already have been
+/// through [`Input::movement`](super::Input::movement) to keep diagonals from going any faster.
+pub fn walk(game_state: &mut GameState, movement: Vec2, delta: f32) {
+    // walking stays level, however far up or down the camera's looking
+    let yaw = game_state.cam.yaw;
+    let forward = Vec2::new(yaw.sin(), yaw.cos());
+    let right = Vec2::new(yaw.cos(), -yaw.sin());
+
+    let forward_speed = if movement.y > 0.0 {
+        FORWARD_SPEED
+    } else {
+        BACK_SPEED
     };
+    let velocity = forward * movement.y * forward_speed + right * movement.x * STRAFE_SPEED;
+    if velocity != Vec2::default() {
+        attempt_move(game_state, velocity * delta);
+    }
+}
+
+/// the walls someone standing in `sector` can't walk across, with steps low enough to walk up
+/// not counting
+fn blocking(game_state: &GameState, sector: Option<usize>) -> impl Iterator<Item = &LineSegment> {
+    game_state
+        .map
+        .walls
+        .iter()
+        .zip(&game_state.sides)
+        .filter(move |(_, sides)| !sides.passable_from(&game_state.map, sector))
+        .map(|(wall, _)| &wall.line)
+}
 
-    // for each wall segment, check if it intersects
-    let intersects = game_state
-        .bsp
-        .order(cam_pos.into())
-        .into_iter()
-        .find(|wall_segment| do_lines_intersect(&segment, wall_segment));
+/// Where the player ends up trying to go from `from` to `to`, and whether they walked across a
+/// wall on the way. That's the only way into another sector, so the only time it needs looking
+/// up again.
+fn step_towards(
+    game_state: &GameState,
+    sector: Option<usize>,
+    from: Vec2,
+    to: Vec2,
+    radius: f32,
+) -> (Vec2, bool) {
+    // a step can be longer than the player is wide once MAX_STEPS kicks in, so it stops at the
+    // first wall in the way for the pushing out to start from
+    let mut pos = to;
+    for line in blocking(game_state, sector) {
+        if let Some(hit) = crossing_point(&LineSegment::new(from, pos), line) {
+            pos = hit;
+        }
+    }
+    for _ in 0..RESOLVE_PASSES {
+        let mut pushed = false;
+        for line in blocking(game_state, sector) {
+            let closest = line.closest_point(pos);
+            let away = pos - closest;
+            let distance = away.length();
+            if distance >= radius {
+                continue;
+            }
+            let normal = if distance > 0.0 {
+                away / distance
+            } else {
+                // right on the wall, so back out the way we came
+                let along = (line.end - line.start).normalize();
+                let normal = Vec2::new(-along.y, along.x);
+                if (from - line.start).dot(&normal) < 0.0 {
+                    normal * -1.0
+                } else {
+                    normal
+                }
+            };
+            pos = closest + normal * radius;
+            pushed = true;
+        }
+        if !pushed {
+            break;
+        }
+    }
+
+    // pushing out of one wall can shove the player through another in a tight enough corner
+    let path = LineSegment::new(from, pos);
+    let mut changed_sector = false;
+    for (wall, sides) in game_state.map.walls.iter().zip(&game_state.sides) {
+        if !do_lines_intersect(&path, &wall.line) {
+            continue;
+        }
+        if !sides.passable_from(&game_state.map, sector) {
+            return (from, false);
+        }
+        changed_sector = true;
+    }
+    (pos, changed_sector)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::fs::load_map;
+
+    fn room() -> GameState {
+        GameState::from_map(load_map("5 5\n0 0 10 0\n10 0 10 10\n10 10 0 10\n0 10 0 0\n").unwrap())
+    }
 
-    if intersects.is_some() {
-        return;
+    fn position(game_state: &GameState) -> Vec2 {
+        game_state.cam.pos.into()
     }
 
-    game_state.cam.pos = new_pos;
+    #[test]
+    fn test_stops_a_radius_from_walls() {
+        let mut game_state = room();
+        attempt_move(&mut game_state, Vec2::new(20.0, 0.0));
+        assert!((position(&game_state).x - (10.0 - DEFAULT_PLAYER_RADIUS)).abs() < 1e-4);
+
+        // backwards is no different
+        attempt_move(&mut game_state, Vec2::new(-30.0, 0.0));
+        assert!((position(&game_state).x - DEFAULT_PLAYER_RADIUS).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_radius_stays_usable() {
+        let mut game_state = room();
+        game_state.set_player_radius(f32::NAN);
+        assert_eq!(game_state.player_radius, DEFAULT_PLAYER_RADIUS);
+        game_state.set_player_radius(-1.0);
+        assert_eq!(game_state.player_radius, PLAYER_RADIUS_RANGE.0);
+        game_state.set_player_radius(f32::INFINITY);
+        assert_eq!(game_state.player_radius, PLAYER_RADIUS_RANGE.1);
+
+        game_state.set_player_radius(1.0);
+        attempt_move(&mut game_state, Vec2::new(20.0, 0.0));
+        assert!((position(&game_state).x - 9.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_slides_along_walls() {
+        let mut game_state = room();
+        game_state.place_camera(Vec2::new(9.7, 5.0), 0.0);
+        attempt_move(&mut game_state, Vec2::new(1.0, 1.0));
+        let pos = position(&game_state);
+        assert!((pos.x - (10.0 - DEFAULT_PLAYER_RADIUS)).abs() < 1e-4);
+        assert!((pos.y - 6.0).abs() < 1e-4);
+
+        // and into a corner, without getting through either wall
+        attempt_move(&mut game_state, Vec2::new(5.0, 5.0));
+        let pos = position(&game_state);
+        assert!(pos.x < 10.0 && pos.y < 10.0);
+    }
+
+    // a room with a low step up from x = 3, then one too high to climb from x = 6
+    const STEPS: &str = "
+1 5
+sector -0.75 1
+0 0 10 0
+10 0 10 10
+10 10 0 10
+0 10 0 0
+sector -0.5 1
+3 0 10 0
+10 0 10 10
+10 10 3 10
+3 10 3 0
+sector 0.5 1
+6 0 10 0
+10 0 10 10
+10 10 6 10
+6 10 6 0
+";
+
+    #[test]
+    fn test_walks_relative_to_facing() {
+        let mut game_state = room();
+        // facing +x, so their right is -y
+        game_state.place_camera(Vec2::new(5.0, 5.0), std::f32::consts::FRAC_PI_2);
+        walk(&mut game_state, Vec2::new(1.0, 0.0), 0.5);
+        let pos = position(&game_state);
+        assert!((pos.x - 5.0).abs() < 1e-4);
+        assert!((pos.y - (5.0 - STRAFE_SPEED * 0.5)).abs() < 1e-4);
+
+        // strafing into a wall slides along it like anything else
+        game_state.place_camera(Vec2::new(5.0, 5.0), 0.0);
+        walk(&mut game_state, Vec2::new(1.0, 0.2).normalize(), 1.0);
+        let pos = position(&game_state);
+        assert!((pos.x - (10.0 - DEFAULT_PLAYER_RADIUS)).abs() < 1e-4);
+        assert!(pos.y > 5.0 && pos.y < 10.0);
+    }
+
+    #[test]
+    fn test_walks_up_low_steps_only() {
+        let mut game_state = GameState::from_map(load_map(STEPS).unwrap());
+        attempt_move(&mut game_state, Vec2::new(8.0, 0.0));
+        assert!((position(&game_state).x - (6.0 - DEFAULT_PLAYER_RADIUS)).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_long_moves_still_stop_at_walls() {
+        // far more steps than MAX_STEPS would take at their usual length
+        let mut game_state = room();
+        attempt_move(&mut game_state, Vec2::new(1000.0, 0.0));
+        assert!((position(&game_state).x - (10.0 - DEFAULT_PLAYER_RADIUS)).abs() < 1e-4);
+
+        let mut game_state = GameState::from_map(load_map(STEPS).unwrap());
+        attempt_move(&mut game_state, Vec2::new(1000.0, 0.0));
+        assert!((position(&game_state).x - (6.0 - DEFAULT_PLAYER_RADIUS)).abs() < 1e-4);
+    }
 }
@@ -0,0 +1,70 @@
+//! picking wall colours, for walls the map didn't give one
+
+use super::map::Map;
+
+/// What walls without a colour get, going round by wall index.
+pub const DEFAULT_PALETTE: [(u8, u8, u8); 8] = [
+    (200, 60, 60),
+    (60, 160, 80),
+    (70, 100, 200),
+    (210, 180, 60),
+    (160, 80, 180),
+    (60, 170, 170),
+    (210, 120, 50),
+    (150, 150, 150),
+];
+
+/// parses a `#rrggbb` colour
+pub(super) fn parse_hex(value: &str) -> Option<(u8, u8, u8)> {
+    let hex = value
+        .strip_prefix('#')
+        .filter(|hex| hex.len() == 6 && hex.is_ascii())?;
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+    Some((channel(0)?, channel(2)?, channel(4)?))
+}
+
+/// the other way round from [`parse_hex`]
+pub(super) fn to_hex((r, g, b): (u8, u8, u8)) -> String {
+    format!("#{r:02x}{g:02x}{b:02x}")
+}
+
+/// Scales a colour's brightness, 1.0 leaving it alone.
+pub fn shade(color: (u8, u8, u8), factor: f32) -> (u8, u8, u8) {
+    let scale = |c: u8| (c as f32 * factor).round().clamp(0.0, 255.0) as u8;
+    (scale(color.0), scale(color.1), scale(color.2))
+}
+
+/// The colour a wall gets drawn with (before any lighting).
+///
+/// Walls with a colour in the map get that. Anything else gets a palette colour picked by its
+/// index, so the same map always looks the same. Either way walls running north-south are drawn
+/// a bit darker than ones running east-west, so corners stand out even between walls of the same
+/// colour.
+pub fn wall_color(map: &Map, wall: usize) -> (u8, u8, u8) {
+    let base = map.walls[wall]
+        .color
+        .unwrap_or(DEFAULT_PALETTE[wall % DEFAULT_PALETTE.len()]);
+
+    let line = map.walls[wall].line;
+    let along = (line.end - line.start).normalize();
+    shade(base, 0.8 + 0.2 * along.x.abs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::fs::load_map;
+
+    #[test]
+    fn test_wall_colors_are_stable() {
+        let map = load_map("0 0\n0 0 1 0\n1 0 1 1 color=#102030\n1 1 0 1\n").unwrap();
+        // east-west walls keep their colour, north-south ones are darker
+        assert_eq!(wall_color(&map, 0), DEFAULT_PALETTE[0]);
+        assert_eq!(wall_color(&map, 1), (13, 26, 38));
+        assert_eq!(wall_color(&map, 2), DEFAULT_PALETTE[2]);
+        assert_eq!(
+            wall_color(&map, 2),
+            wall_color(&load_map("0 0\n0 0 1 0\n1 0 1 1\n1 1 0 1\n").unwrap(), 2)
+        );
+    }
+}
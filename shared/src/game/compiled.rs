@@ -0,0 +1,681 @@
+//! maps with their BSP tree already built, saved in a binary format so they load without
+//! building it again
+//!
+//! A compiled file is the magic bytes `MDMC`, a little-endian `u16` format version, the map
+//! itself and then the tree. Everything after that is little-endian too:
+//!
+//! - numbers are `f32`s, counts and indices `u32`s, and colours three bytes
+//! - strings are a `u32` byte length followed by UTF-8
+//! - anything optional is a byte that's 1 if it's there, followed by it
+//! - lists are a `u32` length followed by their items
+//!
+//! The same map built with the same [`PartitionChooser`] always comes out byte for byte the same.
+//! Bump [`FORMAT_VERSION`] whenever any of this changes, since older files won't read right.
+
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+use super::bsp::{BSPTree, BuildStats, PartitionChooser, Seg};
+use super::fs::{MapError, builtin_map, parse_map_file};
+use super::lighting::Lighting;
+use super::lines::LineSegment;
+use super::map::{Entity, Map, MapMetadata, Sector, Spawn, TextureDef, Wall};
+use super::vecs::Vec2;
+use super::wad;
+
+pub const MAGIC: [u8; 4] = *b"MDMC";
+/// the version written by [`CompiledMap::write`], and the only one it can read
+pub const FORMAT_VERSION: u16 = 1;
+
+/// A map along with the BSP tree built from it.
+pub struct CompiledMap {
+    pub map: Map,
+    pub bsp: BSPTree,
+    /// how the tree came out when it was first built
+    pub stats: BuildStats,
+}
+
+/// Why a compiled map couldn't be read.
+#[derive(Debug)]
+pub enum CompiledMapError {
+    Io(io::Error),
+    /// the file ended partway through
+    Truncated,
+    /// it doesn't start with [`MAGIC`], so isn't a compiled map at all
+    NotCompiled,
+    /// written by a different version of the format
+    UnsupportedVersion(u16),
+    /// the file reads fine but what's in it doesn't hang together, like an index pointing
+    /// past the end of a list
+    Invalid(&'static str),
+}
+
+impl fmt::Display for CompiledMapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CompiledMapError::Io(e) => write!(f, "{e}"),
+            CompiledMapError::Truncated => write!(f, "compiled map ends too early"),
+            CompiledMapError::NotCompiled => write!(f, "not a compiled map"),
+            CompiledMapError::UnsupportedVersion(version) => write!(
+                f,
+                "compiled map is format version {version}, expected {FORMAT_VERSION}"
+            ),
+            CompiledMapError::Invalid(what) => write!(f, "compiled map is corrupt: {what}"),
+        }
+    }
+}
+
+impl std::error::Error for CompiledMapError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CompiledMapError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for CompiledMapError {
+    fn from(e: io::Error) -> Self {
+        if e.kind() == io::ErrorKind::UnexpectedEof {
+            CompiledMapError::Truncated
+        } else {
+            CompiledMapError::Io(e)
+        }
+    }
+}
+
+impl CompiledMap {
+    /// Builds the map's BSP tree, with `chooser` picking where to split it.
+    pub fn compile(map: Map, chooser: &PartitionChooser) -> Self {
+        let segs = map
+            .walls
+            .iter()
+            .enumerate()
+            .map(|(i, wall)| Seg {
+                line: wall.line,
+                wall: i,
+            })
+            .collect();
+        let (bsp, stats) = BSPTree::new(segs, &|p| map.sector_at(p), chooser);
+        CompiledMap { map, bsp, stats }
+    }
+
+    pub fn write(&self, out: impl Write) -> io::Result<()> {
+        let mut encoder = Encoder { out };
+        encoder.bytes(&MAGIC)?;
+        encoder.u16(FORMAT_VERSION)?;
+        encoder.map(&self.map)?;
+        encoder.stats(&self.stats)?;
+        self.bsp.encode(&mut encoder)
+    }
+
+    /// Reads a map written by [`write`](Self::write). The map's `source` is left empty.
+    pub fn read(mut input: impl Read) -> Result<Self, CompiledMapError> {
+        // all of it up front, so no count in the file can ask for more than the file could hold
+        let mut bytes = Vec::new();
+        input.read_to_end(&mut bytes)?;
+        let mut decoder = Decoder { input: &bytes };
+        if decoder.array()? != MAGIC {
+            return Err(CompiledMapError::NotCompiled);
+        }
+        let version = decoder.u16()?;
+        if version != FORMAT_VERSION {
+            return Err(CompiledMapError::UnsupportedVersion(version));
+        }
+        let map = decoder.map()?;
+        map.validate().map_err(CompiledMapError::Invalid)?;
+        let stats = decoder.stats()?;
+        let bsp = BSPTree::decode(&mut decoder, &map)?;
+        Ok(CompiledMap { map, bsp, stats })
+    }
+
+    /// Writes the map to a file. Texture paths are kept relative, so they'll only be found if
+    /// the file ends up in the same folder as the map it was compiled from.
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut out = io::BufWriter::new(std::fs::File::create(path)?);
+        self.write(&mut out)?;
+        out.flush()
+    }
+}
+
+/// A map loaded by [`load_any_map_from_path`], whichever kind of file it came out of.
+pub struct LoadedMap {
+    pub map: Map,
+    /// the BSP tree it was compiled with, if it was a compiled map
+    pub tree: Option<(BSPTree, BuildStats)>,
+    /// what didn't make it across, if it came out of a WAD
+    pub warnings: Vec<wad::WadWarning>,
+}
+
+impl LoadedMap {
+    /// The map along with its BSP tree, which gets built with `chooser` if it wasn't compiled.
+    pub fn into_level(self, chooser: &PartitionChooser) -> CompiledMap {
+        match self.tree {
+            Some((bsp, stats)) => CompiledMap {
+                map: self.map,
+                bsp,
+                stats,
+            },
+            None => CompiledMap::compile(self.map, chooser),
+        }
+    }
+}
+
+/// Loads a map out of any kind of file, told apart by its first few bytes: a Doom WAD (the map
+/// called `wad_map`, or its first one), a compiled map, or a text, TOML or JSON map (by its
+/// extension). `wad_map` is ignored for anything that isn't a WAD.
+pub fn load_any_map_from_path(
+    path: impl AsRef<Path>,
+    wad_map: Option<&str>,
+) -> Result<LoadedMap, MapError> {
+    let path = path.as_ref();
+    let io_error = |source| MapError::Io {
+        path: path.to_path_buf(),
+        source,
+    };
+    let contents = std::fs::read(path).map_err(io_error)?;
+
+    let mut loaded = if wad::is_wad(&contents) {
+        let imported = wad::load_wad_map(&contents, wad_map).map_err(|error| MapError::Wad {
+            path: path.to_path_buf(),
+            error,
+        })?;
+        LoadedMap {
+            map: imported.map,
+            tree: None,
+            warnings: imported.warnings,
+        }
+    } else if contents.starts_with(&MAGIC) {
+        let level = CompiledMap::read(contents.as_slice()).map_err(|error| MapError::Compiled {
+            path: path.to_path_buf(),
+            error,
+        })?;
+        LoadedMap {
+            map: level.map,
+            tree: Some((level.bsp, level.stats)),
+            warnings: Vec::new(),
+        }
+    } else {
+        let text = String::from_utf8(contents)
+            .map_err(|e| io_error(io::Error::new(io::ErrorKind::InvalidData, e)))?;
+        LoadedMap {
+            map: parse_map_file(path, &text)?,
+            tree: None,
+            warnings: Vec::new(),
+        }
+    };
+    loaded.map.metadata.source = Some(path.to_path_buf());
+    Ok(loaded)
+}
+
+/// The map named by the first command line argument, or the built-in one if there isn't one.
+/// WADs load the map named by the second argument (the first in the WAD if there isn't one), and
+/// it's up to the caller to tell anyone about their warnings. This is the usual choice for the
+/// frontends.
+pub fn level_from_args() -> Result<LoadedMap, MapError> {
+    let mut args = std::env::args().skip(1);
+    match args.next() {
+        Some(path) => load_any_map_from_path(path, args.next().as_deref()),
+        None => Ok(LoadedMap {
+            map: builtin_map(),
+            tree: None,
+            warnings: Vec::new(),
+        }),
+    }
+}
+
+pub(super) struct Encoder<W> {
+    out: W,
+}
+
+impl<W: Write> Encoder<W> {
+    fn bytes(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.out.write_all(bytes)
+    }
+
+    pub(super) fn u8(&mut self, value: u8) -> io::Result<()> {
+        self.bytes(&[value])
+    }
+
+    fn u16(&mut self, value: u16) -> io::Result<()> {
+        self.bytes(&value.to_le_bytes())
+    }
+
+    pub(super) fn u32(&mut self, value: usize) -> io::Result<()> {
+        let value = u32::try_from(value)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "too big for a u32"))?;
+        self.bytes(&value.to_le_bytes())
+    }
+
+    pub(super) fn f32(&mut self, value: f32) -> io::Result<()> {
+        self.bytes(&value.to_le_bytes())
+    }
+
+    pub(super) fn option<T>(
+        &mut self,
+        value: Option<T>,
+        mut write: impl FnMut(&mut Self, T) -> io::Result<()>,
+    ) -> io::Result<()> {
+        match value {
+            Some(value) => {
+                self.u8(1)?;
+                write(self, value)
+            }
+            None => self.u8(0),
+        }
+    }
+
+    fn string(&mut self, value: &str) -> io::Result<()> {
+        self.u32(value.len())?;
+        self.bytes(value.as_bytes())
+    }
+
+    fn color(&mut self, (r, g, b): (u8, u8, u8)) -> io::Result<()> {
+        self.bytes(&[r, g, b])
+    }
+
+    pub(super) fn point(&mut self, point: Vec2) -> io::Result<()> {
+        self.f32(point.x)?;
+        self.f32(point.y)
+    }
+
+    pub(super) fn line(&mut self, line: LineSegment) -> io::Result<()> {
+        self.point(line.start)?;
+        self.point(line.end)
+    }
+
+    fn spawn(&mut self, spawn: &Spawn) -> io::Result<()> {
+        self.point(spawn.position)?;
+        self.f32(spawn.yaw)?;
+        self.option(spawn.height, Self::f32)
+    }
+
+    fn map(&mut self, map: &Map) -> io::Result<()> {
+        self.spawn(&map.spawn)?;
+        self.u32(map.named_spawns.len())?;
+        for (name, spawn) in &map.named_spawns {
+            self.string(name)?;
+            self.spawn(spawn)?;
+        }
+        self.option(map.metadata.name.as_deref(), Self::string)?;
+        self.option(map.metadata.author.as_deref(), Self::string)?;
+
+        let lighting = map.lighting;
+        self.f32(lighting.falloff)?;
+        self.f32(lighting.min_light)?;
+        self.option(lighting.fog_distance, Self::f32)?;
+        self.color(lighting.fog_color)?;
+
+        self.u32(map.palette.len())?;
+        for (name, color) in &map.palette {
+            self.string(name)?;
+            self.color(*color)?;
+        }
+
+        self.u32(map.textures.len())?;
+        for texture in &map.textures {
+            self.string(&texture.name)?;
+            let path = texture.path.to_str().ok_or_else(|| {
+                io::Error::new(io::ErrorKind::InvalidInput, "texture path isn't UTF-8")
+            })?;
+            self.string(path)?;
+        }
+
+        self.u32(map.walls.len())?;
+        for wall in &map.walls {
+            self.line(wall.line)?;
+            self.option(wall.texture, Self::u32)?;
+            self.f32(wall.u_offset)?;
+            self.f32(wall.v_offset)?;
+            self.option(wall.color, Self::color)?;
+        }
+
+        self.u32(map.sectors.len())?;
+        for sector in &map.sectors {
+            self.f32(sector.floor)?;
+            self.f32(sector.ceiling)?;
+            self.color(sector.floor_color)?;
+            self.option(sector.ceiling_color, Self::color)?;
+            self.f32(sector.light)?;
+            self.u32(sector.walls.len())?;
+            for &wall in &sector.walls {
+                self.u32(wall)?;
+            }
+        }
+
+        self.u32(map.entities.len())?;
+        for entity in &map.entities {
+            self.string(&entity.kind)?;
+            self.point(entity.position)?;
+            self.f32(entity.yaw)?;
+        }
+        Ok(())
+    }
+
+    fn stats(&mut self, stats: &BuildStats) -> io::Result<()> {
+        self.u32(stats.nodes)?;
+        self.u32(stats.leaves)?;
+        self.u32(stats.depth)?;
+        self.u32(stats.splits)
+    }
+}
+
+pub(super) struct Decoder<'a> {
+    /// what's left of the file
+    input: &'a [u8],
+}
+
+impl Decoder<'_> {
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], CompiledMapError> {
+        let mut bytes = [0; N];
+        self.input.read_exact(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    pub(super) fn u8(&mut self) -> Result<u8, CompiledMapError> {
+        Ok(self.array::<1>()?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, CompiledMapError> {
+        Ok(u16::from_le_bytes(self.array()?))
+    }
+
+    pub(super) fn u32(&mut self) -> Result<usize, CompiledMapError> {
+        Ok(u32::from_le_bytes(self.array()?) as usize)
+    }
+
+    /// The length of a list whose items each take at least `size` bytes. Lengths the rest of the
+    /// file couldn't possibly hold are refused here, before anything gets allocated for them.
+    pub(super) fn count(&mut self, size: usize) -> Result<usize, CompiledMapError> {
+        let count = self.u32()?;
+        if count > self.input.len() / size {
+            return Err(CompiledMapError::Truncated);
+        }
+        Ok(count)
+    }
+
+    /// an index that has to be below `len`
+    pub(super) fn index(
+        &mut self,
+        len: usize,
+        what: &'static str,
+    ) -> Result<usize, CompiledMapError> {
+        let index = self.u32()?;
+        if index >= len {
+            return Err(CompiledMapError::Invalid(what));
+        }
+        Ok(index)
+    }
+
+    pub(super) fn f32(&mut self) -> Result<f32, CompiledMapError> {
+        Ok(f32::from_le_bytes(self.array()?))
+    }
+
+    pub(super) fn option<T>(
+        &mut self,
+        mut read: impl FnMut(&mut Self) -> Result<T, CompiledMapError>,
+    ) -> Result<Option<T>, CompiledMapError> {
+        match self.u8()? {
+            0 => Ok(None),
+            1 => read(self).map(Some),
+            _ => Err(CompiledMapError::Invalid("optional value isn't 0 or 1")),
+        }
+    }
+
+    fn string(&mut self) -> Result<String, CompiledMapError> {
+        let len = self.count(1)?;
+        let (bytes, rest) = self.input.split_at(len);
+        self.input = rest;
+        String::from_utf8(bytes.to_vec())
+            .map_err(|_| CompiledMapError::Invalid("string isn't UTF-8"))
+    }
+
+    fn color(&mut self) -> Result<(u8, u8, u8), CompiledMapError> {
+        let [r, g, b] = self.array()?;
+        Ok((r, g, b))
+    }
+
+    pub(super) fn point(&mut self) -> Result<Vec2, CompiledMapError> {
+        let point = Vec2::new(self.f32()?, self.f32()?);
+        if !point.x.is_finite() || !point.y.is_finite() {
+            return Err(CompiledMapError::Invalid("coordinate isn't finite"));
+        }
+        Ok(point)
+    }
+
+    pub(super) fn line(&mut self) -> Result<LineSegment, CompiledMapError> {
+        Ok(LineSegment::new(self.point()?, self.point()?))
+    }
+
+    fn spawn(&mut self) -> Result<Spawn, CompiledMapError> {
+        Ok(Spawn {
+            position: self.point()?,
+            yaw: self.f32()?,
+            height: self.option(Self::f32)?,
+        })
+    }
+
+    fn map(&mut self) -> Result<Map, CompiledMapError> {
+        let spawn = self.spawn()?;
+        let mut named_spawns = Vec::new();
+        for _ in 0..self.count(4 + 8 + 4 + 1)? {
+            named_spawns.push((self.string()?, self.spawn()?));
+        }
+        let name = self.option(Self::string)?;
+        let author = self.option(Self::string)?;
+
+        let lighting = Lighting {
+            falloff: self.f32()?,
+            min_light: self.f32()?,
+            fog_distance: self.option(Self::f32)?,
+            fog_color: self.color()?,
+        };
+
+        // the smallest each kind of item can be, to check lists' lengths against:
+        // strings are at least their length, and options at least their flag
+        let mut palette = Vec::new();
+        for _ in 0..self.count(4 + 3)? {
+            palette.push((self.string()?, self.color()?));
+        }
+
+        let mut textures = Vec::new();
+        for _ in 0..self.count(4 + 4)? {
+            textures.push(TextureDef {
+                name: self.string()?,
+                path: PathBuf::from(self.string()?),
+            });
+        }
+
+        let mut walls = Vec::new();
+        for _ in 0..self.count(16 + 1 + 4 + 4 + 1)? {
+            walls.push(Wall {
+                line: self.line()?,
+                texture: self.option(|d| d.index(textures.len(), "wall texture out of range"))?,
+                u_offset: self.f32()?,
+                v_offset: self.f32()?,
+                color: self.option(Self::color)?,
+            });
+        }
+        if walls.is_empty() {
+            return Err(CompiledMapError::Invalid("map has no walls"));
+        }
+
+        let mut sectors = Vec::new();
+        for _ in 0..self.count(4 + 4 + 3 + 1 + 4 + 4)? {
+            let mut sector = Sector {
+                floor: self.f32()?,
+                ceiling: self.f32()?,
+                floor_color: self.color()?,
+                ceiling_color: self.option(Self::color)?,
+                light: self.f32()?,
+                walls: Vec::new(),
+            };
+            for _ in 0..self.count(4)? {
+                sector
+                    .walls
+                    .push(self.index(walls.len(), "sector wall out of range")?);
+            }
+            sectors.push(sector);
+        }
+
+        let mut entities = Vec::new();
+        for _ in 0..self.u32()? {
+            entities.push(Entity {
+                kind: self.string()?,
+                position: self.point()?,
+                yaw: self.f32()?,
+            });
+        }
+
+        Ok(Map {
+            spawn,
+            named_spawns,
+            walls,
+            sectors,
+            textures,
+            palette,
+            lighting,
+            entities,
+            metadata: MapMetadata {
+                name,
+                author,
+                source: None,
+                lines: None,
+            },
+        })
+    }
+
+    fn stats(&mut self) -> Result<BuildStats, CompiledMapError> {
+        Ok(BuildStats {
+            nodes: self.u32()?,
+            leaves: self.u32()?,
+            depth: self.u32()?,
+            splits: self.u32()?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::fs::load_map;
+
+    fn compiled(source: &str) -> Vec<u8> {
+        let map = load_map(source).unwrap();
+        let mut bytes = Vec::new();
+        CompiledMap::compile(map, &PartitionChooser::default())
+            .write(&mut bytes)
+            .unwrap();
+        bytes
+    }
+
+    #[test]
+    fn test_round_trips_byte_for_byte() {
+        let steps =
+            std::fs::read_to_string(concat!(env!("CARGO_MANIFEST_DIR"), "/../steps.txt")).unwrap();
+        let source = format!("{steps}spawn top 1 2 yaw=45 height=0.5\n");
+        let bytes = compiled(&source);
+        assert_eq!(bytes, compiled(&source));
+
+        let level = CompiledMap::read(bytes.as_slice()).unwrap();
+        assert_eq!(level.map, load_map(&source).unwrap());
+        // worked out from the tree itself, not taken on trust from the stats
+        assert_eq!(level.bsp.depth(), level.stats.depth);
+        let mut again = Vec::new();
+        level.write(&mut again).unwrap();
+        assert_eq!(again, bytes);
+    }
+
+    #[test]
+    fn test_rejects_bad_files() {
+        let bytes = compiled("1 1\n0 0 4 0\n4 0 4 4\n4 4 0 4\n0 4 0 0\n");
+
+        assert!(matches!(
+            CompiledMap::read(&b"5 5\n0 0 1 1\n"[..]),
+            Err(CompiledMapError::NotCompiled)
+        ));
+        let mut newer = bytes.clone();
+        newer[4..6].copy_from_slice(&(FORMAT_VERSION + 1).to_le_bytes());
+        assert!(matches!(
+            CompiledMap::read(newer.as_slice()),
+            Err(CompiledMapError::UnsupportedVersion(v)) if v == FORMAT_VERSION + 1
+        ));
+        assert!(matches!(
+            CompiledMap::read(&bytes[..bytes.len() - 3]),
+            Err(CompiledMapError::Truncated)
+        ));
+
+        // after the header: the spawn, no named spawns, name or author, then lighting and the
+        // palette's length
+        let mut huge = bytes.clone();
+        huge[37..41].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(matches!(
+            CompiledMap::read(huge.as_slice()),
+            Err(CompiledMapError::Truncated)
+        ));
+        let mut nowhere = bytes.clone();
+        nowhere[6..10].copy_from_slice(&f32::NAN.to_le_bytes());
+        assert!(matches!(
+            CompiledMap::read(nowhere.as_slice()),
+            Err(CompiledMapError::Invalid(_))
+        ));
+        let mut too_bright = bytes.clone();
+        too_bright[29..33].copy_from_slice(&2.0f32.to_le_bytes());
+        assert!(matches!(
+            CompiledMap::read(too_bright.as_slice()),
+            Err(CompiledMapError::Invalid("lighting min_light isn't from 0 to 1"))
+        ));
+    }
+
+    #[test]
+    fn test_loads_every_kind_of_map_the_same_way() {
+        let source = "1 1\n0 0 4 0\n4 0 4 4\n4 4 0 4\n0 4 0 0\n";
+        let dir = std::env::temp_dir().join(format!("micro_doom_load_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let text = dir.join("square.txt");
+        let compiled_path = dir.join("square.mapc");
+        std::fs::write(&text, source).unwrap();
+        std::fs::write(&compiled_path, compiled(source)).unwrap();
+
+        let from_text = load_any_map_from_path(&text, Some("E1M1")).unwrap();
+        assert!(from_text.tree.is_none());
+        assert!(from_text.warnings.is_empty());
+        assert_eq!(
+            from_text.map.metadata.source.as_deref(),
+            Some(text.as_path())
+        );
+        let from_compiled = load_any_map_from_path(&compiled_path, None).unwrap();
+        assert!(from_compiled.tree.is_some());
+        assert_eq!(from_compiled.map.walls, from_text.map.walls);
+        assert_eq!(
+            from_compiled.map.metadata.source.as_deref(),
+            Some(compiled_path.as_path())
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_missing_file() {
+        assert!(matches!(
+            load_any_map_from_path("definitely/not/a/map.txt", None),
+            Err(MapError::Io { .. })
+        ));
+    }
+
+    #[test]
+    fn test_shipped_maps_load() {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("..");
+        for name in [
+            "map01.txt",
+            "map02.txt",
+            "map04.txt",
+            "maze.txt",
+            "test_map.txt",
+            "steps.txt",
+        ] {
+            let map = load_any_map_from_path(root.join(name), None).unwrap().map;
+            assert_eq!(format!("{}.txt", map.display_name()), name);
+        }
+    }
+}
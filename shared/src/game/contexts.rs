@@ -1,4 +1,4 @@
-/// Makes this crate a lot more portable by abstracting away different contexts.
+//! Makes this crate a lot more portable by abstracting away different contexts.
 
 use crate::game::drawing::Drawer;
 
@@ -7,12 +7,3 @@ pub struct GraphicsContext<'a, T: Drawer> {
     pub height: u32,
     pub drawer: &'a mut T,
 }
-
-pub struct KeysDown {
-    pub up: bool,
-    pub down: bool,
-    pub left: bool, 
-    pub right: bool,
-    pub w: bool,
-    pub s: bool,
-}
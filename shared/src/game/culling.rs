@@ -0,0 +1,135 @@
+//! working out which bits of the map can't possibly be on screen, from above
+
+use super::cam::Camera;
+use super::vecs::Vec2;
+
+// a little extra width on the view cone, so nothing right on the edge of the screen gets culled
+const CONE_SLACK: f32 = 0.02;
+
+/// A box on the map, lined up with the axes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BoundingBox {
+    pub min: Vec2,
+    pub max: Vec2,
+}
+
+impl BoundingBox {
+    /// the smallest box with every point in it
+    pub fn around(points: impl IntoIterator<Item = Vec2>) -> Self {
+        let mut min = Vec2::new(f32::INFINITY, f32::INFINITY);
+        let mut max = Vec2::new(f32::NEG_INFINITY, f32::NEG_INFINITY);
+        for p in points {
+            min = Vec2::new(min.x.min(p.x), min.y.min(p.y));
+            max = Vec2::new(max.x.max(p.x), max.y.max(p.y));
+        }
+        BoundingBox { min, max }
+    }
+
+    pub fn corners(&self) -> [Vec2; 4] {
+        [
+            self.min,
+            Vec2::new(self.max.x, self.min.y),
+            self.max,
+            Vec2::new(self.min.x, self.max.y),
+        ]
+    }
+}
+
+/// The wedge of the map in front of the camera that could be on screen, ignoring height.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ViewCone {
+    apex: Vec2,
+    /// normals of the two edges, pointing into the wedge
+    edges: [Vec2; 2],
+}
+
+impl ViewCone {
+    /// `None` if the camera can see all the way around, which happens when it's pitched far
+    /// enough that the bottom (or top) of the screen is looking back behind it.
+    pub fn new(cam: &Camera) -> Option<Self> {
+        let half_fov_y = cam.fov * 0.5;
+        let half_fov_x = (half_fov_y.tan() * cam.aspect).atan();
+
+        // pitching tilts the screen's corners further round to the sides
+        let tilt = (cam.pitch.abs() + half_fov_y).cos();
+        if tilt <= 0.0 {
+            return None;
+        }
+        let half_angle = (half_fov_x.tan() * half_fov_y.cos() / tilt).atan() + CONE_SLACK;
+        if half_angle >= std::f32::consts::FRAC_PI_2 {
+            return None;
+        }
+
+        let forward = Vec2::new(cam.yaw.sin(), cam.yaw.cos());
+        let edge = |angle: f32| {
+            let (sin, cos) = angle.sin_cos();
+            // forward turned by `angle`
+            Vec2::new(
+                forward.x * cos + forward.y * sin,
+                forward.y * cos - forward.x * sin,
+            )
+        };
+        let (left, right) = (edge(-half_angle), edge(half_angle));
+        Some(ViewCone {
+            apex: cam.pos.into(),
+            edges: [
+                // each edge's normal is the direction turned a quarter towards the other edge
+                Vec2::new(left.y, -left.x),
+                Vec2::new(-right.y, right.x),
+            ],
+        })
+    }
+
+    /// Whether any of the box could be inside the cone. Can say yes to boxes just outside it,
+    /// but never no to one that's inside.
+    pub fn sees(&self, bounds: &BoundingBox) -> bool {
+        let corners = bounds.corners();
+        self.edges.iter().all(|normal| {
+            corners
+                .iter()
+                .any(|corner| (*corner - self.apex).dot(normal) >= 0.0)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::vecs::Vec3;
+
+    fn camera(yaw: f32, pitch: f32) -> Camera {
+        Camera {
+            pos: Vec3::new(0.0, 0.0, 0.0),
+            fov: 80.0_f32.to_radians(),
+            yaw,
+            pitch,
+            near: 0.1,
+            aspect: 4.0 / 3.0,
+        }
+    }
+
+    fn square_at(x: f32, y: f32) -> BoundingBox {
+        BoundingBox::around([Vec2::new(x - 0.5, y - 0.5), Vec2::new(x + 0.5, y + 0.5)])
+    }
+
+    #[test]
+    fn test_cone_culls_behind_and_beside() {
+        // looking along +y
+        let cone = ViewCone::new(&camera(0.0, 0.0)).unwrap();
+        assert!(cone.sees(&square_at(0.0, 5.0)));
+        assert!(cone.sees(&square_at(3.0, 5.0)));
+        assert!(!cone.sees(&square_at(0.0, -5.0)));
+        assert!(!cone.sees(&square_at(20.0, 2.0)));
+        assert!(!cone.sees(&square_at(-20.0, 2.0)));
+        // the camera's own square is always visible
+        assert!(cone.sees(&square_at(0.0, 0.0)));
+
+        // a quarter turn looks along +x
+        let cone = ViewCone::new(&camera(std::f32::consts::FRAC_PI_2, 0.0)).unwrap();
+        assert!(cone.sees(&square_at(5.0, 0.0)));
+        assert!(!cone.sees(&square_at(0.0, 5.0)));
+
+        // looking straight down, everything around is fair game
+        assert!(ViewCone::new(&camera(0.0, -1.5)).is_none());
+    }
+}
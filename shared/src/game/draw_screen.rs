@@ -1,29 +1,46 @@
+use std::ops::ControlFlow;
+
 use crate::game::drawing::Drawer;
-use mint::Point2;
 use nalgebra_glm as glm;
-use once_cell::sync::Lazy;
 
 use super::GameState;
 use super::a3d_to_2d;
+use super::automap;
+use super::colors;
+use super::lighting::Light;
+use super::bsp::{Leaf, Seg, Visit};
 use super::lines::LineSegment3;
+use super::occlusion::OcclusionBuffer;
 use super::skybox;
+use super::textures::Texture;
 use super::vecs::{Vec2, Vec3};
 
-use rand::Rng;
-
-static RAND_32: Lazy<u32> = Lazy::new(|| rand::rng().random());
+/// What drawing anything in a frame needs, besides the game and the drawer.
+struct View {
+    proj: glm::Mat4,
+    /// what's been drawn so far, since everything goes nearest first
+    occlusion: OcclusionBuffer,
+    /// which walls got at least a pixel on screen, for the automap
+    drawn_walls: Vec<bool>,
+}
 
 pub fn draw_screen<T: Drawer>(
     game_state: &mut GameState,
     graphics_ctx: &mut super::GraphicsContext<T>,
 ) {
+    if game_state.automap.is_open() {
+        automap::draw_automap(game_state, graphics_ctx);
+        return;
+    }
+
     // the projection matrix
     // This is the calculation of the matrix that converts 3D points to 2D screen points.
     // We use a right-handed coordinate system with zero to one depth range.
     let width = graphics_ctx.width as f32;
     let height = graphics_ctx.height as f32;
+    game_state.cam.aspect = width / height;
     let proj = glm::perspective_rh_zo(
-        width / height,
+        game_state.cam.aspect,
         game_state.cam.fov,
         game_state.cam.near,
         1000.0,
@@ -32,19 +49,55 @@ pub fn draw_screen<T: Drawer>(
 
 
 
-    let cam_pos_2d = Vec2 {
-        x: game_state.cam.pos.x,
-        y: game_state.cam.pos.z,
+    let mut view = View {
+        proj,
+        occlusion: OcclusionBuffer::new(graphics_ctx.width, graphics_ctx.height),
+        drawn_walls: vec![false; game_state.map.walls.len()],
     };
-    skybox::draw_skybox(&game_state.cam, graphics_ctx, proj);
 
-    let out_vec = game_state.bsp.order(cam_pos_2d);
+    // nearest first, so everything only has to fill in whatever isn't covered yet, and once the
+    // screen's full there's no point looking at the rest
+    let _ = game_state.bsp.for_each_front_to_back(&game_state.cam, |visit| {
+        match visit {
+            Visit::Leaf(leaf) => draw_flats(game_state, graphics_ctx, &mut view, leaf),
+            Visit::Seg(seg) => draw_wall(game_state, graphics_ctx, &mut view, &seg),
+        }
+        if view.occlusion.is_full() {
+            ControlFlow::Break(())
+        } else {
+            ControlFlow::Continue(())
+        }
+    });
+
+    // the sky goes behind everything, so it's last
+    skybox::draw_skybox(&game_state.cam, graphics_ctx, view.proj, &mut view.occlusion);
+
+    for (wall, drawn) in view.drawn_walls.into_iter().enumerate() {
+        if drawn {
+            game_state.automap.see(wall);
+        }
+    }
+}
+
+fn draw_wall<T: Drawer>(
+    game_state: &GameState,
+    graphics_ctx: &mut super::GraphicsContext<T>,
+    view: &mut View,
+    seg: &Seg,
+) {
+    let wall_segment = &seg.line;
+    let color = colors::wall_color(&game_state.map, seg.wall);
+    let color = (color.0, color.1, color.2, 255); // shove in alpha
+    let light = Light {
+        level: game_state.map.sector(facing_sector(game_state, seg)).light,
+        lighting: game_state.lighting,
+    };
+    let texture = game_state.map.walls[seg.wall]
+        .texture
+        .and_then(|id| game_state.textures.get(id));
 
-    for wall_segment in out_vec.iter() {
-        let color = random_color((wall_segment.start, wall_segment.end));
-        let color = (color.0, color.1, color.2, 255); // shove in alpha
-        //let rotated_wall_seg = cam::rotate_seg(*wall_segment, &game_state.cam);
-        let wall_3d_segs = wall_floor_to_3d(&wall_segment.start, &wall_segment.end);
+    for (bottom, top) in game_state.sides[seg.wall].spans(&game_state.map) {
+        let wall_3d_segs = wall_floor_to_3d(&wall_segment.start, &wall_segment.end, bottom, top);
 
         let wall_point_set: Vec<Vec3> = vec![
             wall_3d_segs.0.start,
@@ -52,50 +105,153 @@ pub fn draw_screen<T: Drawer>(
             wall_3d_segs.1.end,
             wall_3d_segs.1.start,
         ];
-        
+        let drawn = match texture {
+            Some(texture) => draw_textured_wall(
+                game_state,
+                graphics_ctx,
+                view,
+                seg,
+                &wall_point_set,
+                texture,
+                light,
+            ),
+            None => draw_3d_polygon(game_state, graphics_ctx, view, &wall_point_set, color, light),
+        };
+        if drawn {
+            view.drawn_walls[seg.wall] = true;
+        }
+    }
+}
 
-        let conv_wall_point_set: Vec<glm::Vec3> = wall_point_set
-            .iter()
-            .map(|v| glm::vec3(v.x, v.y, v.z))
-            .collect();
+/// Textures are pinned to the wall's start point and to world height, so the pieces a wall got
+/// split into (and the steps above and below an opening) all line up.
+fn draw_textured_wall<T: Drawer>(
+    game_state: &GameState,
+    graphics_ctx: &mut super::GraphicsContext<T>,
+    view: &mut View,
+    seg: &Seg,
+    points: &[Vec3],
+    texture: &Texture,
+    light: Light,
+) -> bool {
+    let wall = &game_state.map.walls[seg.wall];
+    let along = (wall.line.end - wall.line.start).normalize();
 
-        let screen_coord = a3d_to_2d::clip_and_project_polygon(
-            &conv_wall_point_set,
-            &game_state.cam,
-            proj,
-            width,
-            height,
-        );
-        if screen_coord.len() < 3 {
-            continue;
-        }
-        let screen_coord: Vec<Point2<f32>> = screen_coord
+    let textured: Vec<(glm::Vec3, glm::Vec2)> = points
+        .iter()
+        .map(|p| {
+            let u = (Vec2::from(*p) - wall.line.start).dot(&along) + wall.u_offset;
+            let v = -p.y + wall.v_offset;
+            ((*p).into(), glm::vec2(u, v))
+        })
+        .collect();
+
+    let screen_coord = a3d_to_2d::clip_and_project_textured(
+        &textured,
+        &game_state.cam,
+        view.proj,
+        graphics_ctx.width as f32,
+        graphics_ctx.height as f32,
+    );
+    if screen_coord.len() < 3 {
+        return false;
+    }
+    view.occlusion
+        .draw_textured_polygon(graphics_ctx.drawer, &screen_coord, texture, light)
+}
+
+/// draws the floor and ceiling of a leaf, if they're facing the camera
+fn draw_flats<T: Drawer>(
+    game_state: &GameState,
+    graphics_ctx: &mut super::GraphicsContext<T>,
+    view: &mut View,
+    leaf: &Leaf,
+) {
+    if leaf.region.is_empty() {
+        return;
+    }
+    let sector = game_state.map.sector(leaf.sector);
+    let light = Light {
+        level: sector.light,
+        lighting: game_state.lighting,
+    };
+    let eye = game_state.cam.pos.y;
+    let at_height = |y: f32| -> Vec<Vec3> {
+        leaf.region
             .iter()
-            .map(|v| Point2 { x: v.x, y: v.y })
-            .collect();
-        // draw poly
-        graphics_ctx.drawer.draw_polygon(&screen_coord, color);
+            .map(|p| Vec3 { x: p.x, y, z: p.y })
+            .collect()
+    };
+
+    if eye > sector.floor {
+        let (r, g, b) = sector.floor_color;
+        let floor = at_height(sector.floor);
+        draw_3d_polygon(game_state, graphics_ctx, view, &floor, (r, g, b, 255), light);
     }
+    if eye < sector.ceiling {
+        let ceiling = at_height(sector.ceiling);
+        // an open ceiling still gets drawn, just sky coloured, so nothing behind it shows through.
+        // The sky is never lit, or it wouldn't match the skybox around it
+        let (color, light) = match sector.ceiling_color {
+            Some((r, g, b)) => ((r, g, b, 255), light),
+            None => ((skybox::SKY.0, skybox::SKY.1, skybox::SKY.2, 255), Light::NONE),
+        };
+        draw_3d_polygon(game_state, graphics_ctx, view, &ceiling, color, light);
+    }
+}
 
+/// The sector on the camera's side of a wall, which is what lights it. Solid walls only have the
+/// one sector (if that).
+fn facing_sector(game_state: &GameState, seg: &Seg) -> Option<usize> {
+    let sides = game_state.sides[seg.wall];
+    if !sides.is_two_sided() {
+        return sides.left.or(sides.right);
+    }
+    let line = seg.line;
+    let along = line.end - line.start;
+    let to_cam = Vec2::from(game_state.cam.pos) - line.start;
+    if along.x * to_cam.y - along.y * to_cam.x > 0.0 {
+        sides.left
+    } else {
+        sides.right
+    }
 }
 
-fn random_color(v: (Vec2, Vec2)) -> (u8, u8, u8) {
-    let mut rng = *RAND_32
-        + (v.0.x as u32) * 100
-        + (v.0.y as u32) * 1000
-        + (v.1.x as u32) * 5000
-        + (v.1.y as u32) * 10000;
-    let r = ((rng & 0xFF0000) >> 16) as u8;
-    rng = rng.wrapping_mul(1103515245).wrapping_add(12345);
-    let g = ((rng & 0x00FF00) >> 8) as u8;
-    rng = rng.wrapping_mul(1103515245).wrapping_add(12345);
-    let b = (rng & 0x0000FF) as u8;
-    (r, g, b)
+/// Returns whether any of the polygon made it on screen.
+fn draw_3d_polygon<T: Drawer>(
+    game_state: &GameState,
+    graphics_ctx: &mut super::GraphicsContext<T>,
+    view: &mut View,
+    points: &[Vec3],
+    color: (u8, u8, u8, u8),
+    light: Light,
+) -> bool {
+    let conv_point_set: Vec<glm::Vec3> = points
+        .iter()
+        .map(|v| glm::vec3(v.x, v.y, v.z))
+        .collect();
+
+    let screen_coord = a3d_to_2d::clip_and_project_polygon(
+        &conv_point_set,
+        &game_state.cam,
+        view.proj,
+        graphics_ctx.width as f32,
+        graphics_ctx.height as f32,
+    );
+    if screen_coord.len() < 3 {
+        return false;
+    }
+    view.occlusion
+        .draw_lit_polygon(graphics_ctx.drawer, &screen_coord, color, light)
 }
 
-fn wall_floor_to_3d(wall_left: &Vec2, wall_right: &Vec2) -> (LineSegment3, LineSegment3) {
-    let base = -0.75;
-    let offset_up = 1.75;
+fn wall_floor_to_3d(
+    wall_left: &Vec2,
+    wall_right: &Vec2,
+    base: f32,
+    top: f32,
+) -> (LineSegment3, LineSegment3) {
+    let offset_up = top - base;
     let line_seg = LineSegment3 {
         start: Vec3 {
             x: wall_left.x,
@@ -122,3 +278,67 @@ fn wall_floor_to_3d(wall_left: &Vec2, wall_right: &Vec2) -> (LineSegment3, LineS
     };
     (line_seg, line_seg_top)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::GraphicsContext;
+    use crate::game::compiled::load_any_map_from_path;
+    use crate::game::offscreen::FrameBuffer;
+    use crate::game::textures::Texture;
+    use crate::game::TexturedVertex;
+    use mint::Point2;
+
+    /// counts how many times each pixel gets drawn
+    struct Overdraw {
+        width: u32,
+        height: u32,
+        counts: Vec<u32>,
+    }
+
+    impl Overdraw {
+        fn add(&mut self, points: &[Point2<f32>]) {
+            // rasterise the same way the frame buffer does, into a mask
+            let mut mask = FrameBuffer::new(self.width, self.height);
+            mask.draw_polygon(points, (255, 255, 255, 255));
+            for (count, pixel) in self.counts.iter_mut().zip(mask.pixels().chunks_exact(4)) {
+                if pixel[0] == 255 {
+                    *count += 1;
+                }
+            }
+        }
+    }
+
+    impl Drawer for Overdraw {
+        fn draw_polygon(&mut self, points: &[Point2<f32>], _: (u8, u8, u8, u8)) {
+            self.add(points);
+        }
+
+        fn draw_textured_polygon(&mut self, points: &[TexturedVertex], _: &Texture, _: Light) {
+            let points: Vec<Point2<f32>> = points.iter().map(|p| p.pos).collect();
+            self.add(&points);
+        }
+    }
+
+    #[test]
+    fn test_every_pixel_drawn_once() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/../steps.txt");
+        let mut game_state = GameState::from_map(load_any_map_from_path(path, None).unwrap().map);
+        game_state.load_textures().unwrap();
+        game_state.place_camera(Vec2::new(5.0, 2.0), 0.3);
+
+        let (width, height) = (64, 48);
+        let mut overdraw = Overdraw {
+            width,
+            height,
+            counts: vec![0; width as usize * height as usize],
+        };
+        let mut graphics_ctx = GraphicsContext {
+            width,
+            height,
+            drawer: &mut overdraw,
+        };
+        draw_screen(&mut game_state, &mut graphics_ctx);
+        assert!(overdraw.counts.iter().all(|&count| count == 1));
+    }
+}
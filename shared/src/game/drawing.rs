@@ -6,7 +6,77 @@
 
 use mint::Point2;
 
+use super::lighting::Light;
+use super::textures::Texture;
+
+/// A corner of a textured polygon, already on screen.
+///
+/// `u` and `v` are in texture repeats (so 1.5 is halfway through the second copy), and `inv_w`
+/// is 1/w from clip space. Interpolating `u * inv_w`, `v * inv_w` and `inv_w` across the polygon
+/// and dividing gets perspective correct texture coordinates.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TexturedVertex {
+    pub pos: Point2<f32>,
+    pub u: f32,
+    pub v: f32,
+    pub inv_w: f32,
+}
+
+/// A corner of a flat coloured polygon, already on screen. `inv_w` is 1/w from clip space, the
+/// same as on a [`TexturedVertex`], which is what says how far away each pixel is for lighting.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LitVertex {
+    pub pos: Point2<f32>,
+    pub inv_w: f32,
+}
+
 pub trait Drawer {
     fn draw_polygon(&mut self, points: &[Point2<f32>], color: (u8, u8, u8, u8));
-}
 
+    /// Draws a flat coloured polygon, with every pixel shaded by `light` for its own depth
+    /// (which goes with 1/w across the screen). Backends that can't do that can leave this be,
+    /// and get the whole polygon shaded the same, by [`Light::flat`].
+    fn draw_lit_polygon(&mut self, points: &[LitVertex], color: (u8, u8, u8, u8), light: Light) {
+        let shade = light.flat(points.iter().map(|p| p.inv_w));
+        let points: Vec<Point2<f32>> = points.iter().map(|p| p.pos).collect();
+        self.draw_polygon(&points, shade.apply(color));
+    }
+
+    /// Draws a polygon with a texture stretched over it, lit the same way as
+    /// [`draw_lit_polygon`](Self::draw_lit_polygon). Backends that can't do textures can leave
+    /// this be, and get the texture's average colour lit flat instead.
+    fn draw_textured_polygon(&mut self, points: &[TexturedVertex], texture: &Texture, light: Light) {
+        let shade = light.flat(points.iter().map(|p| p.inv_w));
+        let points: Vec<Point2<f32>> = points.iter().map(|p| p.pos).collect();
+        self.draw_polygon(&points, shade.apply(texture.average_color()));
+    }
+
+    /// Draws a line `width` pixels thick with square ends. By default it goes through
+    /// [`draw_polygon`](Self::draw_polygon) as a long thin quad, so backends don't need their own.
+    fn draw_line(
+        &mut self,
+        start: Point2<f32>,
+        end: Point2<f32>,
+        width: f32,
+        color: (u8, u8, u8, u8),
+    ) {
+        let (dx, dy) = (end.x - start.x, end.y - start.y);
+        let length = (dx * dx + dy * dy).sqrt();
+        if length == 0.0 {
+            return;
+        }
+        // half the width, across the line
+        let (nx, ny) = (-dy / length * width / 2.0, dx / length * width / 2.0);
+        let corner = |p: Point2<f32>, side: f32| Point2 {
+            x: p.x + nx * side,
+            y: p.y + ny * side,
+        };
+        let quad = [
+            corner(start, 1.0),
+            corner(end, 1.0),
+            corner(end, -1.0),
+            corner(start, -1.0),
+        ];
+        self.draw_polygon(&quad, color);
+    }
+}
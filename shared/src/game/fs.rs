@@ -1,6 +1,46 @@
+//! loading maps out of the plain text format
+//!
+//! The first line of numbers is the spawn point (`start_x start_y`), every line after that is a
+//! wall (`x1 y1 x2 y2`). Lines starting with a word are directives (`name My Level`,
+//! `author someone`) and can go anywhere. Blank lines are ignored. Anything else is an error,
+//! and every bad line gets reported instead of just the first one.
+//!
+//! The spawn point can have `yaw=<degrees>` (0 looks along +y, 90 along +x) and
+//! `height=<eye height>` (up to the player's height) after it. More places to start from can be
+//! named with `spawn <name> <x> <y>`, which takes the same two.
+//!
+//! Walls between a `sector <floor> <ceiling>` line and an `end` line (or the next `sector`)
+//! make up the outline of that sector. A wall shared by two sectors can be listed in both. The
+//! `sector` line can also have `floor_color=#rrggbb` and `ceiling_color=#rrggbb` (or
+//! `ceiling_color=sky`) after the heights.
+//!
+//! `texture <name> <path>` declares a texture (the path is relative to the map file), which walls
+//! after it can use with `x1 y1 x2 y2 texture=<name>`, optionally slid along with `u=` and `v=`.
+//!
+//! Untextured walls can be given a colour with `x1 y1 x2 y2 color=#rrggbb`. `palette <name>
+//! #rrggbb` names a colour, and anywhere after it that takes a colour can use the name instead.
+//! Walls without a colour get one picked from their index, so they look the same every time.
+//!
+//! Sectors can be darker with `light=<0 to 1>`. Everything gets darker with distance too, which
+//! a `lighting` line tunes: `falloff=` (how far until it's as dark as it gets), `min_light=`
+//! (how dark that is), and `fog=` (how far until everything's `fog_color=`).
+
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use super::colors::parse_hex;
+use super::compiled::CompiledMapError;
+use super::lighting::Lighting;
+use super::lines::LineSegment;
+use super::map::{Map, MapMetadata, Sector, SourceLines, Spawn, TextureDef, Wall};
+use super::sectors::eye_height_fits;
+use super::structured::{StructuredMapError, load_json_map, load_toml_map};
 use super::vecs::Vec2;
+use super::wad::WadError;
 
 const DEFAULT_MAP: &str = "
+name default
 7.1 1.4
 05 00 10 00
 10 00 10 05
@@ -12,44 +52,958 @@ const DEFAULT_MAP: &str = "
 05 05 05 00
 ";
 
-// returns camera position and line segments from a file
-pub fn segs_from_file() -> (Vec<(Vec2, Vec2)>, Vec2) {
-    let mut args = std::env::args().skip(1);
+/// Something wrong with a single spot in a map file. Lines and columns both start at 1.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyntaxError {
+    pub line: usize,
+    pub column: usize,
+    pub kind: SyntaxErrorKind,
+}
 
-    let contents = match args.next() {
-        Some(arg) => std::fs::read_to_string(arg).expect("Failed to read file :("),
-        None => DEFAULT_MAP.to_string(),
-    };
+#[derive(Debug, Clone, PartialEq)]
+pub enum SyntaxErrorKind {
+    /// a token that isn't a number
+    InvalidNumber(String),
+    /// a line with the wrong amount of numbers on it
+    WrongFieldCount { expected: usize, found: usize },
+    /// a line starting with a word that isn't a known directive
+    UnknownDirective(String),
+    /// a directive with nothing after it
+    MissingValue(String),
+    /// a `sector` whose floor isn't below its ceiling
+    FloorAboveCeiling,
+    /// an `end` that isn't closing a `sector`
+    UnmatchedEnd,
+    /// a `key=value` with a key that doesn't mean anything there
+    UnknownAttribute(String),
+    /// a colour that isn't `#rrggbb` or the name of a palette colour
+    InvalidColor(String),
+    /// a wall using a texture that hasn't been declared (yet)
+    UnknownTexture(String),
+    /// a second texture with the same name as an earlier one
+    DuplicateTexture(String),
+    /// a second palette colour with the same name as an earlier one
+    DuplicateColor(String),
+    /// a second named spawn with the same name as an earlier one
+    DuplicateSpawn(String),
+    /// a number that parsed fine but doesn't make sense for that key
+    OutOfRange { key: String, expected: &'static str },
+}
 
-    let mut line_segments: Vec<(Vec2, Vec2)> = vec![];
-    let mut lines = contents.lines().skip_while(|line| line.trim().is_empty());
+#[derive(Debug)]
+pub enum MapError {
+    Io { path: PathBuf, source: std::io::Error },
+    /// the file doesn't have a spawn point (or anything else) in it
+    Empty,
+    /// there's a spawn point but no walls, which the BSP can't be built from
+    NoWalls,
+    /// every malformed line in the file, in order
+    Syntax(Vec<SyntaxError>),
+    /// the map read fine but doesn't make sense as a level, see [`Map::validate`]
+    Invalid(&'static str),
+    /// a TOML or JSON map that couldn't be loaded
+    Structured(StructuredMapError),
+    /// the file is a compiled map, but not one that can be loaded
+    Compiled {
+        path: PathBuf,
+        error: CompiledMapError,
+    },
+    /// the file is a WAD, but the map couldn't be imported from it
+    Wad { path: PathBuf, error: WadError },
+}
 
-    let cam_line = lines.next().expect("File is empty :(");
-    let cam_coords: Vec<_> = cam_line
-        .split_whitespace()
-        .map(|s| s.parse().expect("Failed to parse camera coordinates :("))
-        .collect();
+impl fmt::Display for SyntaxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}, column {}: ", self.line, self.column)?;
+        match &self.kind {
+            SyntaxErrorKind::InvalidNumber(token) => write!(f, "`{token}` is not a number"),
+            SyntaxErrorKind::WrongFieldCount { expected, found } => {
+                write!(f, "expected {expected} numbers, found {found}")
+            }
+            SyntaxErrorKind::UnknownDirective(word) => write!(f, "unknown directive `{word}`"),
+            SyntaxErrorKind::MissingValue(word) => write!(f, "`{word}` needs a value"),
+            SyntaxErrorKind::FloorAboveCeiling => write!(f, "floor has to be below the ceiling"),
+            SyntaxErrorKind::UnmatchedEnd => write!(f, "`end` without a `sector` to close"),
+            SyntaxErrorKind::UnknownAttribute(key) => write!(f, "unknown attribute `{key}`"),
+            SyntaxErrorKind::UnknownTexture(name) => write!(f, "no texture called `{name}`"),
+            SyntaxErrorKind::DuplicateTexture(name) => {
+                write!(f, "there's already a texture called `{name}`")
+            }
+            SyntaxErrorKind::InvalidColor(value) => {
+                write!(
+                    f,
+                    "`{value}` is not a colour, expected #rrggbb or a palette name"
+                )
+            }
+            SyntaxErrorKind::DuplicateColor(name) => {
+                write!(f, "there's already a palette colour called `{name}`")
+            }
+            SyntaxErrorKind::DuplicateSpawn(name) => {
+                write!(f, "there's already a spawn called `{name}`")
+            }
+            SyntaxErrorKind::OutOfRange { key, expected } => {
+                write!(f, "`{key}` has to be {expected}")
+            }
+        }
+    }
+}
 
-    assert!(
-        cam_coords.len() >= 2,
-        "Camera coordinates (start of file) should have 2 values :("
-    );
+impl fmt::Display for MapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MapError::Io { path, source } => {
+                write!(f, "couldn't read map {}: {source}", path.display())
+            }
+            MapError::Empty => write!(f, "map is empty"),
+            MapError::NoWalls => write!(f, "map has a spawn point but no walls"),
+            MapError::Structured(error) => write!(f, "{error}"),
+            MapError::Compiled { path, error } => write!(f, "{}: {error}", path.display()),
+            MapError::Invalid(what) => write!(f, "map is invalid: {what}"),
+            MapError::Wad { path, error } => write!(f, "{}: {error}", path.display()),
+            MapError::Syntax(errors) => {
+                write!(f, "map has {} malformed line(s)", errors.len())?;
+                for error in errors {
+                    write!(f, "\n  {error}")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
 
-    let camera_pos = if cam_coords.len() == 2 {
-        (cam_coords[0], cam_coords[1]).into()
-    } else {
-        panic!("Camera coordinates (start of file) should have 2 values :(");
-    };
+impl std::error::Error for MapError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            MapError::Io { source, .. } => Some(source),
+            MapError::Structured(error) => Some(error),
+            MapError::Compiled { error, .. } => Some(error),
+            MapError::Wad { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+/// splits a line on whitespace, keeping the (1 based) column each token starts at
+fn tokens(line: &str) -> Vec<(usize, &str)> {
+    let mut out = Vec::new();
+    let mut start = None;
+    for (i, c) in line.char_indices() {
+        match (c.is_whitespace(), start) {
+            (false, None) => start = Some(i),
+            (true, Some(s)) => {
+                out.push((s, &line[s..i]));
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    if let Some(s) = start {
+        out.push((s, &line[s..]));
+    }
+    out.into_iter()
+        .map(|(byte, token)| (line[..byte].chars().count() + 1, token))
+        .collect()
+}
+
+/// the keywords a metadata line can start with
+const DIRECTIVES: &[&str] = &[
+    "name", "author", "sector", "texture", "lighting", "palette", "spawn", "end",
+];
+
+/// Lines starting with one of the keywords are directives. Any other word is taken for a misspelt
+/// one so it's reported as such, unless it reads as a number (`inf`, `nan`), which is left for the
+/// number parsing to reject.
+fn is_directive(line: &str) -> bool {
+    match line.split_whitespace().next() {
+        Some(word) => {
+            DIRECTIVES.contains(&word)
+                || (word.starts_with(|c: char| c.is_ascii_alphabetic())
+                    && word.parse::<f32>().is_err())
+        }
+        None => false,
+    }
+}
+
+// column just past the end of a line, for complaining about things that are missing
+fn end_column(line: &str) -> usize {
+    line.trim_end().chars().count() + 1
+}
+
+/// A wall's endpoints as something that can be hashed, the same whichever way round the wall
+/// is listed.
+type WallKey = [(u32, u32); 2];
+
+fn wall_key(wall: &LineSegment) -> WallKey {
+    // adding 0 turns -0 into 0, so the two hash the same like they compare the same
+    let bits = |v: Vec2| ((v.x + 0.0).to_bits(), (v.y + 0.0).to_bits());
+    let (a, b) = (bits(wall.start), bits(wall.end));
+    if a <= b { [a, b] } else { [b, a] }
+}
+
+/// a `key=value` token
+struct Attribute<'a> {
+    column: usize,
+    key: &'a str,
+    value: &'a str,
+}
+
+impl Attribute<'_> {
+    fn value_column(&self) -> usize {
+        self.column + self.key.chars().count() + 1
+    }
+}
+
+/// splits `key=value` tokens off from the rest
+fn split_attributes<'a>(
+    tokens: &[(usize, &'a str)],
+) -> (Vec<(usize, &'a str)>, Vec<Attribute<'a>>) {
+    let mut plain = Vec::new();
+    let mut attributes = Vec::new();
+    for &(column, token) in tokens {
+        match token.split_once('=') {
+            Some((key, value)) => attributes.push(Attribute { column, key, value }),
+            None => plain.push((column, token)),
+        }
+    }
+    (plain, attributes)
+}
+
+#[derive(Default)]
+struct Parser {
+    errors: Vec<SyntaxError>,
+    metadata: MapMetadata,
+    spawn: Option<Spawn>,
+    named_spawns: Vec<(String, Spawn)>,
+    walls: Vec<Wall>,
+    // where each wall is in `walls`, so sectors sharing one can find it
+    wall_indices: HashMap<WallKey, usize>,
+    sectors: Vec<Sector>,
+    textures: Vec<TextureDef>,
+    palette: Vec<(String, (u8, u8, u8))>,
+    lighting: Lighting,
+    lines: SourceLines,
+    // the sector new walls go into, between a `sector` line and its `end`
+    current_sector: Option<usize>,
+}
+
+impl Parser {
+    fn error(&mut self, line: usize, column: usize, kind: SyntaxErrorKind) {
+        self.errors.push(SyntaxError { line, column, kind });
+    }
+
+    /// parses tokens that should be exactly `N` numbers
+    fn numbers<const N: usize>(
+        &mut self,
+        line_no: usize,
+        tokens: &[(usize, &str)],
+        end_column: usize,
+    ) -> Option<[f32; N]> {
+        let mut numbers = [0.0; N];
+        let mut ok = true;
+
+        for (i, (column, token)) in tokens.iter().enumerate() {
+            match token.parse::<f32>() {
+                Ok(n) if n.is_finite() && i < N => numbers[i] = n,
+                Ok(n) if n.is_finite() => {}
+                _ => {
+                    ok = false;
+                    let kind = SyntaxErrorKind::InvalidNumber(token.to_string());
+                    self.error(line_no, *column, kind);
+                }
+            }
+        }
+
+        if tokens.len() != N {
+            ok = false;
+            // point at the first extra token, or the end of the line if there weren't enough
+            let column = match tokens.get(N) {
+                Some((column, _)) => *column,
+                None => end_column,
+            };
+            let kind = SyntaxErrorKind::WrongFieldCount {
+                expected: N,
+                found: tokens.len(),
+            };
+            self.error(line_no, column, kind);
+        }
+
+        ok.then_some(numbers)
+    }
+
+    /// parses a `#rrggbb` colour, or the name of one from the palette
+    fn color(&mut self, line_no: usize, column: usize, value: &str) -> Option<(u8, u8, u8)> {
+        if let Some((_, color)) = self.palette.iter().find(|(name, _)| name == value) {
+            return Some(*color);
+        }
+        let color = parse_hex(value);
+        if color.is_none() {
+            self.error(
+                line_no,
+                column,
+                SyntaxErrorKind::InvalidColor(value.to_string()),
+            );
+        }
+        color
+    }
+
+    fn number(&mut self, line_no: usize, attribute: &Attribute) -> Option<f32> {
+        match attribute.value.parse() {
+            Ok(n) => Some(n),
+            Err(_) => {
+                let kind = SyntaxErrorKind::InvalidNumber(attribute.value.to_string());
+                self.error(line_no, attribute.value_column(), kind);
+                None
+            }
+        }
+    }
+
+    /// a number from 0 to 1
+    fn fraction(&mut self, line_no: usize, attribute: &Attribute) -> Option<f32> {
+        self.number_where(line_no, attribute, "from 0 to 1", |n| {
+            (0.0..=1.0).contains(&n)
+        })
+    }
+
+    /// a number above 0, like a distance
+    fn positive(&mut self, line_no: usize, attribute: &Attribute) -> Option<f32> {
+        self.number_where(line_no, attribute, "above 0", |n| n > 0.0)
+    }
 
-    for line in lines {
-        let coords: Vec<f32> = line
-            .split_whitespace()
-            .map(|s| s.parse().expect("Failed to parse coordinates :("))
-            .collect();
-        if coords.len() == 4 {
-            line_segments.push(((coords[0], coords[1]).into(), (coords[2], coords[3]).into()));
+    fn number_where(
+        &mut self,
+        line_no: usize,
+        attribute: &Attribute,
+        expected: &'static str,
+        valid: impl Fn(f32) -> bool,
+    ) -> Option<f32> {
+        let n = self.number(line_no, attribute)?;
+        if !valid(n) {
+            let kind = SyntaxErrorKind::OutOfRange {
+                key: attribute.key.to_string(),
+                expected,
+            };
+            self.error(line_no, attribute.value_column(), kind);
+            return None;
         }
+        Some(n)
     }
 
-    (line_segments, camera_pos)
+    /// `yaw=` and `height=`, on the spawn point or a `spawn` line
+    fn spawn_attributes(&mut self, line_no: usize, spawn: &mut Spawn, attributes: Vec<Attribute>) {
+        for attribute in attributes {
+            match attribute.key {
+                "yaw" => {
+                    if let Some(yaw) = self.number(line_no, &attribute) {
+                        spawn.yaw = yaw.to_radians();
+                    }
+                }
+                "height" => {
+                    let expected = "above 0 and at most the player's height";
+                    spawn.height = self.number_where(line_no, &attribute, expected, eye_height_fits)
+                }
+                _ => self.unknown_attribute(line_no, &attribute),
+            }
+        }
+    }
+
+    fn unknown_attribute(&mut self, line_no: usize, attribute: &Attribute) {
+        let kind = SyntaxErrorKind::UnknownAttribute(attribute.key.to_string());
+        self.error(line_no, attribute.column, kind);
+    }
+
+    /// parses a `keyword value...` line
+    fn directive(&mut self, line_no: usize, line: &str) {
+        let tokens = tokens(line);
+        let (column, keyword) = tokens[0];
+        let args = &tokens[1..];
+
+        match keyword {
+            "name" | "author" => {
+                let value = line.trim_start()[keyword.len()..].trim();
+                if value.is_empty() {
+                    let kind = SyntaxErrorKind::MissingValue(keyword.to_string());
+                    self.error(line_no, end_column(line), kind);
+                    return;
+                }
+                let slot = match keyword {
+                    "name" => &mut self.metadata.name,
+                    _ => &mut self.metadata.author,
+                };
+                *slot = Some(value.to_string());
+            }
+            "sector" => {
+                let (args, attributes) = split_attributes(args);
+                // even a broken sector line still opens a sector, so its walls don't end up
+                // outside of it
+                let mut sector = Sector::default();
+                if let Some([floor, ceiling]) = self.numbers::<2>(line_no, &args, end_column(line))
+                {
+                    if floor >= ceiling {
+                        self.error(line_no, args[0].0, SyntaxErrorKind::FloorAboveCeiling);
+                    }
+                    sector.floor = floor;
+                    sector.ceiling = ceiling;
+                }
+                for attribute in attributes {
+                    match attribute.key {
+                        "floor_color" => {
+                            if let Some(color) =
+                                self.color(line_no, attribute.value_column(), attribute.value)
+                            {
+                                sector.floor_color = color;
+                            }
+                        }
+                        "ceiling_color" if attribute.value == "sky" => sector.ceiling_color = None,
+                        "ceiling_color" => {
+                            if let Some(color) =
+                                self.color(line_no, attribute.value_column(), attribute.value)
+                            {
+                                sector.ceiling_color = Some(color);
+                            }
+                        }
+                        "light" => {
+                            if let Some(light) = self.fraction(line_no, &attribute) {
+                                sector.light = light;
+                            }
+                        }
+                        _ => self.unknown_attribute(line_no, &attribute),
+                    }
+                }
+                self.sectors.push(sector);
+                self.lines.sectors.push(line_no);
+                self.current_sector = Some(self.sectors.len() - 1);
+            }
+            "texture" => {
+                let Some(&(name_column, name)) = args.first() else {
+                    let kind = SyntaxErrorKind::MissingValue(keyword.to_string());
+                    self.error(line_no, end_column(line), kind);
+                    return;
+                };
+                // paths can have spaces in them, so it's the whole rest of the line
+                let after_keyword = line.trim_start()[keyword.len()..].trim_start();
+                let path = after_keyword[name.len()..].trim();
+                if path.is_empty() {
+                    let kind = SyntaxErrorKind::MissingValue(keyword.to_string());
+                    self.error(line_no, end_column(line), kind);
+                    return;
+                }
+                if self.textures.iter().any(|t| t.name == name) {
+                    let kind = SyntaxErrorKind::DuplicateTexture(name.to_string());
+                    self.error(line_no, name_column, kind);
+                    return;
+                }
+                self.textures.push(TextureDef {
+                    name: name.to_string(),
+                    path: PathBuf::from(path),
+                });
+            }
+            "lighting" => {
+                let (args, attributes) = split_attributes(args);
+                self.numbers::<0>(line_no, &args, end_column(line));
+                for attribute in attributes {
+                    match attribute.key {
+                        "falloff" => {
+                            if let Some(falloff) = self.positive(line_no, &attribute) {
+                                self.lighting.falloff = falloff;
+                            }
+                        }
+                        "min_light" => {
+                            if let Some(min_light) = self.fraction(line_no, &attribute) {
+                                self.lighting.min_light = min_light;
+                            }
+                        }
+                        "fog" => {
+                            if let Some(fog) = self.positive(line_no, &attribute) {
+                                self.lighting.fog_distance = Some(fog);
+                            }
+                        }
+                        "fog_color" => {
+                            let column = attribute.value_column();
+                            if let Some(color) = self.color(line_no, column, attribute.value) {
+                                self.lighting.fog_color = color;
+                            }
+                        }
+                        _ => self.unknown_attribute(line_no, &attribute),
+                    }
+                }
+            }
+            "palette" => {
+                let (Some(&(name_column, name)), Some(&(value_column, _))) =
+                    (args.first(), args.get(1))
+                else {
+                    let kind = SyntaxErrorKind::MissingValue(keyword.to_string());
+                    self.error(line_no, end_column(line), kind);
+                    return;
+                };
+                if self.palette.iter().any(|(existing, _)| existing == name) {
+                    let kind = SyntaxErrorKind::DuplicateColor(name.to_string());
+                    self.error(line_no, name_column, kind);
+                    return;
+                }
+                // anything past the name is the colour, so stray extra words make it a bad one
+                let after_keyword = line.trim_start()[keyword.len()..].trim_start();
+                let value = after_keyword[name.len()..].trim();
+                if let Some(color) = self.color(line_no, value_column, value) {
+                    self.palette.push((name.to_string(), color));
+                }
+            }
+            "spawn" => {
+                let (args, attributes) = split_attributes(args);
+                let Some(&(name_column, name)) = args.first() else {
+                    let kind = SyntaxErrorKind::MissingValue(keyword.to_string());
+                    self.error(line_no, end_column(line), kind);
+                    return;
+                };
+                let parsed = self.numbers::<2>(line_no, &args[1..], end_column(line));
+                let mut spawn =
+                    Spawn::new(parsed.map(|[x, y]| Vec2::new(x, y)).unwrap_or_default());
+                self.spawn_attributes(line_no, &mut spawn, attributes);
+                if self
+                    .named_spawns
+                    .iter()
+                    .any(|(existing, _)| existing == name)
+                {
+                    let kind = SyntaxErrorKind::DuplicateSpawn(name.to_string());
+                    self.error(line_no, name_column, kind);
+                    return;
+                }
+                self.named_spawns.push((name.to_string(), spawn));
+                self.lines.named_spawns.push(line_no);
+            }
+            "end" => {
+                self.numbers::<0>(line_no, args, end_column(line));
+                if self.current_sector.take().is_none() {
+                    self.error(line_no, column, SyntaxErrorKind::UnmatchedEnd);
+                }
+            }
+            _ => {
+                let kind = SyntaxErrorKind::UnknownDirective(keyword.to_string());
+                self.error(line_no, column, kind);
+            }
+        }
+    }
+
+    fn wall(&mut self, line_no: usize, line: &str) {
+        let tokens = tokens(line);
+        let (coords, attributes) = split_attributes(&tokens);
+        let Some([x1, y1, x2, y2]) = self.numbers::<4>(line_no, &coords, end_column(line)) else {
+            return;
+        };
+        let mut wall = Wall::new(LineSegment::from((x1, y1, x2, y2)));
+        for attribute in attributes {
+            match attribute.key {
+                "texture" => match self.textures.iter().position(|t| t.name == attribute.value) {
+                    Some(id) => wall.texture = Some(id),
+                    None => {
+                        let kind = SyntaxErrorKind::UnknownTexture(attribute.value.to_string());
+                        self.error(line_no, attribute.value_column(), kind);
+                    }
+                },
+                "color" => {
+                    wall.color = self.color(line_no, attribute.value_column(), attribute.value)
+                }
+                "u" => wall.u_offset = self.number(line_no, &attribute).unwrap_or(0.0),
+                "v" => wall.v_offset = self.number(line_no, &attribute).unwrap_or(0.0),
+                _ => self.unknown_attribute(line_no, &attribute),
+            }
+        }
+
+        let key = wall_key(&wall.line);
+        let Some(sector) = self.current_sector else {
+            self.wall_indices.entry(key).or_insert(self.walls.len());
+            self.walls.push(wall);
+            self.lines.walls.push(line_no);
+            return;
+        };
+        // sectors that share an edge both list it, but it's still just the one wall
+        let index = match self.wall_indices.get(&key) {
+            Some(&index) => {
+                // the second listing only gets a say if it actually styles the wall
+                if wall != Wall::new(wall.line) {
+                    self.walls[index] = Wall {
+                        line: self.walls[index].line,
+                        ..wall
+                    };
+                }
+                index
+            }
+            None => {
+                self.wall_indices.insert(key, self.walls.len());
+                self.walls.push(wall);
+                self.lines.walls.push(line_no);
+                self.walls.len() - 1
+            }
+        };
+        self.sectors[sector].walls.push(index);
+    }
+
+    fn line(&mut self, line_no: usize, line: &str) {
+        if is_directive(line) {
+            self.directive(line_no, line);
+        } else if self.spawn.is_none() {
+            let tokens = tokens(line);
+            let (args, attributes) = split_attributes(&tokens);
+            let parsed = self.numbers::<2>(line_no, &args, end_column(line));
+            let mut spawn = Spawn::new(parsed.map(|[x, y]| Vec2::new(x, y)).unwrap_or_default());
+            self.spawn_attributes(line_no, &mut spawn, attributes);
+            self.spawn = Some(spawn);
+            self.lines.spawn = line_no;
+        } else {
+            self.wall(line_no, line);
+        }
+    }
+
+    fn finish(self) -> Result<Map, MapError> {
+        if !self.errors.is_empty() {
+            return Err(MapError::Syntax(self.errors));
+        }
+        let spawn = self.spawn.ok_or(MapError::Empty)?;
+        if self.walls.is_empty() {
+            return Err(MapError::NoWalls);
+        }
+
+        let map = Map {
+            spawn,
+            named_spawns: self.named_spawns,
+            walls: self.walls,
+            sectors: self.sectors,
+            textures: self.textures,
+            palette: self.palette,
+            lighting: self.lighting,
+            entities: Vec::new(),
+            metadata: MapMetadata {
+                lines: Some(self.lines),
+                ..self.metadata
+            },
+        };
+        map.validate().map_err(MapError::Invalid)?;
+        Ok(map)
+    }
+}
+
+/// Parses a map out of the contents of a map file.
+pub fn load_map(contents: &str) -> Result<Map, MapError> {
+    let mut parser = Parser::default();
+    for (i, line) in contents.lines().enumerate() {
+        if !line.trim().is_empty() {
+            parser.line(i + 1, line);
+        }
+    }
+    parser.finish()
+}
+
+/// Parses the contents of a map file in whichever format its extension says.
+pub(super) fn parse_map_file(path: &Path, contents: &str) -> Result<Map, MapError> {
+    let extension = path.extension().and_then(|ext| ext.to_str());
+    let mut map = match extension.map(str::to_ascii_lowercase).as_deref() {
+        Some("toml") => load_toml_map(contents)?,
+        Some("json") => load_json_map(contents)?,
+        _ => load_map(contents)?,
+    };
+    map.metadata.source = Some(path.to_path_buf());
+    Ok(map)
+}
+
+/// The small map that gets used when nobody asks for a specific one.
+pub fn builtin_map() -> Map {
+    load_map(DEFAULT_MAP).expect("the built in map should always parse")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_default_map() {
+        let map = builtin_map();
+        assert_eq!(map.metadata.name.as_deref(), Some("default"));
+        assert_eq!(map.spawn, Spawn::new(Vec2::new(7.1, 1.4)));
+        assert_eq!(map.walls.len(), 8);
+        assert_eq!(map.walls[0].line, LineSegment::from((5.0, 0.0, 10.0, 0.0)));
+    }
+
+    #[test]
+    fn test_reports_every_bad_line() {
+        let contents = "0 0\n1 2 3 4\n1 2 3\n\n1 x 3 4\n1 2 3 4 5\n";
+        let errors = match load_map(contents) {
+            Err(MapError::Syntax(errors)) => errors,
+            other => panic!("expected syntax errors, got {other:?}"),
+        };
+        assert_eq!(
+            errors,
+            vec![
+                SyntaxError {
+                    line: 3,
+                    column: 6,
+                    kind: SyntaxErrorKind::WrongFieldCount { expected: 4, found: 3 },
+                },
+                SyntaxError {
+                    line: 5,
+                    column: 3,
+                    kind: SyntaxErrorKind::InvalidNumber("x".to_string()),
+                },
+                SyntaxError {
+                    line: 6,
+                    column: 9,
+                    kind: SyntaxErrorKind::WrongFieldCount { expected: 4, found: 5 },
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_directives() {
+        let map = load_map("name  Big Room \n0 0\nauthor Enzo\n0 0 1 0\n").unwrap();
+        assert_eq!(map.metadata.name.as_deref(), Some("Big Room"));
+        assert_eq!(map.metadata.author.as_deref(), Some("Enzo"));
+        assert_eq!(map.walls.len(), 1);
+
+        let errors = match load_map("0 0\ncolour red\nname\n0 0 1 0\n") {
+            Err(MapError::Syntax(errors)) => errors,
+            other => panic!("expected syntax errors, got {other:?}"),
+        };
+        assert_eq!(
+            errors.iter().map(|e| e.kind.clone()).collect::<Vec<_>>(),
+            vec![
+                SyntaxErrorKind::UnknownDirective("colour".to_string()),
+                SyntaxErrorKind::MissingValue("name".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_non_finite_numbers_are_not_directives() {
+        let errors = match load_map("0 0\ninf 0 1 0\n0 0 NaN 1\n") {
+            Err(MapError::Syntax(errors)) => errors,
+            other => panic!("expected syntax errors, got {other:?}"),
+        };
+        assert_eq!(
+            errors,
+            vec![
+                SyntaxError {
+                    line: 2,
+                    column: 1,
+                    kind: SyntaxErrorKind::InvalidNumber("inf".to_string()),
+                },
+                SyntaxError {
+                    line: 3,
+                    column: 5,
+                    kind: SyntaxErrorKind::InvalidNumber("NaN".to_string()),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_spawns() {
+        let map =
+            load_map("1 2 yaw=90 height=0.5\nspawn exit 4 5\n0 0 1 0\nspawn hall 6 7 yaw=-90\n")
+                .unwrap();
+        assert_eq!(map.spawn.position, Vec2::new(1.0, 2.0));
+        assert!((map.spawn.yaw - std::f32::consts::FRAC_PI_2).abs() < 1e-6);
+        assert_eq!(map.spawn.height, Some(0.5));
+        assert_eq!(
+            map.named_spawn("exit"),
+            Some(&Spawn::new(Vec2::new(4.0, 5.0)))
+        );
+        assert!((map.named_spawn("hall").unwrap().yaw + std::f32::consts::FRAC_PI_2).abs() < 1e-6);
+        assert_eq!(map.named_spawn("nope"), None);
+        assert_eq!(map.metadata.lines.unwrap().named_spawns, [2, 4]);
+
+        let contents = "0 0 height=0\nspawn\nspawn a 1 1\nspawn a 2 2 pitch=3 height=2\n0 0 1 0\n";
+        let errors = match load_map(contents) {
+            Err(MapError::Syntax(errors)) => errors,
+            other => panic!("expected syntax errors, got {other:?}"),
+        };
+        let too_high = SyntaxErrorKind::OutOfRange {
+            key: "height".to_string(),
+            expected: "above 0 and at most the player's height",
+        };
+        assert_eq!(
+            errors.iter().map(|e| e.kind.clone()).collect::<Vec<_>>(),
+            vec![
+                too_high.clone(),
+                SyntaxErrorKind::MissingValue("spawn".to_string()),
+                SyntaxErrorKind::UnknownAttribute("pitch".to_string()),
+                too_high,
+                SyntaxErrorKind::DuplicateSpawn("a".to_string()),
+            ]
+        );
+
+        // fine on its own, but not in a sector that low
+        let low = "sector 0 0.5\n-1 -1 1 -1\n1 -1 1 1\n1 1 -1 1\n-1 1 -1 -1\n";
+        assert!(load_map(&format!("0 0 height=0.25\n{low}")).is_ok());
+        assert!(matches!(
+            load_map(&format!("0 0 height=0.75\n{low}")),
+            Err(MapError::Invalid("spawn height puts the camera through the ceiling"))
+        ));
+    }
+
+    #[test]
+    fn test_sectors() {
+        let contents = "0 0\n-1 -1 -2 -2\nsector 0 2\n0 0 1 0\n1 0 0 1\n0 1 0 0\nend\n\
+                        sector 0.5 2\n1 0 0 1\n1 0 1 1\n1 1 0 1\n";
+        let map = load_map(contents).unwrap();
+        assert_eq!(map.walls.len(), 6);
+        assert_eq!(map.sectors.len(), 2);
+        assert_eq!(map.sectors[0].walls, vec![1, 2, 3]);
+        // the diagonal is shared, not duplicated
+        assert_eq!(map.sectors[1].walls, vec![2, 4, 5]);
+        assert_eq!(map.sectors[1].floor, 0.5);
+
+        assert_eq!(map.sector_at(Vec2::new(0.2, 0.2)), Some(0));
+        assert_eq!(map.sector_at(Vec2::new(0.8, 0.8)), Some(1));
+        assert_eq!(map.sector_at(Vec2::new(5.0, 5.0)), None);
+
+        let map =
+            load_map("0 0\nsector 0 1 floor_color=#ff0080 ceiling_color=sky\n0 0 1 0\n").unwrap();
+        assert_eq!(map.sectors[0].floor_color, (255, 0, 128));
+        assert_eq!(map.sectors[0].ceiling_color, None);
+
+        let errors = match load_map("0 0\nsector 2 1\n0 0 1 0\nend\nend\n") {
+            Err(MapError::Syntax(errors)) => errors,
+            other => panic!("expected syntax errors, got {other:?}"),
+        };
+        assert_eq!(
+            errors
+                .iter()
+                .map(|e| (e.line, e.kind.clone()))
+                .collect::<Vec<_>>(),
+            vec![
+                (2, SyntaxErrorKind::FloorAboveCeiling),
+                (5, SyntaxErrorKind::UnmatchedEnd),
+            ]
+        );
+
+        let errors = match load_map("0 0\nsector 0 1 floor_color=red glow=1\n0 0 1 0\n") {
+            Err(MapError::Syntax(errors)) => errors,
+            other => panic!("expected syntax errors, got {other:?}"),
+        };
+        assert_eq!(
+            errors
+                .iter()
+                .map(|e| (e.column, e.kind.clone()))
+                .collect::<Vec<_>>(),
+            vec![
+                (24, SyntaxErrorKind::InvalidColor("red".to_string())),
+                (28, SyntaxErrorKind::UnknownAttribute("glow".to_string())),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_textures() {
+        let contents = "0 0\ntexture brick  bricks/red brick.png\n0 0 1 0 texture=brick u=0.5\n\
+                        1 0 1 1 v=-2\n";
+        let map = load_map(contents).unwrap();
+        assert_eq!(map.textures[0].name, "brick");
+        assert_eq!(map.textures[0].path, PathBuf::from("bricks/red brick.png"));
+        assert_eq!(map.walls[0].texture, Some(0));
+        assert_eq!(map.walls[0].u_offset, 0.5);
+        assert_eq!(map.walls[1].texture, None);
+        assert_eq!(map.walls[1].v_offset, -2.0);
+
+        let contents = "0 0\n0 0 1 0 texture=brick\ntexture brick a.png\ntexture brick b.png\n\
+                        texture\n0 0 1 0 u=x\n";
+        let errors = match load_map(contents) {
+            Err(MapError::Syntax(errors)) => errors,
+            other => panic!("expected syntax errors, got {other:?}"),
+        };
+        assert_eq!(
+            errors
+                .iter()
+                .map(|e| (e.line, e.column, e.kind.clone()))
+                .collect::<Vec<_>>(),
+            vec![
+                (2, 17, SyntaxErrorKind::UnknownTexture("brick".to_string())),
+                (4, 9, SyntaxErrorKind::DuplicateTexture("brick".to_string())),
+                (5, 8, SyntaxErrorKind::MissingValue("texture".to_string())),
+                (6, 11, SyntaxErrorKind::InvalidNumber("x".to_string())),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lighting() {
+        let contents = "0 0\nlighting falloff=10 min_light=0.5 fog=20 fog_color=#808080\n\
+                        sector 0 1 light=0.5\n0 0 1 0\n";
+        let map = load_map(contents).unwrap();
+        assert_eq!(map.sectors[0].light, 0.5);
+        assert_eq!(
+            map.lighting,
+            Lighting {
+                falloff: 10.0,
+                min_light: 0.5,
+                fog_distance: Some(20.0),
+                fog_color: (128, 128, 128),
+            }
+        );
+        assert_eq!(
+            load_map("0 0\n0 0 1 0\n").unwrap().lighting,
+            Lighting::default()
+        );
+
+        let contents = "0 0\nsector 0 1 light=2\nlighting falloff=0 min_light=x\n0 0 1 0\n";
+        let errors = match load_map(contents) {
+            Err(MapError::Syntax(errors)) => errors,
+            other => panic!("expected syntax errors, got {other:?}"),
+        };
+        let out_of_range = |key: &str, expected| SyntaxErrorKind::OutOfRange {
+            key: key.to_string(),
+            expected,
+        };
+        assert_eq!(
+            errors
+                .iter()
+                .map(|e| (e.line, e.column, e.kind.clone()))
+                .collect::<Vec<_>>(),
+            vec![
+                (2, 18, out_of_range("light", "from 0 to 1")),
+                (3, 18, out_of_range("falloff", "above 0")),
+                (3, 30, SyntaxErrorKind::InvalidNumber("x".to_string())),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_palette_and_wall_colors() {
+        let contents = "0 0\npalette rust #b7410e\nsector 0 1 floor_color=rust\n\
+                        0 0 1 0 color=rust\n1 0 1 1 color=#00ff00\n1 1 0 1\n";
+        let map = load_map(contents).unwrap();
+        assert_eq!(map.palette, vec![("rust".to_string(), (0xb7, 0x41, 0x0e))]);
+        assert_eq!(map.sectors[0].floor_color, (0xb7, 0x41, 0x0e));
+        assert_eq!(map.walls[0].color, Some((0xb7, 0x41, 0x0e)));
+        assert_eq!(map.walls[1].color, Some((0, 255, 0)));
+        assert_eq!(map.walls[2].color, None);
+
+        let contents = "0 0\n0 0 1 0 color=rust\npalette rust #b7410e\npalette rust #000000\n\
+                        palette\npalette dark #000000 #111111\n";
+        let errors = match load_map(contents) {
+            Err(MapError::Syntax(errors)) => errors,
+            other => panic!("expected syntax errors, got {other:?}"),
+        };
+        assert_eq!(
+            errors
+                .iter()
+                .map(|e| (e.line, e.column, e.kind.clone()))
+                .collect::<Vec<_>>(),
+            vec![
+                (2, 15, SyntaxErrorKind::InvalidColor("rust".to_string())),
+                (4, 9, SyntaxErrorKind::DuplicateColor("rust".to_string())),
+                (5, 8, SyntaxErrorKind::MissingValue("palette".to_string())),
+                (
+                    6,
+                    14,
+                    SyntaxErrorKind::InvalidColor("#000000 #111111".to_string())
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_empty_and_wallless_maps() {
+        assert!(matches!(load_map(" \n\n"), Err(MapError::Empty)));
+        assert!(matches!(load_map("name nothing\n"), Err(MapError::Empty)));
+        assert!(matches!(load_map("1 1\n"), Err(MapError::NoWalls)));
+    }
 }
@@ -0,0 +1,38 @@
+//! gamepads as gilrs sees them, for the frontends that read them through it, so they all agree
+//! on which button is which of the game's [`Key`]s
+
+use gilrs::{Axis, Button, Gamepad};
+
+use super::input::Key;
+use super::vecs::Vec2;
+
+/// The gamepad button a key stands for, if it's one of them.
+pub fn pad_button(key: Key) -> Option<Button> {
+    Some(match key {
+        Key::PadSouth => Button::South,
+        Key::PadEast => Button::East,
+        Key::PadNorth => Button::North,
+        Key::PadWest => Button::West,
+        Key::PadLeftBumper => Button::LeftTrigger,
+        Key::PadRightBumper => Button::RightTrigger,
+        Key::PadLeftTrigger => Button::LeftTrigger2,
+        Key::PadRightTrigger => Button::RightTrigger2,
+        Key::PadSelect => Button::Select,
+        Key::PadStart => Button::Start,
+        Key::PadLeftStick => Button::LeftThumb,
+        Key::PadRightStick => Button::RightThumb,
+        Key::PadUp => Button::DPadUp,
+        Key::PadDown => Button::DPadDown,
+        Key::PadLeft => Button::DPadLeft,
+        Key::PadRight => Button::DPadRight,
+        _ => return None,
+    })
+}
+
+/// A gamepad's left and right sticks, with +y up, ready for
+/// [`Bindings::add_sticks`](super::Bindings::add_sticks).
+pub fn pad_sticks(pad: &Gamepad) -> (Vec2, Vec2) {
+    let left = Vec2::new(pad.value(Axis::LeftStickX), pad.value(Axis::LeftStickY));
+    let right = Vec2::new(pad.value(Axis::RightStickX), pad.value(Axis::RightStickY));
+    (left, right)
+}
@@ -0,0 +1,820 @@
+//! what the player's asking the game to do, kept apart from which keys they pressed to do it
+//!
+//! Frontends work out which [`Key`]s are down, however their windowing library reports them, and
+//! [`Bindings`] turns those into the [`Action`]s the game responds to. Which keys do what comes
+//! from a TOML file that every frontend reads, with an entry for each action to change:
+//!
+//! ```toml
+//! move_forward = ["Up", "W"]
+//! fire = ["LeftCtrl", "MouseLeft"]
+//! use = []  # nothing
+//! ```
+//!
+//! Actions that aren't listed keep their default keys. Names don't care about case, and a key
+//! can do more than one thing. A `[mouse]` table at the end sets up mouse look:
+//!
+//! ```toml
+//! [mouse]
+//! sensitivity = 0.0025  # radians a pixel
+//! invert_y = true
+//! ```
+//!
+//! and a `[gamepad]` table sets up the sticks. The left one walks and the right one turns and
+//! looks, which goes faster the further it's pushed, slowly at first and then quickly for a
+//! `curve` above 1:
+//!
+//! ```toml
+//! [gamepad]
+//! dead_zone = 0.2    # how far a stick goes before it does anything, from 0 to 1
+//! turn_speed = 3.0   # radians a second with the stick all the way over
+//! curve = 2.0
+//! invert_y = false
+//! ```
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io;
+use std::path::Path;
+
+use serde::Deserialize;
+
+use super::vecs::Vec2;
+
+/// Where frontends look for bindings, relative to wherever they're run from.
+pub const BINDINGS_FILE: &str = "bindings.toml";
+
+/// Something the player can ask for by holding down a key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Action {
+    MoveForward,
+    MoveBack,
+    StrafeLeft,
+    StrafeRight,
+    TurnLeft,
+    TurnRight,
+    LookUp,
+    LookDown,
+    /// Nothing in the level reacts to this yet, but it can be bound ready for when it does.
+    Use,
+    /// Like [`Use`](Action::Use), there's nothing to fire yet.
+    Fire,
+    /// Opens and closes the automap. While it's open the movement actions pan it instead.
+    ToggleMap,
+    MapZoomIn,
+    MapZoomOut,
+}
+
+impl Action {
+    pub const ALL: [Action; 13] = [
+        Action::MoveForward,
+        Action::MoveBack,
+        Action::StrafeLeft,
+        Action::StrafeRight,
+        Action::TurnLeft,
+        Action::TurnRight,
+        Action::LookUp,
+        Action::LookDown,
+        Action::Use,
+        Action::Fire,
+        Action::ToggleMap,
+        Action::MapZoomIn,
+        Action::MapZoomOut,
+    ];
+
+    /// what it's called in bindings files
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::MoveForward => "move_forward",
+            Action::MoveBack => "move_back",
+            Action::StrafeLeft => "strafe_left",
+            Action::StrafeRight => "strafe_right",
+            Action::TurnLeft => "turn_left",
+            Action::TurnRight => "turn_right",
+            Action::LookUp => "look_up",
+            Action::LookDown => "look_down",
+            Action::Use => "use",
+            Action::Fire => "fire",
+            Action::ToggleMap => "toggle_map",
+            Action::MapZoomIn => "map_zoom_in",
+            Action::MapZoomOut => "map_zoom_out",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Action> {
+        Action::ALL
+            .into_iter()
+            .find(|action| action.name().eq_ignore_ascii_case(name))
+    }
+
+    /// what it's bound to without a bindings file
+    pub fn default_keys(self) -> &'static [Key] {
+        match self {
+            Action::MoveForward => &[Key::Up, Key::PadUp],
+            Action::MoveBack => &[Key::Down, Key::PadDown],
+            Action::StrafeLeft => &[Key::Comma, Key::A],
+            Action::StrafeRight => &[Key::Period, Key::D],
+            Action::TurnLeft => &[Key::Left, Key::PadLeft],
+            Action::TurnRight => &[Key::Right, Key::PadRight],
+            Action::LookUp => &[Key::W],
+            Action::LookDown => &[Key::S],
+            Action::Use => &[Key::Space, Key::PadSouth],
+            Action::Fire => &[Key::LeftCtrl, Key::MouseLeft, Key::PadRightTrigger],
+            Action::ToggleMap => &[Key::Tab, Key::PadSelect],
+            Action::MapZoomIn => &[Key::Equals, Key::W, Key::PadRightBumper],
+            Action::MapZoomOut => &[Key::Minus, Key::S, Key::PadLeftBumper],
+        }
+    }
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// A key on the keyboard, a mouse button or a gamepad button, named the same whatever the
+/// frontend.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Key {
+    A,
+    B,
+    C,
+    D,
+    E,
+    F,
+    G,
+    H,
+    I,
+    J,
+    K,
+    L,
+    M,
+    N,
+    O,
+    P,
+    Q,
+    R,
+    S,
+    T,
+    U,
+    V,
+    W,
+    X,
+    Y,
+    Z,
+    Num0,
+    Num1,
+    Num2,
+    Num3,
+    Num4,
+    Num5,
+    Num6,
+    Num7,
+    Num8,
+    Num9,
+    Up,
+    Down,
+    Left,
+    Right,
+    Space,
+    Tab,
+    Enter,
+    Escape,
+    Backspace,
+    LeftShift,
+    RightShift,
+    LeftCtrl,
+    RightCtrl,
+    LeftAlt,
+    RightAlt,
+    Minus,
+    Equals,
+    Comma,
+    Period,
+    Slash,
+    Semicolon,
+    MouseLeft,
+    MouseRight,
+    MouseMiddle,
+    /// the bottom face button (A on an Xbox pad, cross on a PlayStation one)
+    PadSouth,
+    PadEast,
+    PadNorth,
+    PadWest,
+    PadLeftBumper,
+    PadRightBumper,
+    PadLeftTrigger,
+    PadRightTrigger,
+    PadSelect,
+    PadStart,
+    /// clicking the left stick in
+    PadLeftStick,
+    PadRightStick,
+    /// the d-pad
+    PadUp,
+    PadDown,
+    PadLeft,
+    PadRight,
+}
+
+/// every key, with what it's called in bindings files
+const KEY_NAMES: [(Key, &str); 76] = [
+    (Key::A, "A"),
+    (Key::B, "B"),
+    (Key::C, "C"),
+    (Key::D, "D"),
+    (Key::E, "E"),
+    (Key::F, "F"),
+    (Key::G, "G"),
+    (Key::H, "H"),
+    (Key::I, "I"),
+    (Key::J, "J"),
+    (Key::K, "K"),
+    (Key::L, "L"),
+    (Key::M, "M"),
+    (Key::N, "N"),
+    (Key::O, "O"),
+    (Key::P, "P"),
+    (Key::Q, "Q"),
+    (Key::R, "R"),
+    (Key::S, "S"),
+    (Key::T, "T"),
+    (Key::U, "U"),
+    (Key::V, "V"),
+    (Key::W, "W"),
+    (Key::X, "X"),
+    (Key::Y, "Y"),
+    (Key::Z, "Z"),
+    (Key::Num0, "0"),
+    (Key::Num1, "1"),
+    (Key::Num2, "2"),
+    (Key::Num3, "3"),
+    (Key::Num4, "4"),
+    (Key::Num5, "5"),
+    (Key::Num6, "6"),
+    (Key::Num7, "7"),
+    (Key::Num8, "8"),
+    (Key::Num9, "9"),
+    (Key::Up, "Up"),
+    (Key::Down, "Down"),
+    (Key::Left, "Left"),
+    (Key::Right, "Right"),
+    (Key::Space, "Space"),
+    (Key::Tab, "Tab"),
+    (Key::Enter, "Enter"),
+    (Key::Escape, "Escape"),
+    (Key::Backspace, "Backspace"),
+    (Key::LeftShift, "LeftShift"),
+    (Key::RightShift, "RightShift"),
+    (Key::LeftCtrl, "LeftCtrl"),
+    (Key::RightCtrl, "RightCtrl"),
+    (Key::LeftAlt, "LeftAlt"),
+    (Key::RightAlt, "RightAlt"),
+    (Key::Minus, "Minus"),
+    (Key::Equals, "Equals"),
+    (Key::Comma, "Comma"),
+    (Key::Period, "Period"),
+    (Key::Slash, "Slash"),
+    (Key::Semicolon, "Semicolon"),
+    (Key::MouseLeft, "MouseLeft"),
+    (Key::MouseRight, "MouseRight"),
+    (Key::MouseMiddle, "MouseMiddle"),
+    (Key::PadSouth, "PadSouth"),
+    (Key::PadEast, "PadEast"),
+    (Key::PadNorth, "PadNorth"),
+    (Key::PadWest, "PadWest"),
+    (Key::PadLeftBumper, "PadLeftBumper"),
+    (Key::PadRightBumper, "PadRightBumper"),
+    (Key::PadLeftTrigger, "PadLeftTrigger"),
+    (Key::PadRightTrigger, "PadRightTrigger"),
+    (Key::PadSelect, "PadSelect"),
+    (Key::PadStart, "PadStart"),
+    (Key::PadLeftStick, "PadLeftStick"),
+    (Key::PadRightStick, "PadRightStick"),
+    (Key::PadUp, "PadUp"),
+    (Key::PadDown, "PadDown"),
+    (Key::PadLeft, "PadLeft"),
+    (Key::PadRight, "PadRight"),
+];
+
+impl Key {
+    /// Every key there is, for frontends that can only ask whether a particular key is down.
+    pub fn all() -> impl Iterator<Item = Key> {
+        KEY_NAMES.into_iter().map(|(key, _)| key)
+    }
+
+    /// what it's called in bindings files
+    pub fn name(self) -> &'static str {
+        KEY_NAMES
+            .iter()
+            .find(|(key, _)| *key == self)
+            .map(|(_, name)| *name)
+            .expect("every key has a name")
+    }
+
+    pub fn from_name(name: &str) -> Option<Key> {
+        KEY_NAMES
+            .iter()
+            .find(|(_, key_name)| key_name.eq_ignore_ascii_case(name))
+            .map(|(key, _)| *key)
+    }
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// What the player's asking for this frame, whatever they pressed to ask for it.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Input {
+    held: Vec<Action>,
+    /// from something analog, like a gamepad stick
+    movement: Vec2,
+    /// radians to turn (x) and look up (y) by
+    look: Vec2,
+    /// radians a second to turn (x) and look up (y) at
+    turning: Vec2,
+}
+
+impl Input {
+    pub fn press(&mut self, action: Action) {
+        if !self.held.contains(&action) {
+            self.held.push(action);
+        }
+    }
+
+    pub fn is_held(&self, action: Action) -> bool {
+        self.held.contains(&action)
+    }
+
+    /// Asks to walk some fraction of full speed, relative to the way the player's facing: +y is
+    /// forward and +x is to their right, and each goes from -1 to 1. This adds to whatever the
+    /// movement actions ask for.
+    pub fn set_movement(&mut self, movement: Vec2) {
+        self.movement = movement;
+    }
+
+    /// Asks to turn right by `look.x` radians and look up by `look.y` radians, all at once rather
+    /// than over time, the way the mouse does it. [`Bindings::input`] fills this in from how far
+    /// the mouse moved.
+    pub fn set_look(&mut self, look: Vec2) {
+        self.look = look;
+    }
+
+    pub fn look(&self) -> Vec2 {
+        self.look
+    }
+
+    /// Asks to keep turning right at `turning.x` radians a second and looking up at `turning.y`,
+    /// the way a stick does it. [`Bindings::add_sticks`] fills this in from a gamepad.
+    pub fn set_turning(&mut self, turning: Vec2) {
+        self.turning = turning;
+    }
+
+    pub fn turning(&self) -> Vec2 {
+        self.turning
+    }
+
+    /// Which way to walk and how fast, from the movement actions and anything analog put
+    /// together. It's never longer than 1, so going diagonally isn't any faster than going
+    /// straight.
+    pub fn movement(&self) -> Vec2 {
+        let mut movement = self.movement;
+        let axis = |positive, negative| {
+            (self.is_held(positive) as i32 - self.is_held(negative) as i32) as f32
+        };
+        movement.x += axis(Action::StrafeRight, Action::StrafeLeft);
+        movement.y += axis(Action::MoveForward, Action::MoveBack);
+        if movement.length() > 1.0 {
+            movement.normalize()
+        } else {
+            movement
+        }
+    }
+}
+
+/// How moving the mouse turns the view.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MouseLook {
+    /// radians turned for each pixel the mouse moves
+    pub sensitivity: f32,
+    /// whether moving the mouse up looks down, like a flight stick
+    pub invert_y: bool,
+}
+
+impl Default for MouseLook {
+    fn default() -> Self {
+        MouseLook {
+            sensitivity: 0.0025,
+            invert_y: false,
+        }
+    }
+}
+
+/// How a gamepad's sticks move the player.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sticks {
+    /// How far a stick has to be pushed before it does anything, from 0 to 1. Sticks never
+    /// quite come back to the middle, so without this the player would drift.
+    pub dead_zone: f32,
+    /// radians a second the right stick turns at when it's pushed all the way
+    pub turn_speed: f32,
+    /// What power of how far the right stick's pushed it turns at, so above 1 leaves room for
+    /// fine aiming near the middle and still turns quickly at the edge. 1 is straight.
+    pub curve: f32,
+    /// whether pushing the right stick up looks down
+    pub invert_y: bool,
+}
+
+impl Default for Sticks {
+    fn default() -> Self {
+        Sticks {
+            dead_zone: 0.2,
+            turn_speed: 3.0,
+            curve: 2.0,
+            invert_y: false,
+        }
+    }
+}
+
+impl Sticks {
+    /// Leaves out the dead zone in the middle, and stretches the rest out so it still goes all
+    /// the way from 0 to 1 rather than jumping as it leaves the dead zone.
+    pub fn without_dead_zone(&self, stick: Vec2) -> Vec2 {
+        let length = stick.length();
+        if length <= self.dead_zone {
+            return Vec2::default();
+        }
+        let length_out = ((length - self.dead_zone) / (1.0 - self.dead_zone)).min(1.0);
+        stick.normalize() * length_out
+    }
+
+    /// how fast the right stick turns (x) and looks up (y), in radians a second
+    pub fn turning(&self, stick: Vec2) -> Vec2 {
+        let stick = self.without_dead_zone(stick);
+        let curved = |value: f32| value.signum() * value.abs().powf(self.curve) * self.turn_speed;
+        let up = if self.invert_y { -1.0 } else { 1.0 };
+        Vec2::new(curved(stick.x), curved(stick.y) * up)
+    }
+}
+
+/// Which keys do which actions, and how the mouse and gamepad sticks look around.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bindings {
+    /// indexed by action
+    keys: Vec<Vec<Key>>,
+    mouse: MouseLook,
+    sticks: Sticks,
+}
+
+impl Default for Bindings {
+    fn default() -> Self {
+        Bindings {
+            keys: Action::ALL
+                .iter()
+                .map(|action| action.default_keys().to_vec())
+                .collect(),
+            mouse: MouseLook::default(),
+            sticks: Sticks::default(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct BindingsFile {
+    #[serde(default)]
+    mouse: MouseFile,
+    #[serde(default)]
+    gamepad: GamepadFile,
+    #[serde(flatten)]
+    actions: BTreeMap<String, Vec<String>>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct MouseFile {
+    sensitivity: Option<f32>,
+    invert_y: Option<bool>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct GamepadFile {
+    dead_zone: Option<f32>,
+    turn_speed: Option<f32>,
+    curve: Option<f32>,
+    invert_y: Option<bool>,
+}
+
+fn invalid(setting: &'static str, message: &'static str) -> BindingsError {
+    BindingsError::Invalid { setting, message }
+}
+
+#[derive(Debug)]
+pub enum BindingsError {
+    Io(io::Error),
+    /// not valid TOML, or not a table of lists of key names
+    Toml(toml::de::Error),
+    UnknownAction(String),
+    UnknownKey {
+        action: Action,
+        key: String,
+    },
+    /// a setting that's the right type but doesn't make sense, like `mouse.sensitivity = 0`
+    Invalid {
+        setting: &'static str,
+        message: &'static str,
+    },
+}
+
+impl fmt::Display for BindingsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BindingsError::Io(e) => write!(f, "{e}"),
+            BindingsError::Toml(e) => write!(f, "{e}"),
+            BindingsError::UnknownAction(name) => write!(f, "there's no action called `{name}`"),
+            BindingsError::UnknownKey { action, key } => {
+                write!(f, "{action}: there's no key called `{key}`")
+            }
+            BindingsError::Invalid { setting, message } => write!(f, "{setting}: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for BindingsError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            BindingsError::Io(e) => Some(e),
+            BindingsError::Toml(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl Bindings {
+    /// the keys that do `action`, which might be none
+    pub fn keys(&self, action: Action) -> &[Key] {
+        &self.keys[action as usize]
+    }
+
+    /// Swaps out whatever keys did `action` for `keys`.
+    pub fn bind(&mut self, action: Action, keys: Vec<Key>) {
+        self.keys[action as usize] = keys;
+    }
+
+    pub fn mouse_look(&self) -> MouseLook {
+        self.mouse
+    }
+
+    pub fn set_mouse_look(&mut self, mouse: MouseLook) {
+        self.mouse = mouse;
+    }
+
+    pub fn sticks(&self) -> Sticks {
+        self.sticks
+    }
+
+    pub fn set_sticks(&mut self, sticks: Sticks) {
+        self.sticks = sticks;
+    }
+
+    /// The defaults, with whatever a bindings file changes.
+    pub fn from_toml(text: &str) -> Result<Self, BindingsError> {
+        let file: BindingsFile = toml::from_str(text).map_err(BindingsError::Toml)?;
+        let mut bindings = Bindings::default();
+        let above_zero = |value: f32| value > 0.0 && value.is_finite();
+        if let Some(sensitivity) = file.mouse.sensitivity {
+            if !above_zero(sensitivity) {
+                return Err(invalid("mouse.sensitivity", "has to be above 0"));
+            }
+            bindings.mouse.sensitivity = sensitivity;
+        }
+        if let Some(invert_y) = file.mouse.invert_y {
+            bindings.mouse.invert_y = invert_y;
+        }
+
+        let gamepad = file.gamepad;
+        if let Some(dead_zone) = gamepad.dead_zone {
+            if !(0.0..1.0).contains(&dead_zone) {
+                return Err(invalid("gamepad.dead_zone", "has to be from 0 up to 1"));
+            }
+            bindings.sticks.dead_zone = dead_zone;
+        }
+        if let Some(turn_speed) = gamepad.turn_speed {
+            if !above_zero(turn_speed) {
+                return Err(invalid("gamepad.turn_speed", "has to be above 0"));
+            }
+            bindings.sticks.turn_speed = turn_speed;
+        }
+        if let Some(curve) = gamepad.curve {
+            if !above_zero(curve) {
+                return Err(invalid("gamepad.curve", "has to be above 0"));
+            }
+            bindings.sticks.curve = curve;
+        }
+        if let Some(invert_y) = gamepad.invert_y {
+            bindings.sticks.invert_y = invert_y;
+        }
+        for (name, key_names) in file.actions {
+            let action = Action::from_name(&name).ok_or(BindingsError::UnknownAction(name))?;
+            let keys = key_names
+                .into_iter()
+                .map(|key| Key::from_name(&key).ok_or(BindingsError::UnknownKey { action, key }))
+                .collect::<Result<_, _>>()?;
+            bindings.bind(action, keys);
+        }
+        Ok(bindings)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, BindingsError> {
+        let text = std::fs::read_to_string(path).map_err(BindingsError::Io)?;
+        Self::from_toml(&text)
+    }
+
+    /// Works out what the player's asking for from the keys that are down and how far the mouse
+    /// moved since last time, in pixels with +y down the screen. Frontends that aren't using the
+    /// mouse to look around (because it isn't grabbed, say) pass a motion of zero.
+    pub fn input(&self, held: &[Key], mouse_motion: Vec2) -> Input {
+        let mut input = Input::default();
+        for action in Action::ALL {
+            if self.keys(action).iter().any(|key| held.contains(key)) {
+                input.press(action);
+            }
+        }
+        // moving the mouse up the screen looks up, unless that's inverted
+        let up = if self.mouse.invert_y { 1.0 } else { -1.0 };
+        input.set_look(Vec2::new(mouse_motion.x, mouse_motion.y * up) * self.mouse.sensitivity);
+        input
+    }
+
+    /// Adds the gamepads' sticks to what the player's asking for: the left one walks and the right
+    /// one turns and looks. Each pad gives both its sticks, from -1 to 1 with +y up. With more
+    /// than one plugged in, each stick goes by whichever pad's is pushed furthest, so a few of
+    /// them resting a little off centre can't add up to more than the dead zone.
+    pub fn add_sticks(&self, input: &mut Input, pads: impl IntoIterator<Item = (Vec2, Vec2)>) {
+        let furthest = |a: Vec2, b: Vec2| if b.length() > a.length() { b } else { a };
+        let (left, right) = pads.into_iter().fold(
+            (Vec2::default(), Vec2::default()),
+            |(left, right), (pad_left, pad_right)| {
+                (furthest(left, pad_left), furthest(right, pad_right))
+            },
+        );
+        input.set_movement(self.sticks.without_dead_zone(left));
+        input.set_turning(self.sticks.turning(right));
+    }
+}
+
+/// The bindings in [`BINDINGS_FILE`], or the defaults if there isn't one.
+pub fn load_bindings() -> Result<Bindings, BindingsError> {
+    match Bindings::load(BINDINGS_FILE) {
+        Err(BindingsError::Io(e)) if e.kind() == io::ErrorKind::NotFound => Ok(Bindings::default()),
+        result => result,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_names_go_both_ways() {
+        for action in Action::ALL {
+            assert_eq!(Action::from_name(action.name()), Some(action));
+        }
+        for key in Key::all() {
+            assert_eq!(Key::from_name(key.name()), Some(key));
+        }
+        assert_eq!(Key::from_name("leftctrl"), Some(Key::LeftCtrl));
+        assert_eq!(Action::from_name("Toggle_Map"), Some(Action::ToggleMap));
+    }
+
+    #[test]
+    fn test_files_change_only_what_they_list() {
+        let bindings = Bindings::from_toml("move_forward = [\"up\", \"W\"]\nuse = []\n").unwrap();
+        assert_eq!(bindings.keys(Action::MoveForward), [Key::Up, Key::W]);
+        assert!(bindings.keys(Action::Use).is_empty());
+        assert_eq!(bindings.keys(Action::MoveBack), [Key::Down, Key::PadDown]);
+
+        // W now walks as well as looking up
+        let input = bindings.input(&[Key::W, Key::Space], Vec2::default());
+        assert!(input.is_held(Action::MoveForward));
+        assert!(input.is_held(Action::LookUp));
+        assert!(!input.is_held(Action::Use));
+        assert!(!input.is_held(Action::MoveBack));
+    }
+
+    #[test]
+    fn test_diagonals_are_no_faster() {
+        let mut input = Input::default();
+        input.press(Action::MoveForward);
+        input.press(Action::StrafeLeft);
+        let movement = input.movement();
+        assert!((movement.length() - 1.0).abs() < 1e-6);
+        assert!(movement.x < 0.0 && movement.y > 0.0);
+
+        // half a stick is half speed, and opposite actions cancel out
+        let mut input = Input::default();
+        input.set_movement(Vec2::new(0.0, 0.5));
+        assert_eq!(input.movement(), Vec2::new(0.0, 0.5));
+        input.press(Action::StrafeLeft);
+        input.press(Action::StrafeRight);
+        assert_eq!(input.movement(), Vec2::new(0.0, 0.5));
+        input.press(Action::MoveForward);
+        assert_eq!(input.movement(), Vec2::new(0.0, 1.0));
+    }
+
+    #[test]
+    fn test_mouse_look() {
+        let bindings = Bindings::default();
+        let input = bindings.input(&[], Vec2::new(100.0, -40.0));
+        // right and up the screen turns right and looks up
+        assert!((input.look().x - 0.25).abs() < 1e-6);
+        assert!((input.look().y - 0.1).abs() < 1e-6);
+
+        let bindings =
+            Bindings::from_toml("[mouse]\nsensitivity = 0.01\ninvert_y = true\n").unwrap();
+        let input = bindings.input(&[], Vec2::new(100.0, -40.0));
+        assert!((input.look().x - 1.0).abs() < 1e-6);
+        assert!((input.look().y + 0.4).abs() < 1e-6);
+        // and the keys are left alone
+        assert_eq!(bindings.keys(Action::Fire), Action::Fire.default_keys());
+    }
+
+    #[test]
+    fn test_sticks() {
+        let sticks = Sticks::default();
+        // nothing in the dead zone, and a smooth ramp up from its edge
+        assert_eq!(
+            sticks.without_dead_zone(Vec2::new(0.15, -0.1)),
+            Vec2::default()
+        );
+        let just_out = sticks.without_dead_zone(Vec2::new(0.0, 0.21));
+        assert!(just_out.y > 0.0 && just_out.y < 0.02);
+        assert!((sticks.without_dead_zone(Vec2::new(0.0, -1.0)).y + 1.0).abs() < 1e-6);
+
+        // halfway out of the dead zone turns at a quarter speed, going by the curve
+        let turning = sticks.turning(Vec2::new(-0.6, 0.0));
+        assert!((turning.x + sticks.turn_speed * 0.25).abs() < 1e-5);
+        assert!((sticks.turning(Vec2::new(0.0, 1.0)).y - sticks.turn_speed).abs() < 1e-5);
+
+        let bindings = Bindings::from_toml("[gamepad]\ncurve = 1\ninvert_y = true\n").unwrap();
+        let mut input = Input::default();
+        bindings.add_sticks(&mut input, [(Vec2::new(1.0, 1.0), Vec2::new(0.0, 0.6))]);
+        assert!((input.turning().y + sticks.turn_speed * 0.5).abs() < 1e-5);
+        // pushing diagonally walks no faster than straight ahead
+        assert!((input.movement().length() - 1.0).abs() < 1e-5);
+
+        // two pads drifting inside the dead zone don't add up to walking
+        let resting = (Vec2::new(0.0, 0.15), Vec2::new(0.15, 0.0));
+        let mut input = Input::default();
+        bindings.add_sticks(&mut input, [resting, resting]);
+        assert_eq!(input.movement(), Vec2::default());
+        assert_eq!(input.turning(), Vec2::default());
+
+        // and one being used isn't pushed any further by another resting
+        let mut input = Input::default();
+        bindings.add_sticks(
+            &mut input,
+            [resting, (Vec2::new(0.0, 0.6), Vec2::default())],
+        );
+        assert!((input.movement().y - 0.5).abs() < 1e-5);
+        assert_eq!(input.turning(), Vec2::default());
+    }
+
+    #[test]
+    fn test_bad_files() {
+        let error = |text| Bindings::from_toml(text).unwrap_err().to_string();
+        assert_eq!(
+            error("jump = [\"Space\"]"),
+            "there's no action called `jump`"
+        );
+        assert_eq!(
+            error("fire = [\"Mouse4\"]"),
+            "fire: there's no key called `Mouse4`"
+        );
+        assert!(matches!(
+            Bindings::from_toml("fire = \"Space\""),
+            Err(BindingsError::Toml(_))
+        ));
+        assert_eq!(
+            error("[mouse]\nsensitivity = -1"),
+            "mouse.sensitivity: has to be above 0"
+        );
+        assert!(matches!(
+            Bindings::from_toml("[mouse]\nspeed = 1"),
+            Err(BindingsError::Toml(_))
+        ));
+        assert_eq!(
+            error("[gamepad]\ndead_zone = 1"),
+            "gamepad.dead_zone: has to be from 0 up to 1"
+        );
+    }
+
+    #[test]
+    fn test_shipped_file_is_the_defaults() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/../", "bindings.toml");
+        assert_eq!(Bindings::load(path).unwrap(), Bindings::default());
+    }
+}
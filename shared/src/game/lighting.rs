@@ -0,0 +1,156 @@
+//! how bright things get drawn, from their sector's light level and how far away they are
+//!
+//! Distance here is depth, how far in front of the camera something is, the way Doom does it.
+//! Depth is 1/w from clip space turned back round, and 1/w already gets interpolated across
+//! polygons for their textures, so every pixel can be lit for its own depth rather than one
+//! brightness going over a whole polygon. That keeps long walls getting darker along their
+//! length, and keeps the picture the same however the BSP tree cut things up.
+
+use super::colors::shade;
+
+/// How light drops off with distance, Doom style. Maps set this with a `lighting` line, and the
+/// game can swap it out afterwards with [`GameState::set_lighting`](super::GameState::set_lighting).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Lighting {
+    /// how far in front of the camera (in map units) something has to be to get as dark as
+    /// `min_light`
+    pub falloff: f32,
+    /// the darkest distance alone makes anything, as a fraction of its sector's light
+    pub min_light: f32,
+    /// how far in front of the camera something has to be to disappear into the fog entirely, no
+    /// fog if `None`
+    pub fog_distance: Option<f32>,
+    pub fog_color: (u8, u8, u8),
+}
+
+impl Lighting {
+    /// Only sector light levels, nothing from distance.
+    pub const FLAT: Lighting = Lighting {
+        falloff: f32::INFINITY,
+        min_light: 1.0,
+        fog_distance: None,
+        fog_color: (0, 0, 0),
+    };
+
+    /// How to shade something in a sector with light level `sector_light`, `distance` in front
+    /// of the camera.
+    pub fn shade(&self, sector_light: f32, distance: f32) -> Shade {
+        let dimming = (1.0 - distance / self.falloff).max(0.0);
+        let light = sector_light * (self.min_light + (1.0 - self.min_light) * dimming);
+        let fog = match self.fog_distance {
+            Some(fog_distance) => (distance / fog_distance).clamp(0.0, 1.0),
+            None => 0.0,
+        };
+        Shade {
+            light,
+            fog,
+            fog_color: self.fog_color,
+        }
+    }
+}
+
+impl Default for Lighting {
+    fn default() -> Self {
+        Lighting {
+            falloff: 32.0,
+            min_light: 0.25,
+            fog_distance: None,
+            fog_color: (0, 0, 0),
+        }
+    }
+}
+
+/// How one polygon is lit: its sector's light level, with [`Lighting`] working out how much
+/// darker each pixel of it gets from there.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Light {
+    pub level: f32,
+    pub lighting: Lighting,
+}
+
+impl Light {
+    /// leaves colours exactly as they are, for things that don't get lit, like the sky
+    pub const NONE: Light = Light {
+        level: 1.0,
+        lighting: Lighting::FLAT,
+    };
+
+    /// The shade for a pixel, from its 1/w (see [`TexturedVertex`](super::TexturedVertex)).
+    pub fn at(&self, inv_w: f32) -> Shade {
+        self.lighting.shade(self.level, 1.0 / inv_w)
+    }
+
+    /// One shade for a whole polygon, for backends that can't light per pixel: whatever it
+    /// would be at the corners' average depth.
+    pub fn flat(&self, inv_ws: impl ExactSizeIterator<Item = f32>) -> Shade {
+        let corners = inv_ws.len().max(1) as f32;
+        let depth = inv_ws.map(|inv_w| 1.0 / inv_w).sum::<f32>() / corners;
+        self.lighting.shade(self.level, depth)
+    }
+}
+
+/// What lighting does to one pixel: darken it, then blend it towards the fog colour.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Shade {
+    pub light: f32,
+    /// 0 for no fog at all, 1 for nothing but fog
+    pub fog: f32,
+    pub fog_color: (u8, u8, u8),
+}
+
+impl Shade {
+    /// leaves colours exactly as they are
+    pub const NONE: Shade = Shade {
+        light: 1.0,
+        fog: 0.0,
+        fog_color: (0, 0, 0),
+    };
+
+    pub fn apply(&self, color: (u8, u8, u8, u8)) -> (u8, u8, u8, u8) {
+        let (r, g, b) = shade((color.0, color.1, color.2), self.light);
+        let blend = |c: u8, fog: u8| (c as f32 + (fog as f32 - c as f32) * self.fog).round() as u8;
+        let (fr, fg, fb) = self.fog_color;
+        (blend(r, fr), blend(g, fg), blend(b, fb), color.3)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_light_diminishes() {
+        let lighting = Lighting::default();
+        assert_eq!(lighting.shade(1.0, 0.0).light, 1.0);
+        assert!(lighting.shade(1.0, 10.0).light < 1.0);
+        assert_eq!(lighting.shade(0.5, 100.0).light, 0.5 * lighting.min_light);
+        assert_eq!(Lighting::FLAT.shade(0.5, 100.0).light, 0.5);
+
+        let foggy = Lighting {
+            fog_distance: Some(10.0),
+            fog_color: (200, 200, 200),
+            ..Lighting::FLAT
+        };
+        assert_eq!(
+            foggy.shade(1.0, 5.0).apply((0, 100, 200, 255)),
+            (100, 150, 200, 255)
+        );
+        assert_eq!(
+            foggy.shade(1.0, 50.0).apply((0, 0, 0, 255)),
+            (200, 200, 200, 255)
+        );
+        assert_eq!(Shade::NONE.apply((1, 2, 3, 4)), (1, 2, 3, 4));
+    }
+
+    #[test]
+    fn test_light_goes_by_depth() {
+        let light = Light {
+            level: 0.5,
+            lighting: Lighting::default(),
+        };
+        assert_eq!(light.at(1.0 / 8.0), light.lighting.shade(0.5, 8.0));
+        let flat = light.flat([1.0 / 4.0, 1.0 / 16.0].into_iter());
+        assert_eq!(flat, light.lighting.shade(0.5, 10.0));
+        assert_eq!(Light::NONE.at(0.01).apply((1, 2, 3, 4)), (1, 2, 3, 4));
+    }
+}
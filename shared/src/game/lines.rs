@@ -128,6 +128,18 @@ impl LineSegment {
     pub fn midpoint(&self) -> Vec2 {
         (self.start + self.end) * 0.5
     }
+
+    /// The point on the segment nearest to `point`, which is one of the ends if `point` is off
+    /// past either of them.
+    pub fn closest_point(&self, point: Vec2) -> Vec2 {
+        let along = self.end - self.start;
+        let length_squared = along.dot(&along);
+        if length_squared == 0.0 {
+            return self.start;
+        }
+        let t = ((point - self.start).dot(&along) / length_squared).clamp(0.0, 1.0);
+        self.start + along * t
+    }
 }
 
 /// Finds the intersection point of two lines. Note that this is lines, NOT line segments, so the
@@ -146,31 +158,32 @@ fn intersection_point(a: &LineSegment, b: &LineSegment) -> Option<Vec2> {
     Some(a.start + (a.end - a.start) * calced)
 }
 
-/// Checks if two line segments intersect. Returns true if they do, false otherwise.
-pub fn do_lines_intersect(a: &LineSegment, b: &LineSegment) -> bool {
-    let p = if let Some(p) = intersection_point(a, b) {
-        p
-    } else {
-        return false;
-    };
+/// Where two line segments cross, if they do.
+pub fn crossing_point(a: &LineSegment, b: &LineSegment) -> Option<Vec2> {
+    let p = intersection_point(a, b)?;
 
     // Check x bounds for both segments
     if p.x < a.start.x.min(a.end.x) || p.x > a.start.x.max(a.end.x) {
-        return false;
+        return None;
     }
     if p.x < b.start.x.min(b.end.x) || p.x > b.start.x.max(b.end.x) {
-        return false;
+        return None;
     }
 
     // Check y bounds for both segments
     if p.y < a.start.y.min(a.end.y) || p.y > a.start.y.max(a.end.y) {
-        return false;
+        return None;
     }
     if p.y < b.start.y.min(b.end.y) || p.y > b.start.y.max(b.end.y) {
-        return false;
+        return None;
     }
 
-    true
+    Some(p)
+}
+
+/// Checks if two line segments intersect. Returns true if they do, false otherwise.
+pub fn do_lines_intersect(a: &LineSegment, b: &LineSegment) -> bool {
+    crossing_point(a, b).is_some()
 }
 
 // Assuming that a interpreted as an inf line intersects with the line segment b,
@@ -245,6 +258,14 @@ mod tests {
         let intersection = intersection_point(&line1, &line2);
         assert!(intersection.is_none());
     }
+    #[test]
+    fn test_closest_point() {
+        let line = LineSegment::new(Vec2::new(0.0, 0.0), Vec2::new(4.0, 0.0));
+        assert_eq!(line.closest_point(Vec2::new(1.0, 3.0)), Vec2::new(1.0, 0.0));
+        assert_eq!(line.closest_point(Vec2::new(-2.0, 1.0)), Vec2::new(0.0, 0.0));
+        assert_eq!(line.closest_point(Vec2::new(9.0, -1.0)), Vec2::new(4.0, 0.0));
+    }
+
     #[test]
     fn test_do_lines_intersect() {
         let line1 = LineSegment::from((0.0, 0.0, 2.0, 2.0));
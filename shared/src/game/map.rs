@@ -0,0 +1,327 @@
+//! a loaded level, before any of the BSP stuff gets built out of it
+
+use std::path::PathBuf;
+
+use super::lighting::Lighting;
+use super::lines::LineSegment;
+use super::sectors::eye_height_fits;
+use super::vecs::Vec2;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Map {
+    /// where the camera starts
+    pub spawn: Spawn,
+    /// other places the camera can be sent to, by name
+    pub named_spawns: Vec<(String, Spawn)>,
+    /// every wall in the level, in the order they appeared in the file
+    pub walls: Vec<Wall>,
+    /// closed areas with their own floor and ceiling. Anywhere that isn't inside one of these
+    /// is treated like [`OUTSIDE`].
+    pub sectors: Vec<Sector>,
+    /// textures walls can use, by index
+    pub textures: Vec<TextureDef>,
+    /// named colours from `palette` lines, already applied to whatever used them
+    pub palette: Vec<(String, (u8, u8, u8))>,
+    /// how light falls off with distance, from a `lighting` line
+    pub lighting: Lighting,
+    /// things placed in the level, kept for whatever wants them
+    pub entities: Vec<Entity>,
+    pub metadata: MapMetadata,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Wall {
+    pub line: LineSegment,
+    /// index into [`Map::textures`], flat coloured if there isn't one
+    pub texture: Option<usize>,
+    /// how far to slide the texture along the wall, in texture repeats
+    pub u_offset: f32,
+    /// how far to slide the texture down the wall, in texture repeats
+    pub v_offset: f32,
+    /// flat colour when there's no texture, picked from the wall's index if there isn't one
+    pub color: Option<(u8, u8, u8)>,
+}
+
+impl Wall {
+    /// an untextured wall with the default colour
+    pub fn new(line: LineSegment) -> Self {
+        Wall {
+            line,
+            texture: None,
+            u_offset: 0.0,
+            v_offset: 0.0,
+            color: None,
+        }
+    }
+}
+
+/// Something placed in the level that isn't part of its shape. Nothing in the engine does
+/// anything with these yet, they just come along with the map.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entity {
+    /// what it is, named however the map likes
+    pub kind: String,
+    pub position: Vec2,
+    /// which way it faces, in radians like [`Spawn::yaw`]
+    pub yaw: f32,
+}
+
+/// Somewhere for the camera to start out.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Spawn {
+    /// in map coordinates
+    pub position: Vec2,
+    /// which way the camera faces, in radians (0 looks along +y, a quarter turn along +x)
+    pub yaw: f32,
+    /// how far above the floor the camera is, the usual eye height if `None`
+    pub height: Option<f32>,
+}
+
+impl Spawn {
+    /// facing along +y at the usual eye height
+    pub fn new(position: Vec2) -> Self {
+        Spawn {
+            position,
+            yaw: 0.0,
+            height: None,
+        }
+    }
+}
+
+/// A texture as the map file names it. Loading the actual image is up to
+/// [`TextureRegistry`](super::textures::TextureRegistry).
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextureDef {
+    pub name: String,
+    /// relative to the map file
+    pub path: PathBuf,
+}
+
+pub const DEFAULT_FLOOR: f32 = -0.75;
+pub const DEFAULT_CEILING: f32 = 1.0;
+pub const DEFAULT_FLOOR_COLOR: (u8, u8, u8) = (100, 100, 100);
+pub const DEFAULT_CEILING_COLOR: (u8, u8, u8) = (60, 60, 60);
+
+/// Everywhere that isn't in a sector: default heights, and open to the sky.
+pub static OUTSIDE: Sector = Sector {
+    floor: DEFAULT_FLOOR,
+    ceiling: DEFAULT_CEILING,
+    floor_color: DEFAULT_FLOOR_COLOR,
+    ceiling_color: None,
+    light: 1.0,
+    walls: Vec::new(),
+};
+
+/// A closed polygon of walls with its own floor and ceiling height.
+///
+/// Walls between two sectors (or a sector and the outside) get drawn as the steps between their
+/// floors and ceilings instead of as a solid wall.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sector {
+    pub floor: f32,
+    pub ceiling: f32,
+    pub floor_color: (u8, u8, u8),
+    /// `None` leaves the ceiling open, showing the sky
+    pub ceiling_color: Option<(u8, u8, u8)>,
+    /// how bright everything in the sector is before distance gets a say, 0 to 1
+    pub light: f32,
+    /// indices into [`Map::walls`] making up the outline
+    pub walls: Vec<usize>,
+}
+
+impl Default for Sector {
+    fn default() -> Self {
+        Sector {
+            floor: DEFAULT_FLOOR,
+            ceiling: DEFAULT_CEILING,
+            floor_color: DEFAULT_FLOOR_COLOR,
+            ceiling_color: Some(DEFAULT_CEILING_COLOR),
+            light: 1.0,
+            walls: Vec::new(),
+        }
+    }
+}
+
+/// Stuff about a map that doesn't change how it plays.
+#[derive(Debug, Clone, Default)]
+pub struct MapMetadata {
+    /// from a `name` line in the file
+    pub name: Option<String>,
+    /// from an `author` line in the file
+    pub author: Option<String>,
+    /// the file the map was loaded from, if it came from one
+    pub source: Option<PathBuf>,
+    /// where things were in the file, if it was a text map
+    pub lines: Option<SourceLines>,
+}
+
+// `lines` is left out, since it's only where things happened to be in the file. The same map
+// with its lines moved about, or loaded from something other than a text map, is still equal.
+impl PartialEq for MapMetadata {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name && self.author == other.author && self.source == other.source
+    }
+}
+
+/// Line numbers (counting from 1) of the parts of a text map, so problems with them can be
+/// pointed out in the file.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SourceLines {
+    pub spawn: usize,
+    /// one per spawn in [`Map::named_spawns`]
+    pub named_spawns: Vec<usize>,
+    /// one per wall in [`Map::walls`], the first line that listed it
+    pub walls: Vec<usize>,
+    /// one per sector in [`Map::sectors`], the line of its `sector` directive
+    pub sectors: Vec<usize>,
+}
+
+impl Map {
+    /// the spawn with that name from [`Map::named_spawns`], if there is one
+    pub fn named_spawn(&self, name: &str) -> Option<&Spawn> {
+        self.named_spawns
+            .iter()
+            .find(|(spawn_name, _)| spawn_name == name)
+            .map(|(_, spawn)| spawn)
+    }
+
+    /// Which sector a point is in, if any. Sectors can be nested (a pillar inside a room), in
+    /// which case the smallest one containing the point wins.
+    pub fn sector_at(&self, point: Vec2) -> Option<usize> {
+        self.sectors
+            .iter()
+            .enumerate()
+            .filter(|(_, sector)| self.sector_contains(sector, point))
+            .min_by(|(_, a), (_, b)| {
+                self.sector_bounds_area(a)
+                    .total_cmp(&self.sector_bounds_area(b))
+            })
+            .map(|(i, _)| i)
+    }
+
+    /// floor and ceiling at a point, using the defaults outside of every sector
+    pub fn heights_at(&self, point: Vec2) -> (f32, f32) {
+        self.sector_heights(self.sector_at(point))
+    }
+
+    pub fn sector_heights(&self, sector: Option<usize>) -> (f32, f32) {
+        let sector = self.sector(sector);
+        (sector.floor, sector.ceiling)
+    }
+
+    /// looks up a sector, with `None` being [`OUTSIDE`]
+    pub fn sector(&self, sector: Option<usize>) -> &Sector {
+        match sector {
+            Some(i) => &self.sectors[i],
+            None => &OUTSIDE,
+        }
+    }
+
+    // even-odd test against the sector's outline
+    fn sector_contains(&self, sector: &Sector, point: Vec2) -> bool {
+        let mut inside = false;
+        for &i in &sector.walls {
+            let LineSegment { start, end } = self.walls[i].line;
+            if (start.y > point.y) != (end.y > point.y) {
+                let x = start.x + (point.y - start.y) / (end.y - start.y) * (end.x - start.x);
+                if point.x < x {
+                    inside = !inside;
+                }
+            }
+        }
+        inside
+    }
+
+    // the outline isn't guaranteed to be in order, so the bounding box stands in for the area
+    fn sector_bounds_area(&self, sector: &Sector) -> f32 {
+        let mut min = Vec2::new(f32::INFINITY, f32::INFINITY);
+        let mut max = Vec2::new(f32::NEG_INFINITY, f32::NEG_INFINITY);
+        for &i in &sector.walls {
+            let line = self.walls[i].line;
+            for p in [line.start, line.end] {
+                min = Vec2::new(min.x.min(p.x), min.y.min(p.y));
+                max = Vec2::new(max.x.max(p.x), max.y.max(p.y));
+            }
+        }
+        (max.x - min.x) * (max.y - min.y)
+    }
+
+    /// The map's name, falling back to the name of the file it came from.
+    pub fn display_name(&self) -> String {
+        if let Some(name) = &self.metadata.name {
+            return name.clone();
+        }
+        self.metadata
+            .source
+            .as_ref()
+            .and_then(|path| path.file_stem())
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "untitled".to_string())
+    }
+
+    /// Checks the map makes sense as a level, whatever it was loaded from: coordinates that are
+    /// actual numbers, floors below their ceilings, light levels from 0 to 1, indices that
+    /// point at something, and spawn heights the player could stand at without poking through
+    /// the ceiling. Says what's wrong with the first thing that doesn't.
+    pub fn validate(&self) -> Result<(), &'static str> {
+        let finite = |p: Vec2| p.x.is_finite() && p.y.is_finite();
+        let spawns = std::iter::once(&self.spawn).chain(self.named_spawns.iter().map(|(_, s)| s));
+        for spawn in spawns.clone() {
+            if !finite(spawn.position) || !spawn.yaw.is_finite() {
+                return Err("spawn point isn't a finite position");
+            }
+        }
+        let placed = |entity: &Entity| finite(entity.position) && entity.yaw.is_finite();
+        if !self.entities.iter().all(placed) {
+            return Err("entity isn't at a finite position");
+        }
+        for wall in &self.walls {
+            if !finite(wall.line.start) || !finite(wall.line.end) {
+                return Err("wall has a coordinate that isn't finite");
+            }
+            if !wall.u_offset.is_finite() || !wall.v_offset.is_finite() {
+                return Err("wall has a texture offset that isn't finite");
+            }
+            if wall.texture.is_some_and(|texture| texture >= self.textures.len()) {
+                return Err("wall uses a texture that doesn't exist");
+            }
+        }
+        for sector in &self.sectors {
+            if !sector.floor.is_finite() || !sector.ceiling.is_finite() {
+                return Err("sector height isn't finite");
+            }
+            if sector.floor >= sector.ceiling {
+                return Err("sector floor isn't below its ceiling");
+            }
+            if !(0.0..=1.0).contains(&sector.light) {
+                return Err("sector light isn't from 0 to 1");
+            }
+            if sector.walls.iter().any(|&wall| wall >= self.walls.len()) {
+                return Err("sector uses a wall that doesn't exist");
+            }
+        }
+        // falloff can be infinite, for no dimming with distance at all
+        let lighting = &self.lighting;
+        let positive = |n: f32| n > 0.0;
+        if !positive(lighting.falloff) || lighting.fog_distance.is_some_and(|fog| !positive(fog)) {
+            return Err("lighting distance isn't above 0");
+        }
+        if !(0.0..=1.0).contains(&lighting.min_light) {
+            return Err("lighting min_light isn't from 0 to 1");
+        }
+        // checked last, since finding the sector a spawn is in needs the sectors to be sound
+        for spawn in spawns {
+            let Some(height) = spawn.height else {
+                continue;
+            };
+            if !eye_height_fits(height) {
+                return Err("spawn height isn't above 0 and at most the player's height");
+            }
+            let (floor, ceiling) = self.heights_at(spawn.position);
+            if floor + height >= ceiling {
+                return Err("spawn height puts the camera through the ceiling");
+            }
+        }
+        Ok(())
+    }
+}
@@ -1,89 +1,349 @@
 mod a3d_to_2d;
+mod automap;
 mod bsp;
 mod cam;
+mod check;
 mod colls;
+mod colors;
+mod compiled;
+mod culling;
 mod draw_screen;
 mod drawing;
 mod fs;
+#[cfg(feature = "gilrs")]
+mod gamepad;
+mod input;
+mod lighting;
 mod lines;
+mod map;
+mod occlusion;
+mod offscreen;
+mod sectors;
 mod skybox;
+mod structured;
+mod svg;
+mod textures;
 mod vecs;
+mod wad;
 mod contexts;
 
-use cam::Camera;
-use colls::attempt_move;
-use lines::LineSegment;
-use vecs::Vec3;
-use bsp::BSPNode;
+use std::ops::ControlFlow;
 
-pub use drawing::Drawer;
-pub use contexts::{GraphicsContext, KeysDown};
+use automap::Automap;
+use colls::walk;
+use sectors::WallSides;
+
+pub use bsp::{BSPTree, BuildStats, Leaf, PartitionChooser, Seg, Visit};
+pub use cam::Camera;
+pub use check::{Place, Problem, ProblemKind, Severity, check_map};
+pub use drawing::{Drawer, LitVertex, TexturedVertex};
+pub use colors::{DEFAULT_PALETTE, wall_color};
+pub use compiled::{
+    CompiledMap, CompiledMapError, FORMAT_VERSION, LoadedMap, MAGIC, level_from_args,
+    load_any_map_from_path,
+};
+pub use contexts::GraphicsContext;
+pub use fs::{MapError, SyntaxError, SyntaxErrorKind, builtin_map, load_map};
+#[cfg(feature = "gilrs")]
+pub use gamepad::{pad_button, pad_sticks};
+pub use input::{
+    Action, BINDINGS_FILE, Bindings, BindingsError, Input, Key, MouseLook, Sticks, load_bindings,
+};
+pub use lighting::{Light, Lighting, Shade};
+pub use lines::LineSegment;
+pub use map::{
+    DEFAULT_CEILING, DEFAULT_FLOOR, Entity, Map, MapMetadata, Sector, SourceLines, Spawn,
+    TextureDef, Wall,
+};
+pub use offscreen::FrameBuffer;
+pub use structured::{
+    STRUCTURED_FORMAT_VERSION, StructuredMapError, load_json_map, load_toml_map, map_to_json,
+    map_to_toml,
+};
+pub use svg::map_to_svg;
+pub use textures::{Texture, TextureError, TextureErrorKind, TextureRegistry};
+pub use vecs::{Vec2, Vec3};
+pub use wad::{
+    WAD_UNITS, WadError, WadMap, WadWarning, is_wad, load_wad_map, wad_map_names,
+};
 
 pub struct GameState {
     cam: Camera,
-    bsp: BSPNode,
+    bsp: BSPTree,
+    bsp_stats: BuildStats,
+    map: Map,
+    // what's on either side of each of the map's walls
+    sides: Vec<WallSides>,
+    textures: TextureRegistry,
+    lighting: Lighting,
+    player_radius: f32,
+    // how far above the floor the camera sits, which spawns can change
+    eye_height: f32,
+    automap: Automap,
+}
+impl Default for GameState {
+    fn default() -> Self {
+        Self::new()
+    }
 }
+
 impl GameState {
+    /// Loads the map named by the first command line argument (or the built in one), see
+    /// [`level_from_args`]. Anything a WAD import had to leave out goes unmentioned.
+    ///
+    /// Panics with every problem in the map if it can't be loaded.
     pub fn new() -> Self {
-
-        let (floor_plan, cam_pos) = fs::segs_from_file();
-        let camera3d: vecs::Vec3 = Vec3 {
-            x: cam_pos.x,
-            y: 0.0,
-            z: cam_pos.y,
-        };
+        match level_from_args() {
+            Ok(loaded) => Self::from_compiled(loaded.into_level(&PartitionChooser::default())),
+            Err(e) => panic!("{e}"),
+        }
+    }
+    /// Builds the game around an already loaded map. Where the map comes from (a file, the
+    /// command line, something generated) is up to the frontend.
+    pub fn from_map(map: Map) -> Self {
+        Self::from_map_with_chooser(map, &PartitionChooser::default())
+    }
+    /// Same as [`from_map`](Self::from_map), with `chooser` deciding how the BSP tree gets built.
+    pub fn from_map_with_chooser(map: Map, chooser: &PartitionChooser) -> Self {
+        Self::from_compiled(CompiledMap::compile(map, chooser))
+    }
+    /// Builds the game around a map whose BSP tree is already built, from
+    /// [`LoadedMap::into_level`] or [`CompiledMap::compile`].
+    pub fn from_compiled(level: CompiledMap) -> Self {
+        let CompiledMap {
+            map,
+            bsp,
+            stats: bsp_stats,
+        } = level;
         let fov: f32 = 80.0_f32.to_radians();
 
-        let floor_plan_segs = floor_plan
-            .iter()
-            .map(|(s, e)| LineSegment { start: *s, end: *e })
-            .collect::<Vec<LineSegment>>();
-
-        let bsp = BSPNode::new(floor_plan_segs);
+        let sides = sectors::wall_sides(&map);
+        let lighting = map.lighting;
 
-        GameState {
+        let spawn = map.spawn;
+        let automap = Automap::new(map.walls.len());
+        let mut game_state = GameState {
             // Initialize game state here
             cam: Camera {
-                pos: camera3d,
+                pos: Vec3::default(),
                 fov,
                 yaw: 0.0,
                 pitch: 0.0,
                 near: 0.1,
+                aspect: 4.0 / 3.0,
             },
             bsp,
+            bsp_stats,
+            map,
+            sides,
+            textures: TextureRegistry::default(),
+            lighting,
+            player_radius: colls::DEFAULT_PLAYER_RADIUS,
+            eye_height: sectors::EYE_HEIGHT,
+            automap,
+        };
+        game_state.spawn_at(spawn);
+        game_state
+    }
+    /// Loads the textures the map asks for. Until this is called (or if it fails) every wall is
+    /// drawn flat coloured.
+    pub fn load_textures(&mut self) -> Result<(), TextureError> {
+        self.textures = TextureRegistry::load_for_map(&self.map)?;
+        Ok(())
+    }
+    /// Swaps in a set of textures that didn't necessarily come from the map's files. Ids line up
+    /// with the map's `texture` declarations.
+    pub fn set_textures(&mut self, textures: TextureRegistry) {
+        self.textures = textures;
+    }
+    /// Puts the camera at a point on the map, facing `yaw` radians (0 looks along +y, a
+    /// quarter turn looks along +x), standing on whatever floor is there.
+    pub fn place_camera(&mut self, pos: Vec2, yaw: f32) {
+        let (floor, _) = self.map.heights_at(pos);
+        self.cam.pos = Vec3 {
+            x: pos.x,
+            y: floor + self.eye_height,
+            z: pos.y,
+        };
+        self.cam.yaw = yaw;
+    }
+    /// Sends the camera back to where the map starts, looking straight ahead.
+    pub fn respawn(&mut self) {
+        self.spawn_at(self.map.spawn);
+    }
+    /// Sends the camera to one of the map's [named spawns](Map::named_spawns), looking straight
+    /// ahead. Returns false (and leaves the camera be) if there isn't one called `name`.
+    pub fn teleport(&mut self, name: &str) -> bool {
+        match self.map.named_spawn(name) {
+            Some(&spawn) => {
+                self.spawn_at(spawn);
+                true
+            }
+            None => false,
         }
     }
+    fn spawn_at(&mut self, spawn: Spawn) {
+        self.eye_height = spawn.height.unwrap_or(sectors::EYE_HEIGHT);
+        self.cam.pitch = 0.0;
+        self.place_camera(spawn.position, spawn.yaw);
+    }
+
+    /// The map's walls that could be on screen right now, as indices into [`Map::walls`],
+    /// nearest first. Walls that aren't in front of the camera are left out, but walls hidden
+    /// behind other walls aren't.
+    pub fn visible_walls(&self) -> Vec<usize> {
+        let mut walls = Vec::new();
+        // walls split into several segs turn up more than once
+        let mut listed = vec![false; self.map.walls.len()];
+        let _ = self.bsp.for_each_front_to_back(&self.cam, |visit| {
+            if let Visit::Seg(seg) = visit
+                && !std::mem::replace(&mut listed[seg.wall], true)
+            {
+                walls.push(seg.wall);
+            }
+            ControlFlow::Continue(())
+        });
+        walls
+    }
+    pub fn camera(&self) -> &Camera {
+        &self.cam
+    }
+    /// The map's walls, split up for drawing in order.
+    pub fn bsp(&self) -> &BSPTree {
+        &self.bsp
+    }
+    /// How the map's BSP tree came out when it was built.
+    pub fn bsp_stats(&self) -> BuildStats {
+        self.bsp_stats
+    }
+    /// How close the player can get to a wall. It gets clamped to between 0.01 and 4 map units,
+    /// and NaN leaves it as it was.
+    pub fn set_player_radius(&mut self, radius: f32) {
+        if radius.is_nan() {
+            return;
+        }
+        let (min, max) = colls::PLAYER_RADIUS_RANGE;
+        self.player_radius = radius.clamp(min, max);
+    }
+    pub fn lighting(&self) -> Lighting {
+        self.lighting
+    }
+    /// Overrides how light falls off with distance, which starts out as whatever the map says.
+    /// [`Lighting::FLAT`] turns it off.
+    pub fn set_lighting(&mut self, lighting: Lighting) {
+        self.lighting = lighting;
+    }
+    /// Whether [`draw_screen`](Self::draw_screen) is drawing the top-down map instead of the
+    /// level.
+    pub fn automap_open(&self) -> bool {
+        self.automap.is_open()
+    }
+    /// Opens or closes the top-down map. It only shows walls that have been drawn on screen.
+    pub fn toggle_automap(&mut self) {
+        self.automap.toggle();
+    }
     pub fn draw_screen<T: Drawer>(&mut self, graphics_context: &mut GraphicsContext<'_, T>) {
         draw_screen::draw_screen(self, graphics_context);
     }
-    pub fn keys(&mut self, delta: f32, keys_down: contexts::KeysDown) {
-        let forward = self.cam.forward_vector();
-
-        let flattened = {
-            let mut flat = forward.clone();
-            flat.y = 0.0;
-            flat
+    /// Draws a frame into memory rather than onto a window.
+    pub fn render_offscreen(&mut self, width: u32, height: u32) -> FrameBuffer {
+        let mut frame = FrameBuffer::new(width, height);
+        let mut graphics_context = GraphicsContext {
+            width,
+            height,
+            drawer: &mut frame,
         };
-        
-        if keys_down.up {
-            attempt_move(self, delta, &flattened);
-        }
-        if keys_down.down {
-            self.cam.pos = self.cam.pos - flattened * 3.0 * delta;
+        self.draw_screen(&mut graphics_context);
+        frame
+    }
+    /// Moves the game on by `delta` seconds, doing whatever `input` asks for.
+    pub fn update(&mut self, delta: f32, input: &Input) {
+        // the player stands still while the map's up, and moving moves the map instead
+        if self.automap.update_toggle(input) {
+            self.automap.update(delta, input);
+            return;
         }
-        if keys_down.left {
+
+        walk(self, input.movement(), delta);
+        if input.is_held(Action::TurnLeft) {
             self.cam.yaw -= 1.0 * delta;
         }
-        if keys_down.right {
+        if input.is_held(Action::TurnRight) {
             self.cam.yaw += 1.0 * delta;
         }
-        if keys_down.w {
-            self.cam.pitch -= 0.01;
+        if input.is_held(Action::LookUp) {
+            self.cam.pitch += 1.0 * delta;
         }
-        if keys_down.s {
-            self.cam.pitch += 0.01;
+        if input.is_held(Action::LookDown) {
+            self.cam.pitch -= 1.0 * delta;
         }
+        let look = input.look() + input.turning() * delta;
+        self.cam.yaw += look.x;
+        self.cam.pitch = (self.cam.pitch + look.y).clamp(-cam::MAX_PITCH, cam::MAX_PITCH);
 
+        // stand on whatever floor we ended up over
+        let (floor, _) = self.map.heights_at(self.cam.pos.into());
+        self.cam.pos.y = floor + self.eye_height;
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a room with a raised platform in the middle of it, and a spawn on top of the platform
+    const PLATFORM: &str = "
+0 0
+sector -0.75 1.0
+0 0 10 0
+10 0 10 10
+10 10 0 10
+0 10 0 0
+sector -0.25 1.0
+4 4 6 4
+6 4 6 6
+6 6 4 6
+4 6 4 4
+spawn top 5 5 yaw=90 height=0.5
+";
+
+    #[test]
+    fn test_spawns_stand_on_the_floor() {
+        let mut game_state = GameState::from_map(fs::load_map(PLATFORM).unwrap());
+        assert_eq!(game_state.camera().pos.y, -0.75 + sectors::EYE_HEIGHT);
+
+        assert!(game_state.teleport("top"));
+        let camera = game_state.camera();
+        assert_eq!(
+            (camera.pos.x, camera.pos.y, camera.pos.z),
+            (5.0, -0.25 + 0.5, 5.0)
+        );
+        assert!((camera.yaw - std::f32::consts::FRAC_PI_2).abs() < 1e-6);
+
+        assert!(!game_state.teleport("bottom"));
+        assert_eq!(game_state.camera().pos.x, 5.0);
+
+        game_state.respawn();
+        let camera = game_state.camera();
+        assert_eq!(
+            (camera.pos.x, camera.pos.y, camera.yaw),
+            (0.0, -0.75 + sectors::EYE_HEIGHT, 0.0)
+        );
+    }
+
+    #[test]
+    fn test_looking_up_stops_short_of_straight_up() {
+        let mut game_state = GameState::from_map(builtin_map());
+        let mut input = Input::default();
+        input.set_look(Vec2::new(0.5, 10.0));
+        game_state.update(0.1, &input);
+        assert_eq!(game_state.camera().pitch, cam::MAX_PITCH);
+        assert!((game_state.camera().yaw - 0.5).abs() < 1e-6);
+
+        // looking with keys goes by time rather than by frame
+        let mut input = Input::default();
+        input.press(Action::LookDown);
+        game_state.update(0.25, &input);
+        assert!((game_state.camera().pitch - (cam::MAX_PITCH - 0.25)).abs() < 1e-6);
+    }
+}
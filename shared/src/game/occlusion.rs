@@ -0,0 +1,382 @@
+//! keeping track of which pixels have been drawn, so things drawn nearest first only fill in what
+//! isn't covered yet
+//!
+//! Every column of the screen keeps a list of the rows already drawn in it. A polygon handed to
+//! the buffer gets cut down to whatever's still uncovered and passed on to the [`Drawer`].
+//! Neighbouring columns whose uncovered rows start and end the same way (at the polygon's own
+//! edge, or at the same row where something nearer cuts it off) go through together, as the
+//! polygon clipped to those columns and rows. Pixels count as covered the same way rasterisers
+//! decide it, by whether the polygon covers their centre, so the clipping lands between pixels.
+
+use mint::Point2;
+
+use super::drawing::{Drawer, LitVertex, TexturedVertex};
+use super::lighting::Light;
+use super::textures::Texture;
+
+pub struct OcclusionBuffer {
+    width: i32,
+    height: i32,
+    /// the rows already drawn in each column, as sorted, non-overlapping `start..end` ranges
+    covered: Vec<Vec<(i32, i32)>>,
+    /// how many columns are covered top to bottom
+    full_columns: usize,
+}
+
+impl OcclusionBuffer {
+    pub fn new(width: u32, height: u32) -> Self {
+        OcclusionBuffer {
+            width: width as i32,
+            height: height as i32,
+            covered: vec![Vec::new(); width as usize],
+            full_columns: 0,
+        }
+    }
+
+    /// whether every pixel on screen has been drawn, so nothing else can show up
+    pub fn is_full(&self) -> bool {
+        self.full_columns == self.covered.len()
+    }
+
+    /// Draws whatever part of a convex polygon isn't covered yet, then marks it as covered.
+    /// Returns whether any of it was left to draw.
+    pub fn draw_polygon<T: Drawer>(
+        &mut self,
+        drawer: &mut T,
+        points: &[Point2<f32>],
+        color: (u8, u8, u8, u8),
+    ) -> bool {
+        let pieces = self.visible_pieces(points);
+        for piece in &pieces {
+            drawer.draw_polygon(piece, color);
+        }
+        !pieces.is_empty()
+    }
+
+    /// Same as [`draw_polygon`](Self::draw_polygon), with the 1/w of the pieces' corners worked
+    /// out from the original ones, for lighting.
+    pub fn draw_lit_polygon<T: Drawer>(
+        &mut self,
+        drawer: &mut T,
+        points: &[LitVertex],
+        color: (u8, u8, u8, u8),
+        light: Light,
+    ) -> bool {
+        let untextured: Vec<TexturedVertex> = points
+            .iter()
+            .map(|p| TexturedVertex {
+                pos: p.pos,
+                u: 0.0,
+                v: 0.0,
+                inv_w: p.inv_w,
+            })
+            .collect();
+        let Some(gradients) = Gradients::new(&untextured) else {
+            return false;
+        };
+        let positions: Vec<Point2<f32>> = points.iter().map(|p| p.pos).collect();
+        let pieces = self.visible_pieces(&positions);
+        for piece in &pieces {
+            let piece: Vec<LitVertex> = piece
+                .iter()
+                .map(|p| LitVertex {
+                    pos: *p,
+                    inv_w: gradients.at(*p).inv_w,
+                })
+                .collect();
+            drawer.draw_lit_polygon(&piece, color, light);
+        }
+        !pieces.is_empty()
+    }
+
+    /// Same as [`draw_polygon`](Self::draw_polygon), with the texture coordinates and 1/w of the
+    /// pieces worked out from the original corners.
+    pub fn draw_textured_polygon<T: Drawer>(
+        &mut self,
+        drawer: &mut T,
+        points: &[TexturedVertex],
+        texture: &Texture,
+        light: Light,
+    ) -> bool {
+        let positions: Vec<Point2<f32>> = points.iter().map(|p| p.pos).collect();
+        // u/w, v/w and 1/w are linear across the screen, so they can be worked out anywhere on
+        // the polygon's plane
+        let Some(gradients) = Gradients::new(points) else {
+            return false;
+        };
+        let pieces = self.visible_pieces(&positions);
+        for piece in &pieces {
+            let piece: Vec<TexturedVertex> = piece.iter().map(|p| gradients.at(*p)).collect();
+            drawer.draw_textured_polygon(&piece, texture, light);
+        }
+        !pieces.is_empty()
+    }
+
+    /// Cuts a convex polygon down to its uncovered parts and marks them covered.
+    fn visible_pieces(&mut self, points: &[Point2<f32>]) -> Vec<Vec<Point2<f32>>> {
+        let mut pieces = Vec::new();
+        if points.len() < 3 {
+            return pieces;
+        }
+        let (x_min, x_max) = points
+            .iter()
+            .fold((f32::INFINITY, f32::NEG_INFINITY), |(lo, hi), p| {
+                (lo.min(p.x), hi.max(p.x))
+            });
+        let first = ((x_min - 0.5).ceil() as i32).max(0);
+        let last = ((x_max - 0.5).ceil() as i32).min(self.width);
+
+        // runs of columns with the same span visible, and the column each one started at
+        let mut runs: Vec<(Span, i32)> = Vec::new();
+        for column in first..last {
+            let spans: Vec<Span> = match self.rows_at(points, column) {
+                Some(rows) => {
+                    let visible = subtract(rows, &self.covered[column as usize]);
+                    self.cover(column, rows);
+                    visible
+                        .into_iter()
+                        .map(|(top, bottom)| Span {
+                            top: (top != rows.0).then_some(top),
+                            bottom: (bottom != rows.1).then_some(bottom),
+                        })
+                        .collect()
+                }
+                None => Vec::new(),
+            };
+            // runs that don't carry on into this column end at it
+            runs.retain(|&(span, start)| {
+                let carries_on = spans.contains(&span);
+                if !carries_on {
+                    pieces.push(span.clip(points, start, column));
+                }
+                carries_on
+            });
+            for span in spans {
+                if !runs.iter().any(|&(running, _)| running == span) {
+                    runs.push((span, column));
+                }
+            }
+        }
+        for (span, start) in runs {
+            pieces.push(span.clip(points, start, last));
+        }
+        pieces.retain(|piece| piece.len() >= 3);
+        pieces
+    }
+
+    /// the rows of a column whose centres are inside the polygon, if there are any
+    fn rows_at(&self, points: &[Point2<f32>], column: i32) -> Option<(i32, i32)> {
+        let x = column as f32 + 0.5;
+        let mut top = f32::INFINITY;
+        let mut bottom = f32::NEG_INFINITY;
+        for (i, a) in points.iter().enumerate() {
+            let b = points[(i + 1) % points.len()];
+            if (a.x <= x) == (b.x <= x) {
+                continue;
+            }
+            let y = a.y + (b.y - a.y) * (x - a.x) / (b.x - a.x);
+            top = top.min(y);
+            bottom = bottom.max(y);
+        }
+        let start = ((top - 0.5).ceil() as i32).max(0);
+        let end = ((bottom - 0.5).ceil() as i32).min(self.height);
+        (start < end).then_some((start, end))
+    }
+
+    fn cover(&mut self, column: i32, (start, end): (i32, i32)) {
+        let ranges = &mut self.covered[column as usize];
+        let was_full = ranges.as_slice() == [(0, self.height)];
+
+        let mut merged = (start, end);
+        ranges.retain(|&(s, e)| {
+            let touching = s <= merged.1 && e >= merged.0;
+            if touching {
+                merged = (merged.0.min(s), merged.1.max(e));
+            }
+            !touching
+        });
+        let at = ranges.partition_point(|&(s, _)| s < merged.0);
+        ranges.insert(at, merged);
+
+        if !was_full && ranges.as_slice() == [(0, self.height)] {
+            self.full_columns += 1;
+        }
+    }
+}
+
+/// Where a visible part of a column starts and stops. `None` is the polygon's own edge, and
+/// anything else is the row something already drawn cuts it off at.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Span {
+    top: Option<i32>,
+    bottom: Option<i32>,
+}
+
+impl Span {
+    /// the part of a convex polygon this covers, across the columns from `start` up to `end`
+    fn clip(self, points: &[Point2<f32>], start: i32, end: i32) -> Vec<Point2<f32>> {
+        let mut piece = clip_columns(points, start, end);
+        if let Some(top) = self.top {
+            piece = clip_y(&piece, top as f32, 1.0);
+        }
+        if let Some(bottom) = self.bottom {
+            piece = clip_y(&piece, bottom as f32, -1.0);
+        }
+        piece
+    }
+}
+
+/// the parts of `rows` that aren't in any of `covered`
+fn subtract((start, end): (i32, i32), covered: &[(i32, i32)]) -> Vec<(i32, i32)> {
+    let mut visible = Vec::new();
+    let mut from = start;
+    for &(s, e) in covered {
+        if e <= from {
+            continue;
+        }
+        if s >= end {
+            break;
+        }
+        if s > from {
+            visible.push((from, s));
+        }
+        from = from.max(e);
+    }
+    if from < end {
+        visible.push((from, end));
+    }
+    visible
+}
+
+/// the part of a convex polygon between the left edges of two columns
+fn clip_columns(points: &[Point2<f32>], start: i32, end: i32) -> Vec<Point2<f32>> {
+    let clipped = clip_x(points, start as f32, 1.0);
+    clip_x(&clipped, end as f32, -1.0)
+}
+
+/// keeps the part of a polygon where `(x - edge) * keep` is positive
+fn clip_x(points: &[Point2<f32>], edge: f32, keep: f32) -> Vec<Point2<f32>> {
+    let mut out = Vec::with_capacity(points.len() + 1);
+    for (i, a) in points.iter().enumerate() {
+        let b = points[(i + 1) % points.len()];
+        let (da, db) = ((a.x - edge) * keep, (b.x - edge) * keep);
+        if da >= 0.0 {
+            out.push(*a);
+        }
+        if (da >= 0.0) != (db >= 0.0) {
+            let t = da / (da - db);
+            out.push(Point2 {
+                x: edge,
+                y: a.y + (b.y - a.y) * t,
+            });
+        }
+    }
+    out
+}
+
+/// keeps the part of a polygon where `(y - edge) * keep` is positive
+fn clip_y(points: &[Point2<f32>], edge: f32, keep: f32) -> Vec<Point2<f32>> {
+    let flip = |p: &Point2<f32>| Point2 { x: p.y, y: p.x };
+    let flipped: Vec<Point2<f32>> = points.iter().map(flip).collect();
+    clip_x(&flipped, edge, keep).iter().map(flip).collect()
+}
+
+/// u/w, v/w and 1/w as planes over the screen
+struct Gradients {
+    planes: [[f32; 3]; 3],
+}
+
+impl Gradients {
+    /// `None` if the polygon doesn't have any area to work them out from
+    fn new(points: &[TexturedVertex]) -> Option<Self> {
+        let attrs = |p: &TexturedVertex| [p.u * p.inv_w, p.v * p.inv_w, p.inv_w];
+        let p0 = points.first()?;
+        // the corner making the biggest triangle with the first two is the least likely to be
+        // thrown off by rounding
+        let p1 = points.get(1)?;
+        let area = |p: &TexturedVertex| {
+            (p1.pos.x - p0.pos.x) * (p.pos.y - p0.pos.y)
+                - (p.pos.x - p0.pos.x) * (p1.pos.y - p0.pos.y)
+        };
+        let p2 = points[2..]
+            .iter()
+            .max_by(|a, b| area(a).abs().total_cmp(&area(b).abs()))?;
+        let det = area(p2);
+        if det.abs() < 1e-6 {
+            return None;
+        }
+
+        let (a0, a1, a2) = (attrs(p0), attrs(p1), attrs(p2));
+        let mut planes = [[0.0; 3]; 3];
+        for (i, plane) in planes.iter_mut().enumerate() {
+            let (d1, d2) = (a1[i] - a0[i], a2[i] - a0[i]);
+            let dx = (d1 * (p2.pos.y - p0.pos.y) - d2 * (p1.pos.y - p0.pos.y)) / det;
+            let dy = (d2 * (p1.pos.x - p0.pos.x) - d1 * (p2.pos.x - p0.pos.x)) / det;
+            *plane = [dx, dy, a0[i] - dx * p0.pos.x - dy * p0.pos.y];
+        }
+        Some(Gradients { planes })
+    }
+
+    fn at(&self, pos: Point2<f32>) -> TexturedVertex {
+        let [uw, vw, inv_w] = self.planes.map(|[dx, dy, c]| dx * pos.x + dy * pos.y + c);
+        TexturedVertex {
+            pos,
+            u: uw / inv_w,
+            v: vw / inv_w,
+            inv_w,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::offscreen::FrameBuffer;
+
+    fn rect(x1: f32, y1: f32, x2: f32, y2: f32) -> Vec<Point2<f32>> {
+        [(x1, y1), (x2, y1), (x2, y2), (x1, y2)]
+            .map(|(x, y)| Point2 { x, y })
+            .to_vec()
+    }
+
+    #[test]
+    fn test_only_uncovered_parts_get_drawn() {
+        let mut frame = FrameBuffer::new(8, 8);
+        let mut occlusion = OcclusionBuffer::new(8, 8);
+        occlusion.draw_polygon(&mut frame, &rect(2.0, 2.0, 6.0, 6.0), (255, 0, 0, 255));
+
+        // the nearer square wins wherever they overlap. The columns it cuts off at the same row go
+        // through as one piece
+        let behind = rect(0.0, 0.0, 4.0, 4.0);
+        let pieces = occlusion.visible_pieces(&behind);
+        assert_eq!(pieces.len(), 2);
+        for piece in &pieces {
+            frame.draw_polygon(piece, (0, 255, 0, 255));
+        }
+        assert_eq!(frame.pixel(1, 1), Some((0, 255, 0, 255)));
+        assert_eq!(frame.pixel(3, 1), Some((0, 255, 0, 255)));
+        assert_eq!(frame.pixel(3, 3), Some((255, 0, 0, 255)));
+        assert_eq!(frame.pixel(7, 7), Some((0, 0, 0, 255)));
+
+        // and something entirely behind doesn't get drawn at all
+        assert!(
+            occlusion
+                .visible_pieces(&rect(2.5, 2.5, 5.5, 5.5))
+                .is_empty()
+        );
+        assert!(!occlusion.is_full());
+        occlusion.draw_polygon(&mut frame, &rect(-1.0, -1.0, 9.0, 9.0), (0, 0, 255, 255));
+        assert!(occlusion.is_full());
+    }
+
+    #[test]
+    fn test_subtract() {
+        assert_eq!(subtract((0, 10), &[]), vec![(0, 10)]);
+        assert_eq!(
+            subtract((0, 10), &[(2, 4), (6, 8)]),
+            vec![(0, 2), (4, 6), (8, 10)]
+        );
+        assert_eq!(subtract((3, 7), &[(0, 4), (6, 12)]), vec![(4, 6)]);
+        assert!(subtract((3, 7), &[(0, 10)]).is_empty());
+    }
+}
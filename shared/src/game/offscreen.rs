@@ -0,0 +1,252 @@
+//! a [`Drawer`] that draws into memory instead of a window, for screenshots and tests
+
+use std::io::{self, Write};
+use std::path::Path;
+
+use mint::Point2;
+
+use super::drawing::{Drawer, LitVertex, TexturedVertex};
+use super::lighting::Light;
+use super::textures::Texture;
+
+/// An RGBA image that polygons get rasterised into, with nothing on screen.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FrameBuffer {
+    width: u32,
+    height: u32,
+    /// rows top to bottom, 4 bytes a pixel
+    pixels: Vec<u8>,
+}
+
+impl FrameBuffer {
+    /// A frame that's all black to start with. Panics if it's too big to fit in memory at all.
+    pub fn new(width: u32, height: u32) -> Self {
+        // in usize and checked, since a big enough frame overflows u32
+        let len = (width as usize)
+            .checked_mul(height as usize)
+            .and_then(|n| n.checked_mul(4))
+            .expect("frame is too big");
+        let mut pixels = vec![0; len];
+        for alpha in pixels.iter_mut().skip(3).step_by(4) {
+            *alpha = 255;
+        }
+        FrameBuffer {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// every pixel as RGBA, rows top to bottom
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixels
+    }
+
+    /// `None` off the edge of the frame
+    pub fn pixel(&self, x: u32, y: u32) -> Option<(u8, u8, u8, u8)> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        let offset = self.offset(x, y);
+        let p = &self.pixels[offset..offset + 4];
+        Some((p[0], p[1], p[2], p[3]))
+    }
+
+    fn offset(&self, x: u32, y: u32) -> usize {
+        (y as usize * self.width as usize + x as usize) * 4
+    }
+
+    fn put(&mut self, x: i32, y: i32, (r, g, b, a): (u8, u8, u8, u8)) {
+        let offset = self.offset(x as u32, y as u32);
+        self.pixels[offset..offset + 4].copy_from_slice(&[r, g, b, a]);
+    }
+
+    /// Writes the frame as a binary PPM. Alpha gets dropped.
+    pub fn write_ppm(&self, mut out: impl Write) -> io::Result<()> {
+        write!(out, "P6\n{} {}\n255\n", self.width, self.height)?;
+        let rgb: Vec<u8> = self
+            .pixels
+            .chunks_exact(4)
+            .flat_map(|p| [p[0], p[1], p[2]])
+            .collect();
+        out.write_all(&rgb)
+    }
+
+    pub fn write_png(&self, out: impl Write) -> io::Result<()> {
+        let mut encoder = png::Encoder::new(out, self.width, self.height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&self.pixels)?;
+        Ok(())
+    }
+
+    /// Saves the frame, as a PPM if the path ends in `.ppm` and a PNG otherwise.
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let path = path.as_ref();
+        let file = io::BufWriter::new(std::fs::File::create(path)?);
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("ppm") => self.write_ppm(file),
+            _ => self.write_png(file),
+        }
+    }
+
+    /// Fills a convex polygon a row at a time, handing `fill` each covered pixel along with
+    /// `attrs` interpolated linearly across the screen. A pixel is covered if its centre is.
+    fn scanline<const N: usize>(
+        &mut self,
+        points: &[(Point2<f32>, [f32; N])],
+        mut fill: impl FnMut(&mut Self, i32, i32, [f32; N]),
+    ) {
+        if points.len() < 3 {
+            return;
+        }
+        let lerp = |a: [f32; N], b: [f32; N], t: f32| {
+            let mut out = a;
+            for i in 0..N {
+                out[i] += (b[i] - a[i]) * t;
+            }
+            out
+        };
+
+        let (y_min, y_max) = points
+            .iter()
+            .fold((f32::INFINITY, f32::NEG_INFINITY), |(lo, hi), (p, _)| {
+                (lo.min(p.y), hi.max(p.y))
+            });
+        let y_start = (y_min - 0.5).ceil().max(0.0) as i32;
+        let y_end = ((y_max - 0.5).ceil() as i32).min(self.height as i32);
+
+        for y in y_start..y_end {
+            let yc = y as f32 + 0.5;
+
+            // where this row crosses the outline, leftmost and rightmost
+            let mut left: Option<(f32, [f32; N])> = None;
+            let mut right: Option<(f32, [f32; N])> = None;
+            for i in 0..points.len() {
+                let (a, a_attrs) = points[i];
+                let (b, b_attrs) = points[(i + 1) % points.len()];
+                if (a.y <= yc) == (b.y <= yc) {
+                    continue;
+                }
+                let t = (yc - a.y) / (b.y - a.y);
+                let crossing = (a.x + (b.x - a.x) * t, lerp(a_attrs, b_attrs, t));
+                if left.is_none_or(|(x, _)| crossing.0 < x) {
+                    left = Some(crossing);
+                }
+                if right.is_none_or(|(x, _)| crossing.0 > x) {
+                    right = Some(crossing);
+                }
+            }
+            let (Some((xl, al)), Some((xr, ar))) = (left, right) else {
+                continue;
+            };
+            if xr <= xl {
+                continue;
+            }
+
+            let x_start = (xl - 0.5).ceil().max(0.0) as i32;
+            let x_end = ((xr - 0.5).ceil() as i32).min(self.width as i32);
+            for x in x_start..x_end {
+                let t = (x as f32 + 0.5 - xl) / (xr - xl);
+                fill(self, x, y, lerp(al, ar, t));
+            }
+        }
+    }
+}
+
+impl Drawer for FrameBuffer {
+    fn draw_polygon(&mut self, points: &[Point2<f32>], color: (u8, u8, u8, u8)) {
+        let points: Vec<(Point2<f32>, [f32; 0])> = points.iter().map(|p| (*p, [])).collect();
+        self.scanline(&points, |frame, x, y, _| frame.put(x, y, color));
+    }
+
+    fn draw_lit_polygon(&mut self, points: &[LitVertex], color: (u8, u8, u8, u8), light: Light) {
+        let points: Vec<(Point2<f32>, [f32; 1])> =
+            points.iter().map(|p| (p.pos, [p.inv_w])).collect();
+        self.scanline(&points, |frame, x, y, [iw]| {
+            frame.put(x, y, light.at(iw).apply(color));
+        });
+    }
+
+    fn draw_textured_polygon(
+        &mut self,
+        points: &[TexturedVertex],
+        texture: &Texture,
+        light: Light,
+    ) {
+        // u/w, v/w and 1/w go linearly across the screen, so those get interpolated and divided
+        // back out per pixel
+        let points: Vec<(Point2<f32>, [f32; 3])> = points
+            .iter()
+            .map(|p| (p.pos, [p.u * p.inv_w, p.v * p.inv_w, p.inv_w]))
+            .collect();
+        self.scanline(&points, |frame, x, y, [uw, vw, iw]| {
+            let (r, g, b, _) = light.at(iw).apply(texture.sample(uw / iw, vw / iw));
+            frame.put(x, y, (r, g, b, 255));
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fills_pixel_centres() {
+        let mut frame = FrameBuffer::new(4, 4);
+        let square = [(1.0, 1.0), (3.0, 1.0), (3.0, 3.0), (1.0, 3.0)].map(|(x, y)| Point2 { x, y });
+        frame.draw_polygon(&square, (255, 0, 0, 255));
+
+        assert_eq!(frame.pixel(0, 0), Some((0, 0, 0, 255)));
+        assert_eq!(frame.pixel(1, 1), Some((255, 0, 0, 255)));
+        assert_eq!(frame.pixel(2, 2), Some((255, 0, 0, 255)));
+        assert_eq!(frame.pixel(3, 3), Some((0, 0, 0, 255)));
+        assert_eq!(frame.pixel(4, 0), None);
+
+        let mut ppm = Vec::new();
+        frame.write_ppm(&mut ppm).unwrap();
+        assert!(ppm.starts_with(b"P6\n4 4\n255\n"));
+        assert_eq!(ppm.len(), 11 + 4 * 4 * 3);
+    }
+
+    #[test]
+    fn test_lights_every_pixel_for_its_depth() {
+        use crate::game::Lighting;
+
+        // a strip going from 1 unit away on the left to 31 on the right
+        let mut frame = FrameBuffer::new(16, 1);
+        let corner = |x, y, depth: f32| LitVertex {
+            pos: Point2 { x, y },
+            inv_w: 1.0 / depth,
+        };
+        let strip = [
+            corner(0.0, 0.0, 1.0),
+            corner(16.0, 0.0, 31.0),
+            corner(16.0, 1.0, 31.0),
+            corner(0.0, 1.0, 1.0),
+        ];
+        let light = Light {
+            level: 1.0,
+            lighting: Lighting::default(),
+        };
+        frame.draw_lit_polygon(&strip, (200, 200, 200, 255), light);
+
+        let red = |x| frame.pixel(x, 0).unwrap().0;
+        assert!((1..16).all(|x| red(x) <= red(x - 1)));
+        assert!(red(15) + 50 < red(0));
+        // it's 1/w that goes evenly across, so the middle's a lot nearer than 16 units away
+        let inv_w = 1.0 + (1.0 / 31.0 - 1.0) * 8.5 / 16.0;
+        assert!(1.0 / inv_w < 3.0);
+        let expected = light.at(inv_w).apply((200, 200, 200, 255));
+        assert_eq!(frame.pixel(8, 0), Some(expected));
+    }
+}
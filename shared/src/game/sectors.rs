@@ -0,0 +1,130 @@
+//! working out which sectors are on either side of each wall, and what that means for drawing
+//! and walking through it
+
+use super::map::Map;
+use super::vecs::Vec2;
+
+// how far off a wall to look when checking what's on either side of it
+const SIDE_SAMPLE: f32 = 1e-3;
+
+/// How high the camera sits above the floor. With the default floor this puts the eye at 0.
+pub const EYE_HEIGHT: f32 = 0.75;
+/// The biggest step up the player can walk onto.
+pub const MAX_STEP: f32 = 0.5;
+/// The smallest gap between floor and ceiling the player fits through.
+pub const PLAYER_HEIGHT: f32 = 1.0;
+
+/// Whether a spawn's eye height is one the player could have: above the floor, and no higher
+/// than they are tall.
+pub fn eye_height_fits(height: f32) -> bool {
+    height > 0.0 && height <= PLAYER_HEIGHT
+}
+
+/// The sectors to the left and right of a wall (looking from its start to its end).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WallSides {
+    pub left: Option<usize>,
+    pub right: Option<usize>,
+}
+
+impl WallSides {
+    /// A wall is only see-through if there's a different sector on each side of it. Anything
+    /// bordering the outside of every sector is solid.
+    pub fn is_two_sided(&self) -> bool {
+        matches!((self.left, self.right), (Some(l), Some(r)) if l != r)
+    }
+
+    /// The vertical spans of wall to draw, as `(bottom, top)` pairs.
+    ///
+    /// Solid walls go from floor to ceiling. Two sided walls only fill in the steps between the
+    /// floors and ceilings of their two sectors, which can be nothing at all.
+    pub fn spans(&self, map: &Map) -> Vec<(f32, f32)> {
+        if !self.is_two_sided() {
+            let (floor, ceiling) = map.sector_heights(self.left.or(self.right));
+            return vec![(floor, ceiling)];
+        }
+        let (left_floor, left_ceiling) = map.sector_heights(self.left);
+        let (right_floor, right_ceiling) = map.sector_heights(self.right);
+
+        let mut spans = Vec::new();
+        if left_floor != right_floor {
+            spans.push((left_floor.min(right_floor), left_floor.max(right_floor)));
+        }
+        if left_ceiling != right_ceiling {
+            spans.push((
+                left_ceiling.min(right_ceiling),
+                left_ceiling.max(right_ceiling),
+            ));
+        }
+        spans
+    }
+
+    /// Whether someone standing in `from` can walk across this wall.
+    pub fn passable_from(&self, map: &Map, from: Option<usize>) -> bool {
+        if !self.is_two_sided() {
+            return false;
+        }
+        let to = if from == self.left {
+            self.right
+        } else {
+            self.left
+        };
+        let (from_floor, _) = map.sector_heights(from);
+        let (to_floor, to_ceiling) = map.sector_heights(to);
+        to_floor - from_floor <= MAX_STEP && to_ceiling - to_floor >= PLAYER_HEIGHT
+    }
+}
+
+/// Works out the sides of every wall in the map, in the same order as [`Map::walls`].
+pub fn wall_sides(map: &Map) -> Vec<WallSides> {
+    map.walls
+        .iter()
+        .map(|wall| {
+            let wall = &wall.line;
+            let dir = (wall.end - wall.start).normalize();
+            let left_normal = Vec2::new(-dir.y, dir.x);
+            let mid = wall.midpoint();
+            WallSides {
+                left: map.sector_at(mid + left_normal * SIDE_SAMPLE),
+                right: map.sector_at(mid - left_normal * SIDE_SAMPLE),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::fs::load_map;
+
+    // a room with a raised platform in the middle of it
+    const PLATFORM: &str = "
+0 0
+sector -0.75 1.0
+0 0 10 0
+10 0 10 10
+10 10 0 10
+0 10 0 0
+sector -0.25 1.0
+4 4 6 4
+6 4 6 6
+6 6 4 6
+4 6 4 4
+";
+
+    #[test]
+    fn test_sides_and_spans() {
+        let map = load_map(PLATFORM).unwrap();
+        let sides = wall_sides(&map);
+
+        // outer walls are solid, floor to ceiling
+        assert!(!sides[0].is_two_sided());
+        assert_eq!(sides[0].spans(&map), vec![(-0.75, 1.0)]);
+
+        // platform edges are just the step
+        assert!(sides[4].is_two_sided());
+        assert_eq!(sides[4].spans(&map), vec![(-0.75, -0.25)]);
+        assert!(sides[4].passable_from(&map, Some(0)));
+        assert!(!sides[0].passable_from(&map, Some(0)));
+    }
+}
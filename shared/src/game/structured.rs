@@ -0,0 +1,703 @@
+//! maps as TOML or JSON, with room for everything the engine knows about
+//!
+//! Both are the same layout, just written differently. In TOML:
+//!
+//! ```toml
+//! format = 1
+//! name = "Example"
+//! author = "someone"
+//!
+//! [spawn]
+//! x = 2.0
+//! y = 2.0
+//! yaw = 90.0 # degrees, 0 looks along +y and 90 along +x
+//! height = 0.75 # optional, how far above the floor the camera is
+//!
+//! [[spawns]] # more places to start from, by name, with the same keys as [spawn]
+//! name = "exit"
+//! x = 3.0
+//! y = 1.0
+//!
+//! [lighting] # all optional, same as the text format's `lighting` line
+//! falloff = 32.0
+//! min_light = 0.25
+//! fog = 40.0
+//! fog_color = "#101018"
+//!
+//! [[palette]]
+//! name = "sand"
+//! color = "#a08060"
+//!
+//! [[textures]]
+//! name = "brick"
+//! path = "textures/brick.png" # relative to the map file
+//!
+//! [[walls]]
+//! line = [0.0, 0.0, 4.0, 0.0]
+//! texture = "brick" # optional, and so is everything else but the line
+//! u = 0.5
+//! v = 0.0
+//! color = "sand" # a palette name or #rrggbb
+//!
+//! [[sectors]]
+//! walls = [0, 1, 2, 3] # indices into the walls, starting at 0
+//! floor = -0.75
+//! ceiling = 1.0
+//! floor_color = "#646464"
+//! ceiling_color = "sky"
+//! light = 0.6
+//!
+//! [[entities]]
+//! kind = "lamp"
+//! x = 1.0
+//! y = 3.0
+//! yaw = 0.0
+//! ```
+//!
+//! Unknown keys are errors, so typos don't go unnoticed. `format` has to be there, and is bumped
+//! whenever the layout changes in a way older loaders would get wrong.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use super::colors::{parse_hex, to_hex};
+use super::fs::MapError;
+use super::lighting::Lighting;
+use super::lines::LineSegment;
+use super::map::{
+    DEFAULT_CEILING, DEFAULT_CEILING_COLOR, DEFAULT_FLOOR, DEFAULT_FLOOR_COLOR, Entity, Map,
+    MapMetadata, Sector, Spawn, TextureDef, Wall,
+};
+use super::sectors::eye_height_fits;
+use super::vecs::Vec2;
+
+/// The `format` structured maps are written with, and the only one they can be read with.
+pub const STRUCTURED_FORMAT_VERSION: u32 = 1;
+
+/// Why a TOML or JSON map couldn't be loaded.
+#[derive(Debug)]
+pub enum StructuredMapError {
+    /// not valid TOML, or not laid out like a map
+    Toml(toml::de::Error),
+    /// not valid JSON, or not laid out like a map
+    Json(serde_json::Error),
+    UnsupportedVersion(u32),
+    /// laid out fine, but something in it doesn't make sense
+    Invalid {
+        /// where, like `walls[3].texture`
+        field: String,
+        message: String,
+    },
+}
+
+impl fmt::Display for StructuredMapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StructuredMapError::Toml(e) => write!(f, "{e}"),
+            StructuredMapError::Json(e) => write!(f, "{e}"),
+            StructuredMapError::UnsupportedVersion(version) => write!(
+                f,
+                "map is format {version}, expected {STRUCTURED_FORMAT_VERSION}"
+            ),
+            StructuredMapError::Invalid { field, message } => write!(f, "{field}: {message}"),
+        }
+    }
+}
+
+impl From<StructuredMapError> for MapError {
+    fn from(e: StructuredMapError) -> Self {
+        MapError::Structured(e)
+    }
+}
+
+impl std::error::Error for StructuredMapError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            StructuredMapError::Toml(e) => Some(e),
+            StructuredMapError::Json(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MapFile {
+    format: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    author: Option<String>,
+    spawn: SpawnFile,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    spawns: Vec<NamedSpawnFile>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    lighting: Option<LightingFile>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    palette: Vec<PaletteFile>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    textures: Vec<TextureFile>,
+    walls: Vec<WallFile>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    sectors: Vec<SectorFile>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    entities: Vec<EntityFile>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SpawnFile {
+    x: f64,
+    y: f64,
+    #[serde(default)]
+    yaw: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    height: Option<f64>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct NamedSpawnFile {
+    name: String,
+    x: f64,
+    y: f64,
+    #[serde(default)]
+    yaw: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    height: Option<f64>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LightingFile {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    falloff: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    min_light: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    fog: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    fog_color: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PaletteFile {
+    name: String,
+    color: String,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TextureFile {
+    name: String,
+    path: String,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct WallFile {
+    line: [f64; 4],
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    texture: Option<String>,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    u: f64,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    v: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    color: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SectorFile {
+    walls: Vec<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    floor: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    ceiling: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    floor_color: Option<String>,
+    /// `sky` for no ceiling
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    ceiling_color: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    light: Option<f64>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct EntityFile {
+    kind: String,
+    x: f64,
+    y: f64,
+    #[serde(default)]
+    yaw: f64,
+}
+
+fn is_zero(value: &f64) -> bool {
+    *value == 0.0
+}
+
+/// Widens a number the way it'd be written, so `0.1` goes out as `0.1` instead of
+/// `0.10000000149011612`. It still comes back as exactly the same `f32`.
+fn wide(value: f32) -> f64 {
+    value.to_string().parse().unwrap_or(value as f64)
+}
+
+fn invalid(field: impl Into<String>, message: impl Into<String>) -> MapError {
+    MapError::Structured(StructuredMapError::Invalid {
+        field: field.into(),
+        message: message.into(),
+    })
+}
+
+impl MapFile {
+    fn from_map(map: &Map) -> Self {
+        // colours that came from the palette go back out by name
+        let color = |color: (u8, u8, u8)| {
+            map.palette
+                .iter()
+                .find(|(_, c)| *c == color)
+                .map(|(name, _)| name.clone())
+                .unwrap_or_else(|| to_hex(color))
+        };
+        let lighting = (map.lighting != Lighting::default()).then(|| LightingFile {
+            falloff: Some(wide(map.lighting.falloff)),
+            min_light: Some(wide(map.lighting.min_light)),
+            fog: map.lighting.fog_distance.map(wide),
+            fog_color: Some(color(map.lighting.fog_color)),
+        });
+
+        MapFile {
+            format: STRUCTURED_FORMAT_VERSION,
+            name: map.metadata.name.clone(),
+            author: map.metadata.author.clone(),
+            spawn: SpawnFile {
+                x: wide(map.spawn.position.x),
+                y: wide(map.spawn.position.y),
+                yaw: wide(map.spawn.yaw.to_degrees()),
+                height: map.spawn.height.map(wide),
+            },
+            spawns: map
+                .named_spawns
+                .iter()
+                .map(|(name, spawn)| NamedSpawnFile {
+                    name: name.clone(),
+                    x: wide(spawn.position.x),
+                    y: wide(spawn.position.y),
+                    yaw: wide(spawn.yaw.to_degrees()),
+                    height: spawn.height.map(wide),
+                })
+                .collect(),
+            lighting,
+            palette: map
+                .palette
+                .iter()
+                .map(|(name, c)| PaletteFile {
+                    name: name.clone(),
+                    color: to_hex(*c),
+                })
+                .collect(),
+            textures: map
+                .textures
+                .iter()
+                .map(|texture| TextureFile {
+                    name: texture.name.clone(),
+                    path: texture.path.to_string_lossy().into_owned(),
+                })
+                .collect(),
+            walls: map
+                .walls
+                .iter()
+                .map(|wall| {
+                    let LineSegment { start, end } = wall.line;
+                    WallFile {
+                        line: [start.x, start.y, end.x, end.y].map(wide),
+                        texture: wall.texture.map(|i| map.textures[i].name.clone()),
+                        u: wide(wall.u_offset),
+                        v: wide(wall.v_offset),
+                        color: wall.color.map(color),
+                    }
+                })
+                .collect(),
+            sectors: map
+                .sectors
+                .iter()
+                .map(|sector| SectorFile {
+                    walls: sector.walls.clone(),
+                    floor: Some(wide(sector.floor)),
+                    ceiling: Some(wide(sector.ceiling)),
+                    floor_color: Some(color(sector.floor_color)),
+                    ceiling_color: Some(
+                        sector
+                            .ceiling_color
+                            .map_or_else(|| "sky".to_string(), color),
+                    ),
+                    light: (sector.light != 1.0).then(|| wide(sector.light)),
+                })
+                .collect(),
+            entities: map
+                .entities
+                .iter()
+                .map(|entity| EntityFile {
+                    kind: entity.kind.clone(),
+                    x: wide(entity.position.x),
+                    y: wide(entity.position.y),
+                    yaw: wide(entity.yaw.to_degrees()),
+                })
+                .collect(),
+        }
+    }
+
+    fn into_map(self) -> Result<Map, MapError> {
+        if self.format != STRUCTURED_FORMAT_VERSION {
+            return Err(StructuredMapError::UnsupportedVersion(self.format).into());
+        }
+
+        let mut palette: Vec<(String, (u8, u8, u8))> = Vec::new();
+        for (i, entry) in self.palette.into_iter().enumerate() {
+            if palette.iter().any(|(name, _)| *name == entry.name) {
+                return Err(invalid(
+                    format!("palette[{i}].name"),
+                    format!("`{}` is already in the palette", entry.name),
+                ));
+            }
+            let color = parse_hex(&entry.color).ok_or_else(|| {
+                invalid(
+                    format!("palette[{i}].color"),
+                    format!("`{}` isn't a #rrggbb colour", entry.color),
+                )
+            })?;
+            palette.push((entry.name, color));
+        }
+        let color = |field: String, value: &str| {
+            palette
+                .iter()
+                .find(|(name, _)| name == value)
+                .map(|(_, color)| *color)
+                .or_else(|| parse_hex(value))
+                .ok_or_else(|| {
+                    invalid(
+                        field,
+                        format!("`{value}` isn't a #rrggbb colour or in the palette"),
+                    )
+                })
+        };
+        let fraction = |field: &str, value: f64| {
+            if (0.0..=1.0).contains(&value) {
+                Ok(value as f32)
+            } else {
+                Err(invalid(field, "has to be from 0 to 1"))
+            }
+        };
+        let positive = |field: &str, value: f64| {
+            if value > 0.0 {
+                Ok(value as f32)
+            } else {
+                Err(invalid(field, "has to be above 0"))
+            }
+        };
+
+        let eye_height = |field: &str, value: f64| {
+            if eye_height_fits(value as f32) {
+                Ok(value as f32)
+            } else {
+                Err(invalid(field, "has to be above 0 and at most the player's height"))
+            }
+        };
+
+        let spawn = |field: &str, x: f64, y: f64, yaw: f64, height: Option<f64>| {
+            Ok::<_, MapError>(Spawn {
+                position: Vec2::new(x as f32, y as f32),
+                yaw: (yaw as f32).to_radians(),
+                height: height
+                    .map(|height| eye_height(&format!("{field}.height"), height))
+                    .transpose()?,
+            })
+        };
+        let file = self.spawn;
+        let first_spawn = spawn("spawn", file.x, file.y, file.yaw, file.height)?;
+        let mut named_spawns: Vec<(String, Spawn)> = Vec::new();
+        for (i, file) in self.spawns.into_iter().enumerate() {
+            let field = format!("spawns[{i}]");
+            if named_spawns.iter().any(|(name, _)| *name == file.name) {
+                return Err(invalid(
+                    format!("{field}.name"),
+                    format!("there's already a spawn called `{}`", file.name),
+                ));
+            }
+            let named = spawn(&field, file.x, file.y, file.yaw, file.height)?;
+            named_spawns.push((file.name, named));
+        }
+
+        let mut lighting = Lighting::default();
+        if let Some(file) = self.lighting {
+            if let Some(falloff) = file.falloff {
+                lighting.falloff = positive("lighting.falloff", falloff)?;
+            }
+            if let Some(min_light) = file.min_light {
+                lighting.min_light = fraction("lighting.min_light", min_light)?;
+            }
+            if let Some(fog) = file.fog {
+                lighting.fog_distance = Some(positive("lighting.fog", fog)?);
+            }
+            if let Some(fog_color) = file.fog_color {
+                lighting.fog_color = color("lighting.fog_color".to_string(), &fog_color)?;
+            }
+        }
+
+        let textures: Vec<TextureDef> = self
+            .textures
+            .into_iter()
+            .map(|texture| TextureDef {
+                name: texture.name,
+                path: texture.path.into(),
+            })
+            .collect();
+
+        if self.walls.is_empty() {
+            return Err(MapError::NoWalls);
+        }
+        let mut walls = Vec::new();
+        for (i, wall) in self.walls.into_iter().enumerate() {
+            let [x1, y1, x2, y2] = wall.line.map(|n| n as f32);
+            let texture = match wall.texture {
+                Some(name) => Some(
+                    textures
+                        .iter()
+                        .position(|texture| texture.name == name)
+                        .ok_or_else(|| {
+                            invalid(
+                                format!("walls[{i}].texture"),
+                                format!("there's no texture called `{name}`"),
+                            )
+                        })?,
+                ),
+                None => None,
+            };
+            walls.push(Wall {
+                line: LineSegment::from((x1, y1, x2, y2)),
+                texture,
+                u_offset: wall.u as f32,
+                v_offset: wall.v as f32,
+                color: match wall.color {
+                    Some(value) => Some(color(format!("walls[{i}].color"), &value)?),
+                    None => None,
+                },
+            });
+        }
+
+        let mut sectors = Vec::new();
+        for (i, file) in self.sectors.into_iter().enumerate() {
+            let mut sector = Sector {
+                floor: file.floor.map_or(DEFAULT_FLOOR, |n| n as f32),
+                ceiling: file.ceiling.map_or(DEFAULT_CEILING, |n| n as f32),
+                light: match file.light {
+                    Some(light) => fraction(&format!("sectors[{i}].light"), light)?,
+                    None => 1.0,
+                },
+                ..Sector::default()
+            };
+            sector.floor_color = match file.floor_color {
+                Some(value) => color(format!("sectors[{i}].floor_color"), &value)?,
+                None => DEFAULT_FLOOR_COLOR,
+            };
+            sector.ceiling_color = match file.ceiling_color.as_deref() {
+                Some("sky") => None,
+                Some(value) => Some(color(format!("sectors[{i}].ceiling_color"), value)?),
+                None => Some(DEFAULT_CEILING_COLOR),
+            };
+            if let Some(wall) = file.walls.iter().find(|&&wall| wall >= walls.len()) {
+                return Err(invalid(
+                    format!("sectors[{i}].walls"),
+                    format!("there's no wall {wall}, there are only {}", walls.len()),
+                ));
+            }
+            sector.walls = file.walls;
+            sectors.push(sector);
+        }
+
+        let map = Map {
+            spawn: first_spawn,
+            named_spawns,
+            walls,
+            sectors,
+            textures,
+            palette,
+            lighting,
+            entities: self
+                .entities
+                .into_iter()
+                .map(|entity| Entity {
+                    kind: entity.kind,
+                    position: Vec2::new(entity.x as f32, entity.y as f32),
+                    yaw: (entity.yaw as f32).to_radians(),
+                })
+                .collect(),
+            metadata: MapMetadata {
+                name: self.name,
+                author: self.author,
+                source: None,
+                lines: None,
+            },
+        };
+        // the same checks as every other kind of map, which also catches numbers too big for an
+        // f32
+        map.validate().map_err(MapError::Invalid)?;
+        Ok(map)
+    }
+}
+
+/// Parses a map out of TOML.
+pub fn load_toml_map(contents: &str) -> Result<Map, MapError> {
+    let file: MapFile = toml::from_str(contents).map_err(StructuredMapError::Toml)?;
+    file.into_map()
+}
+
+/// Parses a map out of JSON.
+pub fn load_json_map(contents: &str) -> Result<Map, MapError> {
+    let file: MapFile = serde_json::from_str(contents).map_err(StructuredMapError::Json)?;
+    file.into_map()
+}
+
+/// Writes a map out as TOML. Colours from the palette are written by name.
+pub fn map_to_toml(map: &Map) -> String {
+    toml::to_string(&MapFile::from_map(map)).expect("maps always fit in TOML")
+}
+
+/// Writes a map out as JSON. Colours from the palette are written by name.
+pub fn map_to_json(map: &Map) -> String {
+    serde_json::to_string_pretty(&MapFile::from_map(map)).expect("maps always fit in JSON")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::compiled::load_any_map_from_path;
+
+    #[test]
+    fn test_shipped_maps_convert() {
+        let root = concat!(env!("CARGO_MANIFEST_DIR"), "/..");
+        for name in ["map01", "map02", "map04", "maze", "steps", "test_map"] {
+            let mut map = load_any_map_from_path(format!("{root}/{name}.txt"), None)
+                .unwrap()
+                .map;
+            map.metadata.source = None;
+            assert_eq!(load_toml_map(&map_to_toml(&map)).unwrap(), map, "{name}");
+            assert_eq!(load_json_map(&map_to_json(&map)).unwrap(), map, "{name}");
+        }
+    }
+
+    #[test]
+    fn test_load_toml() {
+        let map = load_toml_map(
+            r##"
+format = 1
+name = "Example"
+spawn = { x = 2, y = 1.5, yaw = 90 }
+spawns = [{ name = "exit", x = 3, y = 1, height = 0.5 }]
+palette = [{ name = "sand", color = "#a08060" }]
+textures = [{ name = "brick", path = "brick.png" }]
+walls = [
+    { line = [0, 0, 4, 0], texture = "brick", u = 0.5 },
+    { line = [4, 0, 4, 4], color = "sand" },
+    { line = [4, 4, 0, 4] },
+    { line = [0, 4, 0, 0], color = "#ff0000" },
+]
+sectors = [{ walls = [0, 1, 2, 3], floor = -0.5, ceiling_color = "sky", light = 0.5 }]
+entities = [{ kind = "lamp", x = 1, y = 3 }]
+"##,
+        )
+        .unwrap();
+
+        assert_eq!(map.metadata.name.as_deref(), Some("Example"));
+        assert_eq!(map.spawn.position, Vec2::new(2.0, 1.5));
+        assert!((map.spawn.yaw - std::f32::consts::FRAC_PI_2).abs() < 1e-6);
+        assert_eq!(
+            map.named_spawn("exit"),
+            Some(&Spawn {
+                position: Vec2::new(3.0, 1.0),
+                yaw: 0.0,
+                height: Some(0.5),
+            })
+        );
+        assert_eq!(map.walls[0].texture, Some(0));
+        assert_eq!(map.walls[0].u_offset, 0.5);
+        assert_eq!(map.walls[1].color, Some((0xa0, 0x80, 0x60)));
+        assert_eq!(map.walls[3].color, Some((255, 0, 0)));
+        assert_eq!(map.sectors[0].floor, -0.5);
+        assert_eq!(map.sectors[0].ceiling, DEFAULT_CEILING);
+        assert_eq!(map.sectors[0].ceiling_color, None);
+        assert_eq!(map.entities[0].kind, "lamp");
+        assert_eq!(map.entities[0].position, Vec2::new(1.0, 3.0));
+    }
+
+    #[test]
+    fn test_structured_errors() {
+        let error = |source: &str| match load_toml_map(source) {
+            Err(MapError::Structured(e)) => e,
+            other => panic!("expected an error, got {other:?}"),
+        };
+        let walls = "walls = [{ line = [0, 0, 1, 0] }]";
+        assert!(matches!(
+            error(&format!("format = 2\nspawn = {{ x = 0, y = 0 }}\n{walls}")),
+            StructuredMapError::UnsupportedVersion(2)
+        ));
+        // typos get caught instead of ignored
+        assert!(matches!(
+            error(&format!("format = 1\nspwan = {{ x = 0, y = 0 }}\n{walls}")),
+            StructuredMapError::Toml(_)
+        ));
+        let invalid = |source: &str| match error(source) {
+            StructuredMapError::Invalid { field, .. } => field,
+            other => panic!("expected an invalid field, got {other:?}"),
+        };
+        let spawn = "format = 1\nspawn = { x = 0, y = 0 }";
+        assert_eq!(
+            invalid(&format!(
+                "{spawn}\nwalls = [{{ line = [0, 0, 1, 0], texture = \"nope\" }}]"
+            )),
+            "walls[0].texture"
+        );
+        assert_eq!(
+            invalid(&format!("{spawn}\n{walls}\nsectors = [{{ walls = [1] }}]")),
+            "sectors[0].walls"
+        );
+        assert_eq!(
+            invalid(&format!(
+                "{spawn}\n{walls}\nsectors = [{{ walls = [0], light = 2 }}]"
+            )),
+            "sectors[0].light"
+        );
+        // things only the map as a whole can tell are wrong
+        assert!(matches!(
+            load_toml_map(&format!(
+                "{spawn}\n{walls}\nsectors = [{{ walls = [0], floor = 2, ceiling = 1 }}]"
+            )),
+            Err(MapError::Invalid("sector floor isn't below its ceiling"))
+        ));
+        assert!(matches!(
+            load_toml_map(&format!("{spawn}\nwalls = [{{ line = [0, 0, 1e300, 0] }}]")),
+            Err(MapError::Invalid("wall has a coordinate that isn't finite"))
+        ));
+        assert_eq!(
+            invalid(&format!(
+                "{spawn}\n{walls}\nspawns = [{{ name = \"a\", x = 0, y = 0 }}, \
+                 {{ name = \"a\", x = 1, y = 1 }}]"
+            )),
+            "spawns[1].name"
+        );
+        assert_eq!(
+            invalid(&format!(
+                "format = 1\nspawn = {{ x = 0, y = 0, height = -1 }}\n{walls}"
+            )),
+            "spawn.height"
+        );
+        assert!(matches!(
+            load_json_map("{\"format\": 1}"),
+            Err(MapError::Structured(StructuredMapError::Json(_)))
+        ));
+    }
+}
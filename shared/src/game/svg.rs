@@ -0,0 +1,226 @@
+//! drawing maps from above as SVG, for looking over a layout without running the game
+//!
+//! North (+y) is up. Walls are drawn in the colour the game gives them, spawns as a circle with
+//! a line showing which way they face, and named spawns get their name next to them. With a BSP
+//! tree, each node's partition is drawn dashed across the part of the level it splits, and the
+//! points where walls got cut in two are marked.
+
+use super::bsp::BSPTree;
+use super::colors::{to_hex, wall_color};
+use super::culling::BoundingBox;
+use super::map::{Map, Spawn};
+use super::vecs::Vec2;
+
+/// how many pixels across the longer side of the picture is
+const SIZE: f32 = 800.0;
+
+const BACKGROUND: &str = "#1a1a1a";
+const PARTITION_COLOR: &str = "#4a78b8";
+const SPLIT_COLOR: &str = "#ff5050";
+const SPAWN_COLOR: &str = "#50e070";
+const NAMED_SPAWN_COLOR: &str = "#e0c050";
+
+/// a number without float noise like `0.16499999`, to keep the file readable
+fn num(n: f32) -> String {
+    let rounded = format!("{:.4}", n);
+    let trimmed = rounded.trim_end_matches('0').trim_end_matches('.');
+    match trimmed {
+        "-0" => "0".to_string(),
+        _ => trimmed.to_string(),
+    }
+}
+
+/// the attributes for a `<line>`
+fn line_attributes(start: Vec2, end: Vec2) -> String {
+    format!(
+        "x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\"",
+        num(start.x),
+        num(start.y),
+        num(end.x),
+        num(end.y)
+    )
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Draws a map as an SVG, with its BSP tree's partitions and splits on top if it's given one.
+pub fn map_to_svg(map: &Map, bsp: Option<&BSPTree>) -> String {
+    let spawns = std::iter::once((None, &map.spawn)).chain(
+        map.named_spawns
+            .iter()
+            .map(|(name, spawn)| (Some(name.as_str()), spawn)),
+    );
+    let bounds = BoundingBox::around(
+        map.walls
+            .iter()
+            .flat_map(|wall| [wall.line.start, wall.line.end])
+            .chain(spawns.clone().map(|(_, spawn)| spawn.position)),
+    );
+    let extent = (bounds.max.x - bounds.min.x).max(bounds.max.y - bounds.min.y);
+    let margin = (extent * 0.05).max(1.0);
+    let (min, max) = (bounds.min - margin, bounds.max + margin);
+    let (width, height) = (max.x - min.x, max.y - min.y);
+    // map units per pixel, so lines and markers come out the same size whatever the map's size
+    let px = width.max(height) / SIZE;
+
+    let (x, y, width, height) = (num(min.x), num(-max.y), num(width), num(height));
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"{x} {y} {width} {height}\" \
+         width=\"{}\" height=\"{}\">\n",
+        ((max.x - min.x) / px).round(),
+        ((max.y - min.y) / px).round(),
+    );
+    if let Some(name) = &map.metadata.name {
+        svg += &format!("  <title>{}</title>\n", escape(name));
+    }
+    svg += &format!(
+        "  <rect x=\"{x}\" y=\"{y}\" width=\"{width}\" height=\"{height}\" \
+         fill=\"{BACKGROUND}\"/>\n"
+    );
+    // flipped so +y goes up the page
+    svg += &format!(
+        "  <g transform=\"scale(1 -1)\" fill=\"none\" stroke-linecap=\"round\" \
+         stroke-width=\"{}\">\n",
+        num(2.0 * px)
+    );
+
+    let partitions = bsp.map(BSPTree::partition_lines).unwrap_or_default();
+    if !partitions.is_empty() {
+        svg += &format!(
+            "    <g stroke=\"{PARTITION_COLOR}\" stroke-width=\"{}\" stroke-dasharray=\"{} {}\">\n",
+            num(px),
+            num(6.0 * px),
+            num(4.0 * px)
+        );
+        for (_, line) in &partitions {
+            svg += &format!("      <line {}/>\n", line_attributes(line.start, line.end));
+        }
+        svg += "    </g>\n";
+    }
+
+    let lines = map.metadata.lines.as_ref();
+    svg += "    <g>\n";
+    for (i, wall) in map.walls.iter().enumerate() {
+        let line = wall.line;
+        let title = match lines.and_then(|lines| lines.walls.get(i)) {
+            Some(line_no) => format!("wall {i}, line {line_no}"),
+            None => format!("wall {i}"),
+        };
+        svg += &format!(
+            "      <line {} stroke=\"{}\"><title>{title}</title></line>\n",
+            line_attributes(line.start, line.end),
+            to_hex(wall_color(map, i)),
+        );
+    }
+    svg += "    </g>\n";
+
+    // the ends of segs that aren't the ends of their walls are where a partition cut through
+    let mut splits: Vec<Vec2> = Vec::new();
+    for (seg, _) in &partitions {
+        let wall = map.walls[seg.wall].line;
+        for end in [seg.line.start, seg.line.end] {
+            let at = |p: Vec2| (p - end).length() < 1e-4;
+            if !at(wall.start) && !at(wall.end) && !splits.iter().any(|&p| at(p)) {
+                splits.push(end);
+            }
+        }
+    }
+    if !splits.is_empty() {
+        svg += &format!("    <g fill=\"{SPLIT_COLOR}\" stroke=\"none\">\n");
+        for split in splits {
+            svg += &format!(
+                "      <circle cx=\"{}\" cy=\"{}\" r=\"{}\"/>\n",
+                num(split.x),
+                num(split.y),
+                num(3.0 * px)
+            );
+        }
+        svg += "    </g>\n";
+    }
+
+    for (name, spawn) in spawns.clone() {
+        let color = if name.is_some() {
+            NAMED_SPAWN_COLOR
+        } else {
+            SPAWN_COLOR
+        };
+        svg += &spawn_marker(spawn, name.unwrap_or("spawn"), color, px);
+    }
+    svg += "  </g>\n";
+
+    // names go outside the flip, or they'd be upside down
+    for (name, spawn) in spawns.filter_map(|(name, spawn)| Some((name?, spawn))) {
+        svg += &format!(
+            "  <text x=\"{}\" y=\"{}\" fill=\"{NAMED_SPAWN_COLOR}\" font-family=\"sans-serif\" \
+             font-size=\"{}\">{}</text>\n",
+            num(spawn.position.x + 9.0 * px),
+            num(-spawn.position.y + 4.0 * px),
+            num(12.0 * px),
+            escape(name)
+        );
+    }
+    svg += "</svg>\n";
+    svg
+}
+
+/// a circle at the spawn with a line out of it the way it's facing
+fn spawn_marker(spawn: &Spawn, title: &str, color: &str, px: f32) -> String {
+    let position = spawn.position;
+    let facing = position + Vec2::new(spawn.yaw.sin(), spawn.yaw.cos()) * 16.0 * px;
+    format!(
+        "    <g stroke=\"{color}\"><title>{}</title><circle cx=\"{}\" cy=\"{}\" r=\"{}\"/>\
+         <line {}/></g>\n",
+        escape(title),
+        num(position.x),
+        num(position.y),
+        num(6.0 * px),
+        line_attributes(position, facing)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::bsp::PartitionChooser;
+    use crate::game::compiled::CompiledMap;
+    use crate::game::fs::load_map;
+
+    fn count(svg: &str, element: &str) -> usize {
+        svg.matches(&format!("<{element} ")).count()
+    }
+
+    #[test]
+    fn test_draws_walls_and_spawns() {
+        let map =
+            load_map("name <Cross> & co\n5 5 yaw=90\nspawn top 0 9\n-10 0 10 0\n0 -10 0 10\n")
+                .unwrap();
+        let svg = map_to_svg(&map, None);
+        assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\""));
+        assert!(svg.contains("<title>&lt;Cross&gt; &amp; co</title>"));
+        assert!(svg.contains("<title>wall 1, line 5</title>"));
+        // the two walls, plus a facing line for each spawn
+        assert_eq!(count(&svg, "line"), 4);
+        assert_eq!(count(&svg, "circle"), 2);
+        assert!(svg.contains(">top</text>"));
+        // flipped so north is up, with a margin of a twentieth of the level either side
+        assert!(svg.contains("viewBox=\"-11 -11 22 22\" width=\"800\" height=\"800\""));
+    }
+
+    #[test]
+    fn test_draws_partitions_and_splits() {
+        let map = load_map("5 5\n-10 0 10 0\n0 -10 0 10\n").unwrap();
+        let level = CompiledMap::compile(map, &PartitionChooser::FIRST);
+        let svg = map_to_svg(&level.map, Some(&level.bsp));
+
+        // a partition for each of the three segs, and the one place the second wall got cut
+        assert_eq!(level.stats.nodes, 3);
+        assert_eq!(count(&svg, "line"), 2 + 1 + 3);
+        assert_eq!(count(&svg, "circle"), 1 + 1);
+        assert!(svg.contains("<circle cx=\"0\" cy=\"0\""));
+    }
+}
@@ -0,0 +1,225 @@
+//! textures for walls, and loading them off disk
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use super::map::Map;
+
+/// An RGBA image that repeats forever in both directions.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Texture {
+    width: u32,
+    height: u32,
+    /// rows top to bottom, 4 bytes a pixel
+    pixels: Vec<u8>,
+    average: (u8, u8, u8, u8),
+}
+
+impl Texture {
+    /// Makes a texture out of raw RGBA bytes. Panics if there aren't exactly enough of them, or
+    /// if the texture would be empty.
+    pub fn from_rgba(width: u32, height: u32, pixels: Vec<u8>) -> Self {
+        assert!(width > 0 && height > 0, "textures can't be empty");
+        // in usize and checked, since a big enough texture overflows u32
+        let len = (width as usize)
+            .checked_mul(height as usize)
+            .and_then(|n| n.checked_mul(4));
+        assert_eq!(Some(pixels.len()), len, "need 4 bytes for each pixel");
+
+        let mut sum = [0u64; 4];
+        for pixel in pixels.chunks_exact(4) {
+            for (total, channel) in sum.iter_mut().zip(pixel) {
+                *total += *channel as u64;
+            }
+        }
+        let count = (pixels.len() / 4) as u64;
+        let average = (
+            (sum[0] / count) as u8,
+            (sum[1] / count) as u8,
+            (sum[2] / count) as u8,
+            (sum[3] / count) as u8,
+        );
+
+        Texture {
+            width,
+            height,
+            pixels,
+            average,
+        }
+    }
+
+    /// Loads a PNG. Whatever colour type it's in comes out as 8 bit RGBA.
+    pub fn load_png(path: impl AsRef<Path>) -> Result<Self, TextureError> {
+        let path = path.as_ref();
+        let error = |kind| TextureError {
+            path: path.to_path_buf(),
+            kind,
+        };
+
+        let file = std::fs::File::open(path).map_err(|e| error(TextureErrorKind::Io(e)))?;
+        let mut decoder = png::Decoder::new(std::io::BufReader::new(file));
+        decoder.set_transformations(png::Transformations::normalize_to_color8());
+        let decode_error = |e: png::DecodingError| error(TextureErrorKind::Decode(e.to_string()));
+        let mut reader = decoder.read_info().map_err(decode_error)?;
+        let mut buf = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut buf).map_err(decode_error)?;
+        let buf = &buf[..info.buffer_size()];
+
+        let pixels: Vec<u8> = match info.color_type {
+            png::ColorType::Rgba => buf.to_vec(),
+            png::ColorType::Rgb => buf
+                .chunks_exact(3)
+                .flat_map(|p| [p[0], p[1], p[2], 255])
+                .collect(),
+            png::ColorType::GrayscaleAlpha => buf
+                .chunks_exact(2)
+                .flat_map(|p| [p[0], p[0], p[0], p[1]])
+                .collect(),
+            png::ColorType::Grayscale => buf.iter().flat_map(|g| [*g, *g, *g, 255]).collect(),
+            png::ColorType::Indexed => {
+                // normalize_to_color8 expands palettes, so this shouldn't happen
+                let message = "indexed colour wasn't expanded".to_string();
+                return Err(error(TextureErrorKind::Decode(message)));
+            }
+        };
+        if info.width == 0 || info.height == 0 {
+            return Err(error(TextureErrorKind::Decode(
+                "image is empty".to_string(),
+            )));
+        }
+
+        Ok(Texture::from_rgba(info.width, info.height, pixels))
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// The colour at a texture coordinate, where 0..1 covers the texture once and it wraps
+    /// outside of that. Nearest neighbour, `v` going down.
+    pub fn sample(&self, u: f32, v: f32) -> (u8, u8, u8, u8) {
+        let x = ((u.rem_euclid(1.0) * self.width as f32) as u32).min(self.width - 1);
+        let y = ((v.rem_euclid(1.0) * self.height as f32) as u32).min(self.height - 1);
+        let offset = (y as usize * self.width as usize + x as usize) * 4;
+        let p = &self.pixels[offset..offset + 4];
+        (p[0], p[1], p[2], p[3])
+    }
+
+    /// the average of every pixel, for when a backend can't draw textures
+    pub fn average_color(&self) -> (u8, u8, u8, u8) {
+        self.average
+    }
+}
+
+/// Every texture a map uses, indexed the same as [`Map::textures`].
+#[derive(Debug, Clone, Default)]
+pub struct TextureRegistry {
+    textures: Vec<Texture>,
+}
+
+impl TextureRegistry {
+    /// Loads every texture the map declares. Relative paths are relative to the map file, or the
+    /// working directory if the map didn't come from a file.
+    pub fn load_for_map(map: &Map) -> Result<Self, TextureError> {
+        let base = map
+            .metadata
+            .source
+            .as_ref()
+            .and_then(|source| source.parent())
+            .unwrap_or(Path::new(""));
+        let textures = map
+            .textures
+            .iter()
+            .map(|def| Texture::load_png(base.join(&def.path)))
+            .collect::<Result<_, _>>()?;
+        Ok(TextureRegistry { textures })
+    }
+
+    /// adds a texture that didn't come from a file, giving back its id
+    pub fn add(&mut self, texture: Texture) -> usize {
+        self.textures.push(texture);
+        self.textures.len() - 1
+    }
+
+    pub fn get(&self, id: usize) -> Option<&Texture> {
+        self.textures.get(id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.textures.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.textures.is_empty()
+    }
+}
+
+#[derive(Debug)]
+pub struct TextureError {
+    pub path: PathBuf,
+    pub kind: TextureErrorKind,
+}
+
+#[derive(Debug)]
+pub enum TextureErrorKind {
+    Io(std::io::Error),
+    Decode(String),
+}
+
+impl fmt::Display for TextureError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "couldn't load texture {}: ", self.path.display())?;
+        match &self.kind {
+            TextureErrorKind::Io(e) => write!(f, "{e}"),
+            TextureErrorKind::Decode(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for TextureError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match &self.kind {
+            TextureErrorKind::Io(e) => Some(e),
+            TextureErrorKind::Decode(_) => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn checker() -> Texture {
+        // 2x2, white and black
+        let (w, b) = ([255, 255, 255, 255], [0, 0, 0, 255]);
+        Texture::from_rgba(2, 2, [w, b, b, w].concat())
+    }
+
+    #[test]
+    fn test_sample_wraps() {
+        let tex = checker();
+        assert_eq!(tex.sample(0.25, 0.25), (255, 255, 255, 255));
+        assert_eq!(tex.sample(0.75, 0.25), (0, 0, 0, 255));
+        assert_eq!(tex.sample(1.75, -0.75), (0, 0, 0, 255));
+        assert_eq!(tex.average_color(), (127, 127, 127, 255));
+    }
+
+    #[test]
+    #[should_panic(expected = "need 4 bytes for each pixel")]
+    fn test_huge_sizes_dont_wrap() {
+        // 65536 * 65536 * 4 is 0 in u32, which used to match the empty pixel buffer
+        Texture::from_rgba(65536, 65536, Vec::new());
+    }
+
+    #[test]
+    fn test_load_shipped_textures() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("../textures");
+        let brick = Texture::load_png(dir.join("brick.png")).unwrap();
+        assert_eq!((brick.width(), brick.height()), (32, 32));
+        assert!(Texture::load_png(dir.join("nope.png")).is_err());
+    }
+}
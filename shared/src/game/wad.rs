@@ -0,0 +1,605 @@
+//! importing levels out of Doom WAD files
+//!
+//! Only the geometry comes across: `VERTEXES` and `LINEDEFS` become walls, `SECTORS` their
+//! floor and ceiling heights and light levels (with `SIDEDEFS` saying which sectors each line
+//! is between), and the player 1 start in `THINGS` becomes the spawn. The other player starts
+//! and deathmatch starts become named spawns (`player2`, `deathmatch1` and so on). Everything
+//! else, like
+//! textures, line specials and monsters, is left out with a [`WadWarning`] saying so. The
+//! nodes the WAD was built with are thrown away too, since the engine builds its own.
+//!
+//! WADs are little-endian throughout. Coordinates get divided by [`WAD_UNITS`] on the way in.
+
+use std::fmt;
+
+use super::lighting::Lighting;
+use super::lines::LineSegment;
+use super::map::{
+    DEFAULT_CEILING_COLOR, DEFAULT_FLOOR_COLOR, Map, MapMetadata, Sector, Spawn, Wall,
+};
+use super::vecs::Vec2;
+
+/// How many WAD units make one map unit. Doom's player can step up 24 units, which comes out
+/// the same as [`MAX_STEP`](super::sectors::MAX_STEP).
+pub const WAD_UNITS: f32 = 48.0;
+
+// lumps that can come after a map's marker
+const MAP_LUMPS: &[&str] = &[
+    "THINGS", "LINEDEFS", "SIDEDEFS", "VERTEXES", "SEGS", "SSECTORS", "NODES", "SECTORS", "REJECT",
+    "BLOCKMAP", "BEHAVIOR", "SCRIPTS",
+];
+const THING_SIZE: usize = 10;
+const LINEDEF_SIZE: usize = 14;
+const SIDEDEF_SIZE: usize = 30;
+const VERTEX_SIZE: usize = 4;
+const SECTOR_SIZE: usize = 26;
+const PLAYER_1_START: u16 = 1;
+const PLAYER_4_START: u16 = 4;
+const DEATHMATCH_START: u16 = 11;
+const NO_SIDEDEF: u16 = 0xffff;
+
+/// Why a map couldn't be imported from a WAD.
+#[derive(Debug, Clone, PartialEq)]
+pub enum WadError {
+    /// doesn't start with `IWAD` or `PWAD`
+    NotAWad,
+    /// the header or directory points past the end of the file
+    Truncated,
+    /// there are no maps in the WAD at all
+    NoMaps,
+    NoSuchMap(String),
+    MissingLump {
+        map: String,
+        lump: &'static str,
+    },
+    /// a lump that isn't a whole number of records long
+    BadLumpSize {
+        map: String,
+        lump: &'static str,
+    },
+    /// something points at a vertex, sidedef or sector that isn't there
+    BadIndex {
+        map: String,
+        lump: &'static str,
+        record: usize,
+    },
+    /// Hexen's map format, which lays its lumps out differently
+    HexenFormat(String),
+    NoPlayerStart(String),
+    /// every one of the map's lines got left out, so there'd be nothing to draw
+    NoWalls(String),
+}
+
+impl fmt::Display for WadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WadError::NotAWad => write!(f, "not a WAD file"),
+            WadError::Truncated => write!(f, "WAD file is cut short"),
+            WadError::NoMaps => write!(f, "WAD has no maps in it"),
+            WadError::NoSuchMap(name) => write!(f, "WAD has no map called {name}"),
+            WadError::MissingLump { map, lump } => write!(f, "{map} has no {lump} lump"),
+            WadError::BadLumpSize { map, lump } => {
+                write!(f, "{map}'s {lump} lump isn't a whole number of entries")
+            }
+            WadError::BadIndex { map, lump, record } => {
+                write!(
+                    f,
+                    "entry {record} of {map}'s {lump} points at something missing"
+                )
+            }
+            WadError::HexenFormat(map) => {
+                write!(f, "{map} is in Hexen format, which isn't supported")
+            }
+            WadError::NoPlayerStart(map) => write!(f, "{map} has no player 1 start"),
+            WadError::NoWalls(map) => write!(f, "{map} has no walls that could be imported"),
+        }
+    }
+}
+
+impl std::error::Error for WadError {}
+
+/// Something in the map that got left out or changed on the way in.
+#[derive(Debug, Clone, PartialEq)]
+pub enum WadWarning {
+    /// wall textures and flats can't be loaded from a WAD, so everything is flat coloured
+    Textures,
+    /// lines with a special (doors, lifts, switches and so on), which don't do anything here
+    LineSpecials(usize),
+    /// sectors with a special (damage, blinking lights and so on)
+    SectorSpecials(usize),
+    /// sectors with their ceiling down on the floor, like closed doors, which got raised just
+    /// off it
+    ClosedSectors(usize),
+    /// two-sided lines with the same sector on both sides, which got left out
+    SameSectorBothSides(usize),
+    /// lines without a front side, which got left out
+    NoFrontSide(usize),
+    /// things other than player and deathmatch starts
+    Things(usize),
+}
+
+impl fmt::Display for WadWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WadWarning::Textures => {
+                write!(
+                    f,
+                    "textures and flats aren't imported, walls will be flat coloured"
+                )
+            }
+            WadWarning::LineSpecials(count) => {
+                write!(
+                    f,
+                    "{count} line(s) have specials (doors, lifts, ...) that won't work"
+                )
+            }
+            WadWarning::SectorSpecials(count) => {
+                write!(f, "{count} sector(s) have specials that won't work")
+            }
+            WadWarning::ClosedSectors(count) => {
+                write!(
+                    f,
+                    "{count} closed sector(s) (doors, ...) were opened a crack"
+                )
+            }
+            WadWarning::SameSectorBothSides(count) => write!(
+                f,
+                "left out {count} line(s) with the same sector on both sides"
+            ),
+            WadWarning::NoFrontSide(count) => {
+                write!(f, "left out {count} line(s) without a front side")
+            }
+            WadWarning::Things(count) => {
+                write!(f, "left out {count} thing(s) besides player starts")
+            }
+        }
+    }
+}
+
+impl WadWarning {
+    /// how many things it's about, 1 for ones that aren't a count
+    fn count(&self) -> usize {
+        match self {
+            WadWarning::Textures => 1,
+            WadWarning::LineSpecials(count)
+            | WadWarning::SectorSpecials(count)
+            | WadWarning::ClosedSectors(count)
+            | WadWarning::SameSectorBothSides(count)
+            | WadWarning::NoFrontSide(count)
+            | WadWarning::Things(count) => *count,
+        }
+    }
+}
+
+/// A map imported from a WAD, along with everything that didn't make it across.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WadMap {
+    pub map: Map,
+    pub warnings: Vec<WadWarning>,
+}
+
+struct Lump<'a> {
+    name: String,
+    data: &'a [u8],
+}
+
+fn u16_at(bytes: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([bytes[offset], bytes[offset + 1]])
+}
+
+fn i16_at(bytes: &[u8], offset: usize) -> i16 {
+    u16_at(bytes, offset) as i16
+}
+
+fn u32_at(bytes: &[u8], offset: usize) -> Option<usize> {
+    let bytes = bytes.get(offset..offset + 4)?;
+    Some(u32::from_le_bytes(bytes.try_into().unwrap()) as usize)
+}
+
+// names are padded out to 8 bytes with zeros
+fn name_at(bytes: &[u8], offset: usize) -> String {
+    let name = &bytes[offset..offset + 8];
+    let end = name.iter().position(|&b| b == 0).unwrap_or(8);
+    String::from_utf8_lossy(&name[..end]).to_ascii_uppercase()
+}
+
+fn lumps(wad: &[u8]) -> Result<Vec<Lump<'_>>, WadError> {
+    if !is_wad(wad) {
+        return Err(WadError::NotAWad);
+    }
+    let count = u32_at(wad, 4).ok_or(WadError::Truncated)?;
+    let directory = u32_at(wad, 8).ok_or(WadError::Truncated)?;
+    (0..count)
+        .map(|i| {
+            let entry = directory + i * 16;
+            let start = u32_at(wad, entry).ok_or(WadError::Truncated)?;
+            let size = u32_at(wad, entry + 4).ok_or(WadError::Truncated)?;
+            let name = wad
+                .get(entry + 8..entry + 16)
+                .map(|_| name_at(wad, entry + 8))
+                .ok_or(WadError::Truncated)?;
+            let data = wad.get(start..start + size).ok_or(WadError::Truncated)?;
+            Ok(Lump { name, data })
+        })
+        .collect()
+}
+
+// a map's marker is whatever comes right before its THINGS
+fn is_marker(lumps: &[Lump], i: usize) -> bool {
+    lumps.get(i + 1).is_some_and(|next| next.name == "THINGS")
+}
+
+/// The names of every map in a WAD (like `E1M1` or `MAP01`), in the order they're in.
+pub fn wad_map_names(wad: &[u8]) -> Result<Vec<String>, WadError> {
+    let lumps = lumps(wad)?;
+    Ok((0..lumps.len())
+        .filter(|&i| is_marker(&lumps, i))
+        .map(|i| lumps[i].name.clone())
+        .collect())
+}
+
+/// Imports a map out of a WAD, the first one if `name` is `None`.
+pub fn load_wad_map(wad: &[u8], name: Option<&str>) -> Result<WadMap, WadError> {
+    let lumps = lumps(wad)?;
+    let marker = match name {
+        Some(name) => (0..lumps.len())
+            .find(|&i| is_marker(&lumps, i) && lumps[i].name.eq_ignore_ascii_case(name))
+            .ok_or_else(|| WadError::NoSuchMap(name.to_string()))?,
+        None => (0..lumps.len())
+            .find(|&i| is_marker(&lumps, i))
+            .ok_or(WadError::NoMaps)?,
+    };
+    let map_name = lumps[marker].name.clone();
+    let map_lumps: Vec<&Lump> = lumps[marker + 1..]
+        .iter()
+        .take_while(|lump| MAP_LUMPS.contains(&lump.name.as_str()))
+        .collect();
+    if map_lumps.iter().any(|lump| lump.name == "BEHAVIOR") {
+        return Err(WadError::HexenFormat(map_name));
+    }
+
+    // every lump's records, checked to be whole
+    let records = |lump: &'static str, size: usize| {
+        let data = map_lumps
+            .iter()
+            .find(|l| l.name == lump)
+            .ok_or_else(|| WadError::MissingLump {
+                map: map_name.clone(),
+                lump,
+            })?
+            .data;
+        if data.len() % size != 0 {
+            return Err(WadError::BadLumpSize {
+                map: map_name.clone(),
+                lump,
+            });
+        }
+        Ok(data.chunks_exact(size).collect::<Vec<_>>())
+    };
+    let bad_index = |lump, record| WadError::BadIndex {
+        map: map_name.clone(),
+        lump,
+        record,
+    };
+    let vertices: Vec<Vec2> = records("VERTEXES", VERTEX_SIZE)?
+        .iter()
+        .map(|v| Vec2::new(i16_at(v, 0) as f32, i16_at(v, 2) as f32) / WAD_UNITS)
+        .collect();
+
+    let sector_records = records("SECTORS", SECTOR_SIZE)?;
+    let mut sectors: Vec<Sector> = sector_records
+        .iter()
+        .map(|s| Sector {
+            floor: i16_at(s, 0) as f32 / WAD_UNITS,
+            ceiling: i16_at(s, 2) as f32 / WAD_UNITS,
+            floor_color: DEFAULT_FLOOR_COLOR,
+            // sky flats are F_SKY1 in Doom, F_SKY in some others
+            ceiling_color: (!name_at(s, 12).starts_with("F_SKY")).then_some(DEFAULT_CEILING_COLOR),
+            light: (i16_at(s, 20) as f32 / 255.0).clamp(0.0, 1.0),
+            walls: Vec::new(),
+        })
+        .collect();
+    let sector_specials = sector_records.iter().filter(|s| u16_at(s, 22) != 0).count();
+    // nothing here can open a closed door again, and a map's floors have to be below their
+    // ceilings, so they get the smallest gap there is
+    let mut closed = 0;
+    for sector in &mut sectors {
+        if sector.ceiling <= sector.floor {
+            sector.ceiling = sector.floor + 1.0 / WAD_UNITS;
+            closed += 1;
+        }
+    }
+
+    // each sidedef's sector
+    let sidedefs: Vec<usize> = records("SIDEDEFS", SIDEDEF_SIZE)?
+        .iter()
+        .enumerate()
+        .map(|(i, side)| {
+            let sector = u16_at(side, 28) as usize;
+            if sector < sectors.len() {
+                Ok(sector)
+            } else {
+                Err(bad_index("SIDEDEFS", i))
+            }
+        })
+        .collect::<Result<_, _>>()?;
+    let side_sector = |side: u16, line: usize| match side {
+        NO_SIDEDEF => Ok(None),
+        side => sidedefs
+            .get(side as usize)
+            .copied()
+            .map(Some)
+            .ok_or_else(|| bad_index("LINEDEFS", line)),
+    };
+
+    let mut walls = Vec::new();
+    let (mut line_specials, mut same_sector, mut no_front) = (0, 0, 0);
+    for (i, line) in records("LINEDEFS", LINEDEF_SIZE)?.iter().enumerate() {
+        let vertex = |offset| {
+            vertices
+                .get(u16_at(line, offset) as usize)
+                .copied()
+                .ok_or_else(|| bad_index("LINEDEFS", i))
+        };
+        let (start, end) = (vertex(0)?, vertex(2)?);
+        let front = side_sector(u16_at(line, 10), i)?;
+        let back = side_sector(u16_at(line, 12), i)?;
+        if u16_at(line, 6) != 0 {
+            line_specials += 1;
+        }
+        let Some(front) = front else {
+            no_front += 1;
+            continue;
+        };
+        if back == Some(front) {
+            same_sector += 1;
+            continue;
+        }
+
+        let wall = walls.len();
+        walls.push(Wall::new(LineSegment::new(start, end)));
+        sectors[front].walls.push(wall);
+        if let Some(back) = back {
+            sectors[back].walls.push(wall);
+        }
+    }
+
+    if walls.is_empty() {
+        return Err(WadError::NoWalls(map_name));
+    }
+
+    let mut warnings = Vec::new();
+    let things = records("THINGS", THING_SIZE)?;
+    let start = things
+        .iter()
+        .find(|thing| u16_at(thing, 6) == PLAYER_1_START)
+        .ok_or_else(|| WadError::NoPlayerStart(map_name.clone()))?;
+    let spawn = |thing: &[u8]| Spawn {
+        position: Vec2::new(i16_at(thing, 0) as f32, i16_at(thing, 2) as f32) / WAD_UNITS,
+        // Doom's angles are degrees anticlockwise from east
+        yaw: (90.0 - i16_at(thing, 4) as f32).to_radians(),
+        height: None,
+    };
+    let mut named_spawns = Vec::new();
+    let mut deathmatch = 0;
+    for thing in &things {
+        let name = match u16_at(thing, 6) {
+            kind @ 2..=PLAYER_4_START => format!("player{kind}"),
+            DEATHMATCH_START => {
+                deathmatch += 1;
+                format!("deathmatch{deathmatch}")
+            }
+            _ => continue,
+        };
+        named_spawns.push((name, spawn(thing)));
+    }
+
+    warnings.push(WadWarning::Textures);
+    let counted = [
+        WadWarning::LineSpecials(line_specials),
+        WadWarning::SectorSpecials(sector_specials),
+        WadWarning::ClosedSectors(closed),
+        WadWarning::SameSectorBothSides(same_sector),
+        WadWarning::NoFrontSide(no_front),
+        WadWarning::Things(things.len() - 1 - named_spawns.len()),
+    ];
+    warnings.extend(counted.into_iter().filter(|warning| warning.count() > 0));
+
+    Ok(WadMap {
+        map: Map {
+            spawn: spawn(start),
+            named_spawns,
+            walls,
+            sectors,
+            textures: Vec::new(),
+            palette: Vec::new(),
+            lighting: Lighting::default(),
+            entities: Vec::new(),
+            metadata: MapMetadata {
+                name: Some(map_name),
+                author: None,
+                source: None,
+                lines: None,
+            },
+        },
+        warnings,
+    })
+}
+
+/// whether a file's first bytes look like a WAD
+pub fn is_wad(contents: &[u8]) -> bool {
+    contents.starts_with(b"IWAD") || contents.starts_with(b"PWAD")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Puts a WAD together out of (name, data) lumps.
+    fn build_wad(lumps: &[(&str, Vec<u8>)]) -> Vec<u8> {
+        let mut wad = b"PWAD".to_vec();
+        wad.extend((lumps.len() as u32).to_le_bytes());
+        let data_len: usize = lumps.iter().map(|(_, data)| data.len()).sum();
+        wad.extend(((12 + data_len) as u32).to_le_bytes());
+        for (_, data) in lumps {
+            wad.extend(data);
+        }
+        let mut offset = 12;
+        for (name, data) in lumps {
+            wad.extend((offset as u32).to_le_bytes());
+            wad.extend((data.len() as u32).to_le_bytes());
+            let mut padded = [0; 8];
+            padded[..name.len()].copy_from_slice(name.as_bytes());
+            wad.extend(padded);
+            offset += data.len();
+        }
+        wad
+    }
+
+    fn words(values: &[i16]) -> Vec<u8> {
+        values.iter().flat_map(|v| v.to_le_bytes()).collect()
+    }
+
+    fn sidedef(sector: i16) -> Vec<u8> {
+        let mut side = words(&[0, 0]);
+        side.extend(b"-\0\0\0\0\0\0\0-\0\0\0\0\0\0\0STARTAN3");
+        side.extend(words(&[sector]));
+        side
+    }
+
+    fn sector(floor: i16, ceiling: i16, ceiling_flat: &[u8; 8], light: i16) -> Vec<u8> {
+        let mut sector = words(&[floor, ceiling]);
+        sector.extend(b"FLOOR4_8");
+        sector.extend(ceiling_flat);
+        sector.extend(words(&[light, 0, 0]));
+        sector
+    }
+
+    /// a 192 unit square room with a 96 unit square raised platform in the middle, open to the
+    /// sky, and an imp
+    fn room_wad() -> Vec<u8> {
+        room_wad_with_platform_ceiling(128)
+    }
+
+    fn room_wad_with_platform_ceiling(platform_ceiling: i16) -> Vec<u8> {
+        let vertices = words(&[
+            0, 0, 192, 0, 192, 192, 0, 192, // the room
+            48, 48, 144, 48, 144, 144, 48, 144, // the platform
+        ]);
+        // v1, v2, flags, special, tag, front, back
+        let mut linedefs = Vec::new();
+        for (v1, v2) in [(0, 1), (1, 2), (2, 3), (3, 0)] {
+            linedefs.extend(words(&[v1, v2, 1, 0, 0, 0, -1]));
+        }
+        for (v1, v2) in [(4, 5), (5, 6), (6, 7), (7, 4)] {
+            linedefs.extend(words(&[v1, v2, 4, 0, 0, 1, 2]));
+        }
+        // a door special on the last one, and a line with the room on both sides
+        linedefs[7 * 14 + 6..7 * 14 + 8].copy_from_slice(&1i16.to_le_bytes());
+        linedefs.extend(words(&[0, 2, 4, 0, 0, 0, 0]));
+
+        let mut sidedefs = sidedef(0);
+        sidedefs.extend(sidedef(1));
+        sidedefs.extend(sidedef(0));
+        let mut sectors = sector(0, 128, b"F_SKY1\0\0", 255);
+        sectors.extend(sector(24, platform_ceiling, b"CEIL3_5\0", 128));
+        // player 1 start facing east, a deathmatch start facing north, and an imp
+        let mut things = words(&[96, 24, 0, 1, 7]);
+        things.extend(words(&[48, 48, 90, 11, 7]));
+        things.extend(words(&[96, 168, 270, 3001, 7]));
+
+        build_wad(&[
+            ("E1M1", Vec::new()),
+            ("THINGS", things),
+            ("LINEDEFS", linedefs),
+            ("SIDEDEFS", sidedefs),
+            ("VERTEXES", vertices),
+            ("SECTORS", sectors),
+            ("E1M2", Vec::new()),
+            ("THINGS", Vec::new()),
+        ])
+    }
+
+    #[test]
+    fn test_imports_a_room() {
+        let wad = room_wad();
+        assert_eq!(wad_map_names(&wad).unwrap(), ["E1M1", "E1M2"]);
+        let WadMap { map, warnings } = load_wad_map(&wad, None).unwrap();
+
+        assert_eq!(map.metadata.name.as_deref(), Some("E1M1"));
+        assert_eq!(map.spawn.position, Vec2::new(2.0, 0.5));
+        // facing east
+        assert!((map.spawn.yaw - std::f32::consts::FRAC_PI_2).abs() < 1e-6);
+        assert_eq!(
+            map.named_spawn("deathmatch1"),
+            Some(&Spawn::new(Vec2::new(1.0, 1.0)))
+        );
+        assert_eq!(map.walls.len(), 8);
+        assert_eq!(map.walls[1].line, LineSegment::from((4.0, 0.0, 4.0, 4.0)));
+        assert_eq!(map.sectors[0].walls, [0, 1, 2, 3, 4, 5, 6, 7]);
+        assert_eq!(map.sectors[1].walls, [4, 5, 6, 7]);
+        assert_eq!(map.sectors[0].ceiling_color, None);
+        assert_eq!(map.sectors[1].floor, 0.5);
+        assert!((map.sectors[1].light - 128.0 / 255.0).abs() < 1e-6);
+        // the platform's the smaller sector, so wins where they overlap
+        assert_eq!(map.sector_at(Vec2::new(2.0, 2.0)), Some(1));
+        assert_eq!(map.sector_at(Vec2::new(0.5, 2.0)), Some(0));
+
+        assert_eq!(
+            warnings,
+            [
+                WadWarning::Textures,
+                WadWarning::LineSpecials(1),
+                WadWarning::SameSectorBothSides(1),
+                WadWarning::Things(1),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_opens_closed_sectors() {
+        let WadMap { map, warnings } =
+            load_wad_map(&room_wad_with_platform_ceiling(24), None).unwrap();
+        assert_eq!(map.sectors[1].ceiling, 25.0 / WAD_UNITS);
+        assert!(warnings.contains(&WadWarning::ClosedSectors(1)));
+        assert_eq!(map.validate(), Ok(()));
+    }
+
+    #[test]
+    fn test_wad_errors() {
+        let wad = room_wad();
+        assert_eq!(
+            load_wad_map(&wad, Some("MAP01")),
+            Err(WadError::NoSuchMap("MAP01".to_string()))
+        );
+        assert_eq!(
+            load_wad_map(&wad, Some("e1m2")),
+            Err(WadError::MissingLump {
+                map: "E1M2".to_string(),
+                lump: "VERTEXES"
+            })
+        );
+        assert_eq!(load_wad_map(b"5 5\n", None), Err(WadError::NotAWad));
+        assert_eq!(
+            load_wad_map(&wad[..wad.len() - 20], None),
+            Err(WadError::Truncated)
+        );
+    }
+
+    #[test]
+    fn test_rejects_maps_without_any_walls_left() {
+        // the only line has the same sector on both sides, so gets left out
+        let wad = build_wad(&[
+            ("MAP01", Vec::new()),
+            ("THINGS", words(&[0, 0, 0, 1, 7])),
+            ("LINEDEFS", words(&[0, 1, 4, 0, 0, 0, 0])),
+            ("SIDEDEFS", sidedef(0)),
+            ("VERTEXES", words(&[0, 0, 64, 0])),
+            ("SECTORS", sector(0, 128, b"CEIL3_5\0", 255)),
+        ]);
+        assert_eq!(
+            load_wad_map(&wad, None),
+            Err(WadError::NoWalls("MAP01".to_string()))
+        );
+    }
+}
@@ -0,0 +1,133 @@
+//! Renders the shipped maps from fixed spots and compares them against reference images in
+//! `tests/golden`, so changes to projection, clipping or BSP ordering show up as failures.
+//! Every spot is rendered with the BSP tree built two different ways as well, since how the
+//! tree's partitioned should never change what ends up on screen.
+//!
+//! After an intentional change to how things look, regenerate the references with
+//! `UPDATE_GOLDEN=1 cargo test -p shared --test golden` and check the new images by eye.
+
+use std::path::{Path, PathBuf};
+
+use shared::{FrameBuffer, GameState, PartitionChooser, Vec2, load_any_map_from_path};
+
+const WIDTH: u32 = 160;
+const HEIGHT: u32 = 120;
+/// how far off a channel can be before the pixel counts as different
+const CHANNEL_TOLERANCE: u8 = 8;
+/// how many pixels can be different before the frame does, for rasterisation wobble on edges
+const MAX_DIFFERENT: f32 = 0.005;
+
+/// (map, position, yaw, reference name)
+const CASES: &[(&str, (f32, f32), f32, &str)] = &[
+    ("map01.txt", (7.1, 1.4), 0.0, "map01_spawn"),
+    ("map01.txt", (13.0, 9.0), 0.8, "map01_bend"),
+    ("map02.txt", (0.0, 0.0), 0.0, "map02_spawn"),
+    ("map02.txt", (0.0, 0.0), 2.5, "map02_behind"),
+    ("map04.txt", (7.0, 2.0), 0.8, "map04_inside"),
+    ("maze.txt", (2.0, 2.0), 0.0, "maze_spawn"),
+    ("maze.txt", (2.0, 2.0), 1.5, "maze_side"),
+    ("test_map.txt", (2.0, 2.0), 0.6, "test_map_room"),
+];
+
+fn repo_root() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("..")
+}
+
+fn golden_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden")
+}
+
+fn render(map: &str, pos: (f32, f32), yaw: f32, chooser: &PartitionChooser) -> FrameBuffer {
+    let map = load_any_map_from_path(repo_root().join(map), None)
+        .unwrap()
+        .map;
+    let mut game_state = GameState::from_map_with_chooser(map, chooser);
+    game_state.load_textures().unwrap();
+    game_state.place_camera(Vec2::new(pos.0, pos.1), yaw);
+    game_state.render_offscreen(WIDTH, HEIGHT)
+}
+
+fn load_reference(path: &Path) -> Option<(u32, u32, Vec<u8>)> {
+    let file = std::fs::File::open(path).ok()?;
+    let mut reader = png::Decoder::new(std::io::BufReader::new(file))
+        .read_info()
+        .unwrap();
+    let mut buf = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buf).unwrap();
+    assert_eq!(
+        info.color_type,
+        png::ColorType::Rgba,
+        "{} isn't RGBA",
+        path.display()
+    );
+    buf.truncate(info.buffer_size());
+    Some((info.width, info.height, buf))
+}
+
+/// the fraction of pixels that are further apart than the tolerance
+fn fraction_different(a: &[u8], b: &[u8]) -> f32 {
+    let different = a
+        .chunks_exact(4)
+        .zip(b.chunks_exact(4))
+        .filter(|(a, b)| {
+            a.iter()
+                .zip(*b)
+                .any(|(a, b)| a.abs_diff(*b) > CHANNEL_TOLERANCE)
+        })
+        .count();
+    different as f32 / (a.len() / 4) as f32
+}
+
+#[test]
+fn test_golden_images() {
+    let update = std::env::var_os("UPDATE_GOLDEN").is_some();
+    let mut failures = Vec::new();
+    // references are made with the first of these
+    let choosers = [
+        ("default", PartitionChooser::default()),
+        ("first", PartitionChooser::FIRST),
+    ];
+
+    for &(map, pos, yaw, case) in CASES {
+        let reference = golden_dir().join(format!("{case}.png"));
+        if update {
+            render(map, pos, yaw, &choosers[0].1)
+                .save(&reference)
+                .unwrap();
+            continue;
+        }
+
+        let Some((width, height, expected)) = load_reference(&reference) else {
+            failures.push(format!(
+                "{case}: no reference image at {}",
+                reference.display()
+            ));
+            continue;
+        };
+        if (width, height) != (WIDTH, HEIGHT) {
+            failures.push(format!("{case}: reference is {width}x{height}"));
+            continue;
+        }
+        for (tree, chooser) in &choosers {
+            let frame = render(map, pos, yaw, chooser);
+            let different = fraction_different(frame.pixels(), &expected);
+            if different > MAX_DIFFERENT {
+                // keep what actually got drawn around to compare against
+                let actual =
+                    Path::new(env!("CARGO_TARGET_TMPDIR")).join(format!("{case}_{tree}.png"));
+                frame.save(&actual).unwrap();
+                failures.push(format!(
+                    "{case} with the {tree} tree: {:.2}% of pixels differ, got {}",
+                    different * 100.0,
+                    actual.display()
+                ));
+            }
+        }
+    }
+
+    assert!(
+        failures.is_empty(),
+        "golden images differ:\n  {}",
+        failures.join("\n  ")
+    );
+}